@@ -95,6 +95,7 @@ pub enum MetadataError {
 /// targets = [ "x86_64-apple-darwin", "x86_64-pc-windows-msvc" ]
 /// rustc-args = [ "--example-rustc-arg" ]
 /// rustdoc-args = [ "--example-rustdoc-arg" ]
+/// toolchain = "nightly-2024-01-01"
 /// ```
 ///
 /// You can define one or more fields in your `Cargo.toml`.
@@ -138,6 +139,12 @@ pub struct Metadata {
     /// These cannot be a subcommand, they may only be options.
     #[serde(default)]
     cargo_args: Vec<String>,
+
+    /// (experimental) The toolchain the crate needs to be documented with,
+    /// for example `nightly-2024-01-01`.
+    ///
+    /// docs.rs can't pin toolchains per crate yet, see [`Metadata::toolchain`].
+    toolchain: Option<String>,
 }
 
 /// The targets that should be built for a crate.
@@ -191,6 +198,14 @@ impl Metadata {
         Metadata::from_str(&buf).map_err(Into::into)
     }
 
+    /// The toolchain the crate asked to be documented with, if any.
+    ///
+    /// This is only a hint: docs.rs doesn't honor it yet, and builds with its
+    /// current toolchain.
+    pub fn toolchain(&self) -> Option<&str> {
+        self.toolchain.as_deref()
+    }
+
     /// Return the targets that should be built.
     ///
     /// The `default_target` will never be one of the `other_targets`.
@@ -663,6 +678,21 @@ mod test_calculations {
         args
     }
 
+    #[test]
+    fn test_toolchain() {
+        let manifest = r#"
+            [package]
+            name = "test"
+
+            [package.metadata.docs.rs]
+            toolchain = "nightly-2024-01-01"
+        "#;
+        let metadata = Metadata::from_str(manifest).unwrap();
+        assert_eq!(metadata.toolchain(), Some("nightly-2024-01-01"));
+
+        assert!(Metadata::default().toolchain().is_none());
+    }
+
     #[test]
     fn test_defaults() {
         let metadata = Metadata::default();
@@ -0,0 +1,4 @@
+CREATE TABLE crate_redirects (
+    from_name VARCHAR(255) PRIMARY KEY,
+    to_name VARCHAR(255) NOT NULL
+);
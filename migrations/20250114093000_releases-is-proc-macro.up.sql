@@ -0,0 +1 @@
+ALTER TABLE releases ADD COLUMN is_proc_macro BOOLEAN;
@@ -0,0 +1,20 @@
+UPDATE builds SET build_status = 'failure' WHERE build_status = 'skipped';
+UPDATE release_build_status SET build_status = 'failure' WHERE build_status = 'skipped';
+
+ALTER TYPE build_status RENAME TO build_status_old;
+
+CREATE TYPE build_status AS ENUM (
+    'in_progress',
+    'success',
+    'failure'
+);
+
+ALTER TABLE builds ALTER build_status
+TYPE build_status
+USING build_status::text::build_status;
+
+ALTER TABLE release_build_status ALTER build_status
+TYPE build_status
+USING build_status::text::build_status;
+
+DROP TYPE build_status_old;
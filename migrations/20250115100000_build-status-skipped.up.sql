@@ -0,0 +1 @@
+ALTER TYPE build_status ADD VALUE 'skipped';
@@ -0,0 +1,8 @@
+CREATE TABLE permalinks (
+    id SERIAL PRIMARY KEY,
+    short_id VARCHAR(16) NOT NULL UNIQUE,
+    release_id INTEGER NOT NULL REFERENCES releases(id) ON DELETE CASCADE,
+    path TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (release_id, path)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE builds
+    DROP COLUMN build_duration_ms,
+    DROP COLUMN peak_memory;
@@ -0,0 +1,3 @@
+ALTER TABLE builds
+    ADD COLUMN build_duration_ms BIGINT,
+    ADD COLUMN peak_memory BIGINT;
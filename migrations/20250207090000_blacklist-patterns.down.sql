@@ -0,0 +1 @@
+DROP TABLE blacklisted_crate_patterns;
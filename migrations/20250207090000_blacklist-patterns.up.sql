@@ -0,0 +1,3 @@
+CREATE TABLE blacklisted_crate_patterns (
+    pattern VARCHAR PRIMARY KEY
+);
@@ -0,0 +1,3 @@
+ALTER TABLE crates
+    DROP COLUMN keywords,
+    DROP COLUMN categories;
@@ -0,0 +1,3 @@
+ALTER TABLE crates
+    ADD COLUMN keywords TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN categories TEXT[] NOT NULL DEFAULT '{}';
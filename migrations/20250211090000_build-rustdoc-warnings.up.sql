@@ -0,0 +1 @@
+ALTER TABLE builds ADD COLUMN rustdoc_warnings JSONB;
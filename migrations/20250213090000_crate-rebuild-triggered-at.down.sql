@@ -0,0 +1 @@
+ALTER TABLE crates DROP COLUMN rebuild_triggered_at;
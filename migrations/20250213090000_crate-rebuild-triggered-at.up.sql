@@ -0,0 +1 @@
+ALTER TABLE crates ADD COLUMN rebuild_triggered_at TIMESTAMPTZ;
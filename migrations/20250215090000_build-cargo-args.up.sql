@@ -0,0 +1 @@
+ALTER TABLE builds ADD COLUMN cargo_args TEXT[];
@@ -0,0 +1 @@
+ALTER TABLE crates ADD COLUMN reverse_dependency_count INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+DROP TABLE toolchain_history;
@@ -0,0 +1,5 @@
+CREATE TABLE toolchain_history (
+    id SERIAL PRIMARY KEY,
+    toolchain TEXT NOT NULL,
+    changed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
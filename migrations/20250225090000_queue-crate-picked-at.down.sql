@@ -0,0 +1 @@
+ALTER TABLE queue DROP COLUMN crate_picked_at;
@@ -0,0 +1 @@
+ALTER TABLE queue ADD COLUMN crate_picked_at TIMESTAMP WITH TIME ZONE;
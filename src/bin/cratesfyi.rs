@@ -11,8 +11,8 @@ use docs_rs::cdn::CdnBackend;
 use docs_rs::db::{self, add_path_into_database, CrateId, Overrides, Pool};
 use docs_rs::repositories::RepositoryStatsUpdater;
 use docs_rs::utils::{
-    get_config, get_crate_pattern_and_priority, list_crate_priorities, queue_builder,
-    remove_crate_priority, set_config, set_crate_priority, ConfigName,
+    crate_priorities_to_json, get_config, get_crate_pattern_and_priority, list_crate_priorities,
+    queue_builder, remove_crate_priority, set_crate_priority, ConfigName,
 };
 use docs_rs::{
     start_background_metrics_webserver, start_web_server, AsyncBuildQueue, AsyncStorage,
@@ -127,6 +127,24 @@ enum Toggle {
     Disabled,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "snake_case")]
+enum ListFormat {
+    /// one entry per line
+    Text,
+    /// a JSON array of entries
+    Json,
+}
+
+impl ListFormat {
+    fn render(self, entries: &[String]) -> Result<String> {
+        Ok(match self {
+            Self::Text => entries.join("\n"),
+            Self::Json => serde_json::to_string(entries)?,
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Parser)]
 #[command(
     about = env!("CARGO_PKG_DESCRIPTION"),
@@ -155,6 +173,13 @@ enum CommandLine {
             value_enum
         )]
         repository_stats_updater: Toggle,
+        /// Enable or disable the periodic update of the reverse dependency counts
+        #[arg(
+            long = "reverse-dependency-updater",
+            default_value = "enabled",
+            value_enum
+        )]
+        reverse_dependency_updater: Toggle,
         #[arg(long = "cdn-invalidator", default_value = "enabled", value_enum)]
         cdn_invalidator: Toggle,
         #[arg(long = "queue-rebuilds", default_value = "enabled", value_enum)]
@@ -195,12 +220,16 @@ impl CommandLine {
             Self::StartRegistryWatcher {
                 metric_server_socket_addr,
                 repository_stats_updater,
+                reverse_dependency_updater,
                 cdn_invalidator,
                 queue_rebuilds,
             } => {
                 if repository_stats_updater == Toggle::Enabled {
                     docs_rs::utils::daemon::start_background_repository_stats_updater(&ctx)?;
                 }
+                if reverse_dependency_updater == Toggle::Enabled {
+                    docs_rs::utils::daemon::start_background_reverse_dependency_updater(&ctx)?;
+                }
                 if cdn_invalidator == Toggle::Enabled {
                     docs_rs::utils::daemon::start_background_cdn_invalidator(&ctx)?;
                 }
@@ -265,6 +294,19 @@ enum QueueSubcommand {
         build_priority: i32,
     },
 
+    /// Change the priority of a release that is already queued
+    Reprioritize {
+        /// Name of the queued crate
+        #[arg(name = "CRATE_NAME")]
+        crate_name: String,
+        /// Version of the queued crate
+        #[arg(name = "CRATE_VERSION")]
+        crate_version: String,
+        /// New priority of the build (lower is built earlier)
+        #[arg(name = "BUILD_PRIORITY", allow_negative_numbers = true)]
+        build_priority: i32,
+    },
+
     /// Interactions with build queue priorities
     DefaultPriority {
         #[command(subcommand)]
@@ -302,6 +344,18 @@ impl QueueSubcommand {
                 ctx.config()?.registry_url.as_deref(),
             )?,
 
+            Self::Reprioritize {
+                crate_name,
+                crate_version,
+                build_priority,
+            } => {
+                if build_queue.reprioritize(&crate_name, &crate_version, build_priority)? {
+                    println!("Set priority of {crate_name}-{crate_version} to {build_priority}");
+                } else {
+                    println!("{crate_name}-{crate_version} is not queued");
+                }
+            }
+
             Self::GetLastSeenReference => {
                 if let Some(reference) = build_queue.last_seen_reference()? {
                     println!("Last seen reference: {reference}");
@@ -339,7 +393,11 @@ enum PrioritySubcommand {
     Get { crate_name: String },
 
     /// List priorities for all patterns
-    List,
+    List {
+        /// Print the priorities as a JSON array instead of a table
+        #[arg(long)]
+        json: bool,
+    },
 
     /// Set all crates matching a pattern to a priority level
     Set {
@@ -364,9 +422,14 @@ impl PrioritySubcommand {
         ctx.runtime()?.block_on(async move {
             let mut conn = ctx.pool()?.get_async().await?;
             match self {
-                Self::List => {
-                    for (pattern, priority) in list_crate_priorities(&mut conn).await? {
-                        println!("{pattern:>20} : {priority:>3}");
+                Self::List { json } => {
+                    let priorities = list_crate_priorities(&mut conn).await?;
+                    if json {
+                        println!("{}", crate_priorities_to_json(&priorities));
+                    } else {
+                        for (pattern, priority) in priorities {
+                            println!("{pattern:>20} : {priority:>3}");
+                        }
                     }
                 }
 
@@ -418,6 +481,11 @@ enum BuildSubcommand {
         /// Build a crate at a specific path
         #[arg(short = 'l', long = "local", conflicts_with_all(&["CRATE_NAME", "CRATE_VERSION"]))]
         local: Option<PathBuf>,
+
+        /// Additionally write the unpacked documentation and a `build-report.json`
+        /// into this directory
+        #[arg(long = "output-dir")]
+        output_dir: Option<PathBuf>,
     },
 
     /// update the currently installed rustup toolchain
@@ -435,7 +503,11 @@ enum BuildSubcommand {
     },
 
     /// Locks the daemon, preventing it from building new crates
-    Lock,
+    Lock {
+        /// Reason for the lock, shown on the build queue page
+        #[arg(long)]
+        reason: Option<String>,
+    },
 
     /// Unlocks the daemon to continue building new crates
     Unlock,
@@ -451,8 +523,10 @@ impl BuildSubcommand {
                 crate_name,
                 crate_version,
                 local,
+                output_dir,
             } => {
                 let mut builder = rustwide_builder()?;
+                builder.set_output_dir(output_dir);
 
                 if let Some(path) = local {
                     builder
@@ -518,13 +592,15 @@ impl BuildSubcommand {
                         .get_async()
                         .await
                         .context("failed to get a database connection")?;
-                    set_config(&mut conn, ConfigName::Toolchain, toolchain_name)
+                    db::set_toolchain(&mut conn, &toolchain_name)
                         .await
                         .context("failed to set toolchain in database")
                 })?;
             }
 
-            Self::Lock => build_queue.lock().context("Failed to lock")?,
+            Self::Lock { reason } => build_queue
+                .lock(reason.as_deref())
+                .context("Failed to lock")?,
             Self::Unlock => build_queue.unlock().context("Failed to unlock")?,
         }
 
@@ -574,6 +650,23 @@ enum DatabaseSubcommand {
         command: BlacklistSubcommand,
     },
 
+    /// Redirect a crate that doesn't exist (anymore) to a renamed crate
+    AddCrateRedirect {
+        /// Name of the crate to redirect from
+        #[arg(name = "FROM")]
+        from: String,
+        /// Name of the crate to redirect to
+        #[arg(name = "TO")]
+        to: String,
+    },
+
+    /// Remove the redirect for a crate
+    RemoveCrateRedirect {
+        /// Name of the crate to remove the redirect for
+        #[arg(name = "FROM")]
+        from: String,
+    },
+
     /// Limit overrides operations
     Limits {
         #[command(subcommand)]
@@ -586,6 +679,14 @@ enum DatabaseSubcommand {
         #[arg(long)]
         dry_run: bool,
     },
+
+    /// Compares the database with the index and prints a summary of the drift,
+    /// without resolving anything
+    ConsistencyReport {
+        /// Also list releases whose rustdoc archive is missing from storage
+        #[arg(long)]
+        check_storage: bool,
+    },
 }
 
 impl DatabaseSubcommand {
@@ -685,12 +786,35 @@ impl DatabaseSubcommand {
                 .context("failed to delete the crate")?,
             Self::Blacklist { command } => command.handle_args(ctx)?,
 
+            Self::AddCrateRedirect { from, to } => ctx
+                .runtime()?
+                .block_on(async move {
+                    let mut conn = ctx.pool()?.get_async().await?;
+                    db::crate_redirects::add_redirect(&mut conn, &from, &to).await
+                })
+                .context("failed to add crate redirect")?,
+
+            Self::RemoveCrateRedirect { from } => ctx
+                .runtime()?
+                .block_on(async move {
+                    let mut conn = ctx.pool()?.get_async().await?;
+                    db::crate_redirects::remove_redirect(&mut conn, &from).await
+                })
+                .context("failed to remove crate redirect")?,
+
             Self::Limits { command } => command.handle_args(ctx)?,
 
             Self::Synchronize { dry_run } => {
                 ctx.runtime()?
                     .block_on(docs_rs::utils::consistency::run_check(&ctx, dry_run))?;
             }
+
+            Self::ConsistencyReport { check_storage } => {
+                let report = ctx
+                    .runtime()?
+                    .block_on(docs_rs::utils::consistency::run_report(&ctx, check_storage))?;
+                print!("{report}");
+            }
         }
         Ok(())
     }
@@ -769,7 +893,11 @@ impl LimitsSubcommand {
 #[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
 enum BlacklistSubcommand {
     /// List all crates on the blacklist
-    List,
+    List {
+        /// Output format of the list
+        #[arg(long, value_enum, default_value_t = ListFormat::Text)]
+        format: ListFormat,
+    },
 
     /// Add a crate to the blacklist
     Add {
@@ -784,6 +912,27 @@ enum BlacklistSubcommand {
         #[arg(name = "CRATE_NAME")]
         crate_name: String,
     },
+
+    /// List all patterns on the blacklist
+    ListPatterns {
+        /// Output format of the list
+        #[arg(long, value_enum, default_value_t = ListFormat::Text)]
+        format: ListFormat,
+    },
+
+    /// Blacklist all crates matching a pattern
+    AddPattern {
+        /// Postgres LIKE pattern, like `evil-%`
+        #[arg(name = "PATTERN")]
+        pattern: String,
+    },
+
+    /// Remove a pattern from the blacklist
+    RemovePattern {
+        /// Pattern
+        #[arg(name = "PATTERN")]
+        pattern: String,
+    },
 }
 
 impl BlacklistSubcommand {
@@ -791,12 +940,12 @@ impl BlacklistSubcommand {
         ctx.runtime()?.block_on(async {
             let conn = &mut *ctx.pool()?.get_async().await?;
             match self {
-                Self::List => {
+                Self::List { format } => {
                     let crates = db::blacklist::list_crates(conn)
                         .await
                         .context("failed to list crates on blacklist")?;
 
-                    println!("{}", crates.join("\n"));
+                    println!("{}", format.render(&crates)?);
                 }
 
                 Self::Add { crate_name } => db::blacklist::add_crate(conn, &crate_name)
@@ -806,6 +955,22 @@ impl BlacklistSubcommand {
                 Self::Remove { crate_name } => db::blacklist::remove_crate(conn, &crate_name)
                     .await
                     .context("failed to remove crate from blacklist")?,
+
+                Self::ListPatterns { format } => {
+                    let patterns = db::blacklist::list_patterns(conn)
+                        .await
+                        .context("failed to list patterns on blacklist")?;
+
+                    println!("{}", format.render(&patterns)?);
+                }
+
+                Self::AddPattern { pattern } => db::blacklist::add_pattern(conn, &pattern)
+                    .await
+                    .context("failed to add pattern to blacklist")?,
+
+                Self::RemovePattern { pattern } => db::blacklist::remove_pattern(conn, &pattern)
+                    .await
+                    .context("failed to remove pattern from blacklist")?,
             }
             Ok(())
         })
@@ -903,23 +1068,19 @@ impl Context for BinContext {
                 .enable_all()
                 .build()?
         };
-        fn index(self) -> Index = {
-            let config = self.config()?;
-            let path = config.registry_index_path.clone();
-            if let Some(registry_url) = config.registry_url.clone() {
-                Index::from_url(path, registry_url)
-            } else {
-                Index::new(path)
-            }?
-        };
+        fn index(self) -> Index = Index::from_config(&self.config()?)?;
         fn registry_api(self) -> RegistryApi = {
             let config = self.config()?;
-            RegistryApi::new(config.registry_api_host.clone(), config.crates_io_api_call_retries)?
+            RegistryApi::new(
+                config.registry_api_host.clone(),
+                config.crates_io_api_call_retries,
+                config.max_upstream_body_bytes,
+            )?
         };
         fn repository_stats_updater(self) -> RepositoryStatsUpdater = {
             let config = self.config()?;
             let pool = self.pool()?;
-            RepositoryStatsUpdater::new(&config, pool)
+            RepositoryStatsUpdater::new(&config, pool, self.instance_metrics()?)
         };
     }
 
@@ -970,3 +1131,27 @@ impl Context for BinContext {
             .clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_format_json() {
+        let crates = vec!["crate A".to_string(), "crate B".to_string()];
+        assert_eq!(
+            ListFormat::Json.render(&crates).unwrap(),
+            r#"["crate A","crate B"]"#
+        );
+        assert_eq!(ListFormat::Json.render(&[]).unwrap(), "[]");
+    }
+
+    #[test]
+    fn list_format_text() {
+        let crates = vec!["crate A".to_string(), "crate B".to_string()];
+        assert_eq!(
+            ListFormat::Text.render(&crates).unwrap(),
+            "crate A\ncrate B"
+        );
+    }
+}
@@ -92,9 +92,14 @@ impl AsyncBuildQueue {
     ) -> Result<()> {
         let mut conn = self.db.get_async().await?;
 
+        // new versions inherit when the crate was last picked from the queue,
+        // see `process_next_crate`.
         sqlx::query!(
-            "INSERT INTO queue (name, version, priority, registry)
-                 VALUES ($1, $2, $3, $4)
+            "INSERT INTO queue (name, version, priority, registry, crate_picked_at)
+                 VALUES (
+                    $1, $2, $3, $4,
+                    (SELECT MAX(crate_picked_at) FROM queue WHERE name = $1)
+                 )
                  ON CONFLICT (name, version) DO UPDATE
                     SET priority = EXCLUDED.priority,
                         registry = EXCLUDED.registry,
@@ -168,7 +173,12 @@ impl AsyncBuildQueue {
             "SELECT id, name, version, priority, registry
                  FROM queue
                  WHERE attempt < $1
-                 ORDER BY priority ASC, attempt ASC, id ASC",
+                 ORDER BY
+                    priority ASC,
+                    ROW_NUMBER() OVER (PARTITION BY name, priority ORDER BY id) ASC,
+                    crate_picked_at ASC NULLS FIRST,
+                    attempt ASC,
+                    id ASC",
             self.max_attempts
         )
         .fetch_all(&mut *conn)
@@ -193,6 +203,31 @@ impl AsyncBuildQueue {
         .await?
         .is_some())
     }
+
+    /// Change the priority of a queued release.
+    ///
+    /// Returns `false` when the release is not in the queue.
+    #[context("error trying to reprioritize {name}-{version}")]
+    pub async fn reprioritize(&self, name: &str, version: &str, priority: i32) -> Result<bool> {
+        let mut conn = self.db.get_async().await?;
+
+        Ok(sqlx::query!(
+            "UPDATE queue
+             SET priority = $1
+             WHERE
+                attempt < $2 AND
+                name = $3 AND
+                version = $4",
+            priority,
+            self.max_attempts,
+            name,
+            version,
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected()
+            > 0)
+    }
 }
 
 /// Locking functions.
@@ -206,15 +241,25 @@ impl AsyncBuildQueue {
             .unwrap_or(false))
     }
 
+    /// the reason the queue was locked with, if any.
+    pub async fn lock_reason(&self) -> Result<Option<String>> {
+        let mut conn = self.db.get_async().await?;
+        get_config::<String>(&mut conn, ConfigName::QueueLockReason).await
+    }
+
     /// lock the queue. Daemon will check this lock and stop operating if it exists.
-    pub async fn lock(&self) -> Result<()> {
+    ///
+    /// The optional reason is shown on the build queue page while the lock is active.
+    pub async fn lock(&self, reason: Option<&str>) -> Result<()> {
         let mut conn = self.db.get_async().await?;
+        set_config(&mut conn, ConfigName::QueueLockReason, reason).await?;
         set_config(&mut conn, ConfigName::QueueLocked, true).await
     }
 
     /// unlock the queue.
     pub async fn unlock(&self) -> Result<()> {
         let mut conn = self.db.get_async().await?;
+        set_config(&mut conn, ConfigName::QueueLockReason, None::<&str>).await?;
         set_config(&mut conn, ConfigName::QueueLocked, false).await
     }
 }
@@ -435,11 +480,18 @@ impl BuildQueue {
         self.runtime
             .block_on(self.inner.set_yanked(name, version, yanked))
     }
+    pub fn reprioritize(&self, name: &str, version: &str, priority: i32) -> Result<bool> {
+        self.runtime
+            .block_on(self.inner.reprioritize(name, version, priority))
+    }
     pub fn is_locked(&self) -> Result<bool> {
         self.runtime.block_on(self.inner.is_locked())
     }
-    pub fn lock(&self) -> Result<()> {
-        self.runtime.block_on(self.inner.lock())
+    pub fn lock_reason(&self) -> Result<Option<String>> {
+        self.runtime.block_on(self.inner.lock_reason())
+    }
+    pub fn lock(&self, reason: Option<&str>) -> Result<()> {
+        self.runtime.block_on(self.inner.lock(reason))
     }
     pub fn unlock(&self) -> Result<()> {
         self.runtime.block_on(self.inner.unlock())
@@ -492,17 +544,37 @@ impl BuildQueue {
         // `SKIP LOCKED` here will enable another build-server to just
         // skip over taken (=locked) rows and start building the first
         // available one.
+        //
+        // Within a priority, the first queued version of every crate comes
+        // before the second version of any crate, and so on. The rank is
+        // computed here so it always matches the current queue. Between crates
+        // with the same rank, the one picked least recently goes first, so a
+        // crate with many queued versions takes turns with the others.
+        // `FOR UPDATE` can't be combined with window functions, so the rank
+        // is computed in a subquery.
         let to_process = match self.runtime.block_on(
             sqlx::query_as!(
                 QueuedCrate,
-                "SELECT id, name, version, priority, registry
+                "SELECT queue.id, queue.name, queue.version, queue.priority, queue.registry
                  FROM queue
+                 INNER JOIN (
+                    SELECT
+                        id,
+                        ROW_NUMBER() OVER (PARTITION BY name, priority ORDER BY id) AS crate_rank
+                    FROM queue
+                    WHERE attempt < $1
+                 ) AS ranked ON ranked.id = queue.id
                  WHERE
-                    attempt < $1 AND
-                    (last_attempt IS NULL OR last_attempt < NOW() - make_interval(secs => $2))
-                 ORDER BY priority ASC, attempt ASC, id ASC
+                    queue.attempt < $1 AND
+                    (queue.last_attempt IS NULL OR queue.last_attempt < NOW() - make_interval(secs => $2))
+                 ORDER BY
+                    queue.priority ASC,
+                    ranked.crate_rank ASC,
+                    queue.crate_picked_at ASC NULLS FIRST,
+                    queue.attempt ASC,
+                    queue.id ASC
                  LIMIT 1
-                 FOR UPDATE SKIP LOCKED",
+                 FOR UPDATE OF queue SKIP LOCKED",
                 self.inner.max_attempts,
                 self.inner.config.delay_between_build_attempts.as_secs_f64(),
             )
@@ -512,6 +584,29 @@ impl BuildQueue {
             None => return Ok(()),
         };
 
+        // Remember when this crate had its turn, outside of the transaction so we
+        // don't keep its other queued versions locked during the build. Rows another
+        // build-server is working on are skipped.
+        if let Err(err) = self.runtime.block_on(async {
+            let mut conn = self.inner.db.get_async().await?;
+            sqlx::query!(
+                "UPDATE queue
+                 SET crate_picked_at = NOW()
+                 WHERE id IN (
+                    SELECT id
+                    FROM queue
+                    WHERE name = $1
+                    FOR UPDATE SKIP LOCKED
+                 )",
+                to_process.name,
+            )
+            .execute(&mut *conn)
+            .await?;
+            Ok::<_, anyhow::Error>(())
+        }) {
+            report_error(&err);
+        }
+
         let res = self
             .inner
             .metrics
@@ -533,7 +628,8 @@ impl BuildQueue {
                     "UPDATE queue
                          SET
                             attempt = attempt + 1,
-                            last_attempt = NOW()
+                            last_attempt = NOW(),
+                            crate_picked_at = NOW()
                          WHERE id = $1
                          RETURNING attempt;",
                     to_process.id,
@@ -633,7 +729,7 @@ impl BuildQueue {
                 3,
             ) {
                 report_error(&err);
-                self.lock()?;
+                self.lock(Some("reinitializing the build workspace failed"))?;
                 return Err(err);
             }
 
@@ -642,7 +738,7 @@ impl BuildQueue {
                 .context("Updating toolchain failed, locking queue")
             {
                 report_error(&err);
-                self.lock()?;
+                self.lock(Some("updating the toolchain failed"))?;
                 return Err(err);
             }
 
@@ -962,6 +1058,48 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_reprioritize_changes_pop_order() {
+        crate::test::wrapper(|env| {
+            let queue = env.build_queue();
+
+            queue.add_crate("first", "1.0.0", 0, None)?;
+            queue.add_crate("second", "1.0.0", 0, None)?;
+
+            assert!(queue.reprioritize("second", "1.0.0", -100)?);
+
+            queue.process_next_crate(|krate| {
+                assert_eq!(krate.name, "second");
+                assert_eq!(krate.priority, -100);
+                Ok(BuildPackageSummary::default())
+            })?;
+            queue.process_next_crate(|krate| {
+                assert_eq!(krate.name, "first");
+                Ok(BuildPackageSummary::default())
+            })?;
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_reprioritize_not_queued() {
+        crate::test::async_wrapper(|env| async move {
+            let queue = env.async_build_queue().await;
+
+            queue.add_crate("queued", "1.0.0", 0, None).await?;
+
+            assert!(!queue.reprioritize("not-queued", "1.0.0", -100).await?);
+            assert!(!queue.reprioritize("queued", "2.0.0", -100).await?);
+
+            let queued_crates = queue.queued_crates().await?;
+            assert_eq!(queued_crates.len(), 1);
+            assert_eq!(queued_crates[0].priority, 0);
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_wait_between_build_attempts() {
         crate::test::wrapper(|env| {
@@ -1295,6 +1433,74 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_queue_interleaves_crates_with_equal_priority() {
+        crate::test::wrapper(|env| {
+            env.override_config(|config| {
+                config.delay_between_build_attempts = Duration::ZERO;
+            });
+            let queue = env.build_queue();
+
+            for patch in 0..10 {
+                queue.add_crate("many", &format!("1.0.{patch}"), 0, None)?;
+            }
+            queue.add_crate("few", "1.0.0", 0, None)?;
+            queue.add_crate("few", "1.0.1", 0, None)?;
+
+            let mut processed = Vec::new();
+            for _ in 0..5 {
+                queue.process_next_crate(|krate| {
+                    processed.push(format!("{} {}", krate.name, krate.version));
+                    Ok(BuildPackageSummary::default())
+                })?;
+            }
+
+            assert_eq!(
+                processed,
+                vec![
+                    "many 1.0.0",
+                    "few 1.0.0",
+                    "many 1.0.1",
+                    "few 1.0.1",
+                    "many 1.0.2",
+                ]
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_queue_rank_follows_priority_changes() {
+        crate::test::wrapper(|env| {
+            let queue = env.build_queue();
+
+            for patch in 0..3 {
+                queue.add_crate("many", &format!("1.0.{patch}"), 0, None)?;
+            }
+            queue.add_crate("few", "1.0.0", 5, None)?;
+            // re-queueing moves the version to the other priority, where it's the
+            // first queued version of its crate.
+            queue.add_crate("many", "1.0.2", 5, None)?;
+
+            assert_eq!(
+                queue
+                    .queued_crates()?
+                    .iter()
+                    .map(|c| (c.name.as_str(), c.version.as_str(), c.priority))
+                    .collect::<Vec<_>>(),
+                vec![
+                    ("many", "1.0.0", 0),
+                    ("many", "1.0.1", 0),
+                    ("many", "1.0.2", 5),
+                    ("few", "1.0.0", 5),
+                ]
+            );
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn test_queued_crates() {
         crate::test::wrapper(|env| {
@@ -1372,11 +1578,31 @@ mod tests {
             // unlocked without config
             assert!(!queue.is_locked()?);
 
-            queue.lock()?;
+            queue.lock(None)?;
+            assert!(queue.is_locked()?);
+            assert_eq!(queue.lock_reason()?, None);
+
+            queue.unlock()?;
+            assert!(!queue.is_locked()?);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_queue_lock_reason() {
+        crate::test::wrapper(|env| {
+            let queue = env.build_queue();
+            assert_eq!(queue.lock_reason()?, None);
+
+            queue.lock(Some("toolchain update"))?;
             assert!(queue.is_locked()?);
+            assert_eq!(queue.lock_reason()?.as_deref(), Some("toolchain update"));
 
+            // unlocking clears the reason
             queue.unlock()?;
             assert!(!queue.is_locked()?);
+            assert_eq!(queue.lock_reason()?, None);
 
             Ok(())
         });
@@ -1149,6 +1149,26 @@ mod tests {
         });
     }
 
+    #[test]
+    fn queue_invalidation_without_distribution_is_noop() {
+        crate::test::async_wrapper(|env| async move {
+            env.override_config(|config| {
+                config.cache_invalidatable_responses = true;
+                config.cloudfront_distribution_id_web = None;
+                config.cloudfront_distribution_id_static = None;
+            });
+
+            let mut conn = env.async_db().await.async_conn().await;
+            queue_crate_invalidation(&mut conn, &env.config(), "krate").await?;
+
+            assert!(queued_or_active_crate_invalidations(&mut conn)
+                .await?
+                .is_empty());
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn dont_create_invalidations_without_paths() {
         crate::test::async_wrapper(|env| async move {
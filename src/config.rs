@@ -1,7 +1,14 @@
-use crate::{cdn::CdnKind, storage::StorageKind};
-use anyhow::{anyhow, bail, Context, Result};
+use crate::{
+    cdn::CdnKind,
+    storage::StorageKind,
+    web::{cache::CachePolicy, sitemap::RobotsRule},
+};
+use anyhow::{anyhow, bail, ensure, Context, Result};
 use chrono::NaiveDate;
-use std::{env::VarError, error::Error, path::PathBuf, str::FromStr, time::Duration};
+use http::HeaderName;
+use std::{
+    collections::HashMap, env::VarError, error::Error, path::PathBuf, str::FromStr, time::Duration,
+};
 use tracing::trace;
 use url::Url;
 
@@ -9,7 +16,10 @@ use url::Url;
 pub struct Config {
     pub prefix: PathBuf,
     pub registry_index_path: PathBuf,
+    /// Git URL of the registry index to watch and build from, crates.io when unset.
+    /// Crate files are downloaded from the `dl` endpoint in the index's `config.json`.
     pub registry_url: Option<String>,
+    /// Base URL of the registry's web API, used for owners, download counts and search.
     pub registry_api_host: Url,
 
     /// How long to wait between registry checks
@@ -19,6 +29,14 @@ pub struct Config {
     pub(crate) database_url: String,
     pub(crate) max_pool_size: u32,
     pub(crate) min_pool_idle: u32,
+    // How long to wait for a free connection before giving up, and how long
+    // connections are kept open in total and when idle.
+    pub(crate) database_acquire_timeout: Duration,
+    pub(crate) database_max_lifetime: Duration,
+    pub(crate) database_idle_timeout: Duration,
+    // Queries taking longer than this are logged as warnings
+    // in the `sqlx::query` target.
+    pub(crate) slow_query_threshold: Duration,
 
     // Storage params
     pub(crate) storage_backend: StorageKind,
@@ -48,16 +66,33 @@ pub struct Config {
     // constant_time_eq for comparisons!)
     pub(crate) cratesio_token: Option<String>,
 
+    // minimum time between two triggered rebuilds of the same crate
+    pub(crate) rebuild_trigger_interval: Duration,
+
     // amount of retries for external API calls, mostly crates.io
     pub crates_io_api_call_retries: u32,
 
+    // maximum size of a response body we buffer from upstream APIs like
+    // crates.io, GitHub or GitLab
+    pub max_upstream_body_bytes: usize,
+
     // request timeout in seconds
     pub(crate) request_timeout: Option<Duration>,
     pub(crate) report_request_timeouts: bool,
 
+    // Additionally serve all metrics under `/metrics` on the background metrics web server,
+    // the default path for Prometheus scrapers.
+    pub(crate) metrics_scrape_endpoint: bool,
+
+    // Origins allowed to call the JSON API endpoints from a browser, comma separated.
+    // Defaults to `*` since these endpoints are read-only.
+    pub(crate) cors_allowed_origins: Vec<String>,
+
     // Max size of the files served by the docs.rs frontend
     pub(crate) max_file_size: usize,
     pub(crate) max_file_size_html: usize,
+    // Max total size of the files in a `docs.tar` download
+    pub(crate) max_rustdoc_tarball_size: usize,
     // The most memory that can be used to parse an HTML file
     pub(crate) max_parse_memory: usize,
     // Time between 'git gc --auto' calls in seconds
@@ -66,6 +101,10 @@ pub struct Config {
     /// amount of threads for CPU intensive rendering
     pub(crate) render_threads: usize,
 
+    // How many crate owners are shown on the crate page, the rest is
+    // listed on `/crate/{name}/owners`.
+    pub(crate) max_inline_owners: usize,
+
     // random crate search generates a number of random IDs to
     // efficiently find a random crate with > 100 GH stars.
     // The amount depends on the ratio of crates with >100 stars
@@ -82,11 +121,36 @@ pub struct Config {
     // Content Security Policy
     pub(crate) csp_report_only: bool,
 
+    // Public base URL of this instance, used for canonical links and the sitemap.
+    // This is never derived from request headers to avoid host-header injection.
+    pub(crate) external_base_url: Url,
+
+    // Additional `robots.txt` rules, comma separated `Allow: /path` or `Disallow: /path`
+    // entries. For example, to keep crawlers away from specific versions while still
+    // allowing `latest` and the crate roots:
+    // `Allow: /*/latest/, Allow: /crate/*/latest, Disallow: /*/*.*.*/, Disallow: /crate/*/*.*`
+    pub(crate) robots_txt_rules: Vec<RobotsRule>,
+
+    // Rate limiting for crawlers, per client IP.
+    // Disabled when no rate is set.
+    pub(crate) rate_limit_per_second: Option<f64>,
+    pub(crate) rate_limit_burst: u32,
+    // Header to read the client IP from, like `X-Forwarded-For`.
+    // When unset, the socket address is used.
+    pub(crate) rate_limit_ip_header: Option<HeaderName>,
+
     // Cache-Control header, for versioned URLs.
     // If both are absent, don't generate the header. If only one is present,
     // generate just that directive. Values are in seconds.
     pub(crate) cache_control_stale_while_revalidate: Option<u32>,
 
+    // `stale-while-revalidate` and `stale-if-error` durations in seconds for the CDN,
+    // added to `Cache-Control`. Set per cache policy as comma separated
+    // `policy=seconds` pairs, like `forever_in_cdn=86400`, using the policy names
+    // from the cache policy metric.
+    pub(crate) cdn_stale_while_revalidate: HashMap<String, u32>,
+    pub(crate) cdn_stale_if_error: HashMap<String, u32>,
+
     // Activate full page caching.
     // When disabled, we still cache static assets.
     // This only affects pages that depend on invalidations to work.
@@ -117,6 +181,17 @@ pub struct Config {
     pub(crate) build_default_memory_limit: Option<usize>,
     pub(crate) include_default_targets: bool,
     pub(crate) disable_memory_limit: bool,
+    // Target to try when the build for the default target of a crate fails.
+    // Some crates only fail on their configured default target.
+    pub(crate) fallback_target: Option<String>,
+
+    // Don't build a release again when it already has a successful build
+    // with the current rustc version.
+    pub(crate) skip_up_to_date_builds: bool,
+
+    // cgroup (v2) directory the build sandboxes run in, used to record
+    // the peak memory usage of each build.
+    pub(crate) build_cgroup_path: Option<PathBuf>,
 
     // automatic rebuild configuration
     pub(crate) max_queued_rebuilds: Option<u16>,
@@ -159,6 +234,7 @@ impl Config {
             )?),
 
             crates_io_api_call_retries: env("DOCSRS_CRATESIO_API_CALL_RETRIES", 3)?,
+            max_upstream_body_bytes: env("DOCSRS_MAX_UPSTREAM_BODY_BYTES", 10 * 1024 * 1024)?,
 
             registry_index_path: env("REGISTRY_INDEX_PATH", prefix.join("crates.io-index"))?,
             registry_url: maybe_env("REGISTRY_URL")?,
@@ -171,6 +247,22 @@ impl Config {
             database_url: require_env("DOCSRS_DATABASE_URL")?,
             max_pool_size: env("DOCSRS_MAX_POOL_SIZE", 90)?,
             min_pool_idle: env("DOCSRS_MIN_POOL_IDLE", 10)?,
+            database_acquire_timeout: Duration::from_secs(env(
+                "DOCSRS_DATABASE_ACQUIRE_TIMEOUT",
+                30,
+            )?),
+            database_max_lifetime: Duration::from_secs(env(
+                "DOCSRS_DATABASE_MAX_LIFETIME",
+                30 * 60,
+            )?),
+            database_idle_timeout: Duration::from_secs(env(
+                "DOCSRS_DATABASE_IDLE_TIMEOUT",
+                10 * 60,
+            )?),
+            slow_query_threshold: Duration::from_millis(env(
+                "DOCSRS_SLOW_QUERY_THRESHOLD_MS",
+                1000,
+            )?),
 
             storage_backend: env("DOCSRS_STORAGE_BACKEND", StorageKind::Database)?,
 
@@ -196,24 +288,53 @@ impl Config {
             gitlab_accesstoken: maybe_env("DOCSRS_GITLAB_ACCESSTOKEN")?,
 
             cratesio_token: maybe_env("DOCSRS_CRATESIO_TOKEN")?,
+            rebuild_trigger_interval: Duration::from_secs(env(
+                "DOCSRS_REBUILD_TRIGGER_INTERVAL",
+                60 * 60,
+            )?),
 
             max_file_size: env("DOCSRS_MAX_FILE_SIZE", 50 * 1024 * 1024)?,
             max_file_size_html: env("DOCSRS_MAX_FILE_SIZE_HTML", 50 * 1024 * 1024)?,
+            max_rustdoc_tarball_size: env("DOCSRS_MAX_RUSTDOC_TARBALL_SIZE", 1024 * 1024 * 1024)?,
             // LOL HTML only uses as much memory as the size of the start tag!
             // https://github.com/rust-lang/docs.rs/pull/930#issuecomment-667729380
             max_parse_memory: env("DOCSRS_MAX_PARSE_MEMORY", 5 * 1024 * 1024)?,
             registry_gc_interval: env("DOCSRS_REGISTRY_GC_INTERVAL", 60 * 60)?,
             render_threads: env("DOCSRS_RENDER_THREADS", num_cpus::get())?,
+            max_inline_owners: env("DOCSRS_MAX_INLINE_OWNERS", 20)?,
             request_timeout: maybe_env::<u64>("DOCSRS_REQUEST_TIMEOUT")?.map(Duration::from_secs),
             report_request_timeouts: env("DOCSRS_REPORT_REQUEST_TIMEOUTS", false)?,
+            metrics_scrape_endpoint: env("DOCSRS_METRICS_SCRAPE_ENDPOINT", false)?,
+            cors_allowed_origins: env("DOCSRS_CORS_ALLOWED_ORIGINS", "*".to_string())?
+                .split(',')
+                .map(|origin| origin.trim().to_string())
+                .filter(|origin| !origin.is_empty())
+                .collect(),
 
             random_crate_search_view_size: env("DOCSRS_RANDOM_CRATE_SEARCH_VIEW_SIZE", 500)?,
 
             csp_report_only: env("DOCSRS_CSP_REPORT_ONLY", false)?,
 
+            external_base_url: env(
+                "DOCSRS_EXTERNAL_BASE_URL",
+                "https://docs.rs".parse().unwrap(),
+            )?,
+            robots_txt_rules: env("DOCSRS_ROBOTS_TXT_RULES", String::new())?
+                .split(',')
+                .map(str::trim)
+                .filter(|rule| !rule.is_empty())
+                .map(str::parse)
+                .collect::<Result<_>>()?,
+
+            rate_limit_per_second: maybe_positive_env("DOCSRS_RATE_LIMIT_PER_SECOND")?,
+            rate_limit_burst: env("DOCSRS_RATE_LIMIT_BURST", 100)?,
+            rate_limit_ip_header: maybe_env("DOCSRS_RATE_LIMIT_IP_HEADER")?,
+
             cache_control_stale_while_revalidate: maybe_env(
                 "CACHE_CONTROL_STALE_WHILE_REVALIDATE",
             )?,
+            cdn_stale_while_revalidate: policy_durations("DOCSRS_CDN_STALE_WHILE_REVALIDATE")?,
+            cdn_stale_if_error: policy_durations("DOCSRS_CDN_STALE_IF_ERROR")?,
 
             cache_invalidatable_responses: env("DOCSRS_CACHE_INVALIDATEABLE_RESPONSES", true)?,
 
@@ -238,10 +359,13 @@ impl Config {
             build_default_memory_limit: maybe_env("DOCSRS_BUILD_DEFAULT_MEMORY_LIMIT")?,
             include_default_targets: env("DOCSRS_INCLUDE_DEFAULT_TARGETS", true)?,
             disable_memory_limit: env("DOCSRS_DISABLE_MEMORY_LIMIT", false)?,
+            fallback_target: maybe_env("DOCSRS_FALLBACK_TARGET")?,
             build_workspace_reinitialization_interval: Duration::from_secs(env(
                 "DOCSRS_BUILD_WORKSPACE_REINITIALIZATION_INTERVAL",
                 86400,
             )?),
+            skip_up_to_date_builds: env("DOCSRS_SKIP_UP_TO_DATE_BUILDS", false)?,
+            build_cgroup_path: maybe_env("DOCSRS_BUILD_CGROUP_PATH")?,
             max_queued_rebuilds: maybe_env("DOCSRS_MAX_QUEUED_REBUILDS")?,
             rebuild_up_to_date: maybe_env("DOCSRS_REBUILD_UP_TO_DATE")?,
         })
@@ -256,6 +380,48 @@ where
     Ok(maybe_env(var)?.unwrap_or(default))
 }
 
+/// parse `policy=seconds` pairs, separated by commas.
+///
+/// `policy` is the label of a [`CachePolicy`], unknown policies are rejected.
+fn policy_durations(var: &str) -> Result<HashMap<String, u32>> {
+    let Some(content) = maybe_env::<String>(var)? else {
+        return Ok(HashMap::new());
+    };
+
+    content
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (policy, seconds) = pair.split_once('=').with_context(|| {
+                format!("invalid entry {pair:?} in {var}, expected policy=seconds")
+            })?;
+            let policy = policy.trim();
+            ensure!(
+                CachePolicy::from_label(policy).is_some(),
+                "unknown cache policy {policy:?} in {var}"
+            );
+            let seconds = seconds
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid duration for {policy} in {var}"))?;
+            Ok((policy.to_owned(), seconds))
+        })
+        .collect()
+}
+
+/// like [`maybe_env`], but rejects values that aren't positive.
+fn maybe_positive_env(var: &str) -> Result<Option<f64>> {
+    let value = maybe_env::<f64>(var)?;
+    if let Some(value) = value {
+        ensure!(
+            value > 0.0,
+            "configuration variable {var} has to be positive"
+        );
+    }
+    Ok(value)
+}
+
 fn require_env<T>(var: &str) -> Result<T>
 where
     T: FromStr,
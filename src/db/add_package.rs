@@ -1,15 +1,17 @@
 use crate::{
     db::types::{BuildStatus, Feature},
-    docbuilder::DocCoverage,
+    docbuilder::{DocCoverage, RustdocWarnings},
     error::Result,
     registry_api::{CrateData, CrateOwner, ReleaseData},
     storage::CompressionAlgorithm,
+    target::is_known_target,
     utils::{rustc_version::parse_rustc_date, MetadataPackage},
     web::crate_details::{latest_release, releases_for_crate},
 };
 use anyhow::{anyhow, Context};
 use derive_more::Display;
 use futures_util::stream::TryStreamExt;
+use semver::Version;
 use serde::Serialize;
 use serde_json::Value;
 use slug::slugify;
@@ -18,8 +20,9 @@ use std::{
     fs,
     io::{BufRead, BufReader},
     path::Path,
+    time::Duration,
 };
-use tracing::{debug, error, info, instrument};
+use tracing::{debug, error, info, instrument, warn};
 
 #[derive(Debug, Clone, Copy, Display, PartialEq, Eq, Hash, Serialize, sqlx::Type)]
 #[sqlx(transparent)]
@@ -64,6 +67,17 @@ pub(crate) async fn finish_release(
     let readme = get_readme(metadata_pkg, source_dir).unwrap_or(None);
     let features = get_features(metadata_pkg);
     let is_library = metadata_pkg.is_library();
+    let is_proc_macro = metadata_pkg.is_proc_macro();
+
+    let unknown_targets = unknown_doc_targets(&doc_targets);
+    if !unknown_targets.is_empty() {
+        warn!(
+            name = %metadata_pkg.name,
+            version = %metadata_pkg.version,
+            ?unknown_targets,
+            "release has doc targets that are not known to rustc"
+        );
+    }
 
     let result = sqlx::query!(
         r#"UPDATE releases
@@ -90,7 +104,8 @@ pub(crate) async fn finish_release(
                features = $22,
                repository_id = $23,
                archive_storage = $24,
-               source_size = $25
+               source_size = $25,
+               is_proc_macro = $26
            WHERE id = $1"#,
         release_id.0,
         registry_data.release_time,
@@ -117,6 +132,7 @@ pub(crate) async fn finish_release(
         repository_id,
         archive_storage,
         source_size as i64,
+        is_proc_macro,
     )
     .execute(&mut *conn)
     .await?;
@@ -168,6 +184,7 @@ pub async fn update_build_status(
          CASE
            WHEN summary.success_count > 0 THEN 'success'::build_status
            WHEN summary.failure_count > 0 THEN 'failure'::build_status
+           WHEN summary.skipped_count > 0 THEN 'skipped'::build_status
            ELSE 'in_progress'::build_status
          END as build_status
 
@@ -176,7 +193,8 @@ pub async fn update_build_status(
                r.id,
                MAX(b.build_finished) as last_build_time,
                SUM(CASE WHEN b.build_status = 'success' THEN 1 ELSE 0 END) as success_count,
-               SUM(CASE WHEN b.build_status = 'failure' THEN 1 ELSE 0 END) as failure_count
+               SUM(CASE WHEN b.build_status = 'failure' THEN 1 ELSE 0 END) as failure_count,
+               SUM(CASE WHEN b.build_status = 'skipped' THEN 1 ELSE 0 END) as skipped_count
              FROM
                releases as r
                LEFT OUTER JOIN builds AS b on b.rid = r.id
@@ -332,6 +350,141 @@ pub(crate) async fn update_build_with_error(
     Ok(build_id)
 }
 
+/// Mark a build as intentionally not performed, with the reason as error.
+#[instrument(skip(conn))]
+pub(crate) async fn update_build_skipped(
+    conn: &mut sqlx::PgConnection,
+    build_id: BuildId,
+    reason: &str,
+) -> Result<()> {
+    debug!("marking build as skipped");
+    let release_id = sqlx::query_scalar!(
+        r#"UPDATE builds
+         SET
+             build_status = $1,
+             errors = $2,
+             build_finished = NOW()
+         WHERE id = $3
+         RETURNING rid as "rid: ReleaseId" "#,
+        BuildStatus::Skipped as BuildStatus,
+        reason,
+        build_id.0,
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    update_build_status(conn, release_id).await?;
+
+    Ok(())
+}
+
+/// Resources a build used, as measured by the builder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BuildResourceUsage {
+    /// wall-clock time of the whole build, including all targets
+    pub(crate) duration: Duration,
+    /// peak memory usage in bytes, when it could be measured
+    pub(crate) peak_memory: Option<u64>,
+}
+
+#[instrument(skip(conn))]
+pub(crate) async fn update_build_resource_usage(
+    conn: &mut sqlx::PgConnection,
+    build_id: BuildId,
+    usage: BuildResourceUsage,
+) -> Result<()> {
+    sqlx::query!(
+        "UPDATE builds
+         SET
+             build_duration_ms = $1,
+             peak_memory = $2
+         WHERE id = $3",
+        usage.duration.as_millis() as i64,
+        usage.peak_memory.map(|v| v as i64),
+        build_id.0,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(conn))]
+pub(crate) async fn update_build_rustdoc_warnings(
+    conn: &mut sqlx::PgConnection,
+    build_id: BuildId,
+    warnings: &RustdocWarnings,
+) -> Result<()> {
+    sqlx::query!(
+        "UPDATE builds SET rustdoc_warnings = $1 WHERE id = $2",
+        serde_json::to_value(warnings)?,
+        build_id.0,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(conn))]
+pub(crate) async fn update_build_cargo_args(
+    conn: &mut sqlx::PgConnection,
+    build_id: BuildId,
+    cargo_args: &[String],
+) -> Result<()> {
+    sqlx::query!(
+        "UPDATE builds SET cargo_args = $1 WHERE id = $2",
+        cargo_args,
+        build_id.0,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// store informational notes about a build, like ignored configuration.
+///
+/// These are kept apart from `errors`, since they also apply to successful builds.
+#[instrument(skip(conn))]
+pub(crate) async fn update_build_notes(
+    conn: &mut sqlx::PgConnection,
+    build_id: BuildId,
+    notes: &[String],
+) -> Result<()> {
+    sqlx::query!(
+        "UPDATE builds SET notes = $1 WHERE id = $2",
+        notes,
+        build_id.0,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Whether the release already has a successful build using the given rustc version.
+pub(crate) async fn has_successful_build_with_rustc(
+    conn: &mut sqlx::PgConnection,
+    release_id: ReleaseId,
+    rustc_version: &str,
+) -> Result<bool> {
+    Ok(sqlx::query_scalar!(
+        r#"SELECT EXISTS (
+             SELECT 1
+             FROM builds
+             WHERE
+                 rid = $1 AND
+                 build_status = 'success' AND
+                 rustc_version = $2
+         ) as "exists!" "#,
+        release_id.0,
+        rustc_version,
+    )
+    .fetch_one(&mut *conn)
+    .await?)
+}
+
 pub(crate) async fn initialize_crate(conn: &mut sqlx::PgConnection, name: &str) -> Result<CrateId> {
     sqlx::query_scalar!(
         "INSERT INTO crates (name)
@@ -556,6 +709,49 @@ pub async fn update_crate_data_in_database(
     .await?;
 
     update_owners_in_database(conn, &registry_data.owners, crate_id).await?;
+    let release_downloads: Vec<_> = registry_data
+        .releases
+        .iter()
+        .map(|(version, data)| (version.clone(), data.downloads))
+        .collect();
+    update_release_downloads_in_database(conn, &release_downloads, crate_id).await?;
+
+    sqlx::query!(
+        "UPDATE crates SET keywords = $2, categories = $3 WHERE id = $1",
+        crate_id.0,
+        &registry_data.keywords,
+        &registry_data.categories,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Updates the download counts of the releases we know about
+async fn update_release_downloads_in_database(
+    conn: &mut sqlx::PgConnection,
+    release_downloads: &[(Version, i32)],
+    crate_id: CrateId,
+) -> Result<()> {
+    let (versions, downloads): (Vec<String>, Vec<i32>) = release_downloads
+        .iter()
+        .map(|(version, downloads)| (version.to_string(), *downloads))
+        .unzip();
+
+    sqlx::query!(
+        "UPDATE releases
+         SET downloads = data.downloads
+         FROM UNNEST($2::TEXT[], $3::INT[]) AS data(version, downloads)
+         WHERE
+             releases.crate_id = $1 AND
+             releases.version = data.version",
+        crate_id.0,
+        &versions,
+        &downloads,
+    )
+    .execute(&mut *conn)
+    .await?;
 
     Ok(())
 }
@@ -639,6 +835,17 @@ where
     Ok(())
 }
 
+/// returns the doc targets that are not in the list of targets known to rustc.
+///
+/// These are still stored, the list is only used to surface bogus data.
+fn unknown_doc_targets(doc_targets: &[String]) -> Vec<&str> {
+    doc_targets
+        .iter()
+        .map(String::as_str)
+        .filter(|target| !is_known_target(target))
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -648,6 +855,63 @@ mod test {
     use chrono::NaiveDate;
     use test_case::test_case;
 
+    #[test]
+    fn test_has_successful_build_with_rustc() {
+        async_wrapper(|env| async move {
+            let mut conn = env.async_db().await.async_conn().await;
+            let current = "rustc 1.84.0-nightly (e7c0d2750 2024-10-15)";
+
+            let built = env
+                .fake_release()
+                .await
+                .name("built")
+                .version("0.1.0")
+                .builds(vec![FakeBuild::default().rustc_version(current)])
+                .create()
+                .await?;
+            assert!(has_successful_build_with_rustc(&mut conn, built, current).await?);
+            assert!(
+                !has_successful_build_with_rustc(
+                    &mut conn,
+                    built,
+                    "rustc 1.83.0 (90b35a623 2024-11-26)"
+                )
+                .await?
+            );
+
+            let failed = env
+                .fake_release()
+                .await
+                .name("failed")
+                .version("0.1.0")
+                .builds(vec![FakeBuild::default()
+                    .rustc_version(current)
+                    .build_status(BuildStatus::Failure)])
+                .create()
+                .await?;
+            assert!(!has_successful_build_with_rustc(&mut conn, failed, current).await?);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_unknown_doc_targets() {
+        let known = vec![
+            "x86_64-unknown-linux-gnu".to_string(),
+            "i686-pc-windows-msvc".to_string(),
+            "aarch64-apple-darwin".to_string(),
+        ];
+        assert!(unknown_doc_targets(&known).is_empty());
+
+        let mut with_bogus = known.clone();
+        with_bogus.push("x86_64-unknown-bogus".to_string());
+        assert_eq!(
+            unknown_doc_targets(&with_bogus),
+            vec!["x86_64-unknown-bogus"]
+        );
+    }
+
     #[test]
     fn test_set_build_to_error() {
         async_wrapper(|env| async move {
@@ -1079,6 +1343,48 @@ mod test {
         })
     }
 
+    #[test]
+    fn update_release_downloads() {
+        async_wrapper(|env| async move {
+            let mut conn = env.async_db().await.async_conn().await;
+            let crate_id = initialize_crate(&mut conn, "krate").await?;
+            initialize_release(&mut conn, crate_id, "0.1.0").await?;
+            initialize_release(&mut conn, crate_id, "0.2.0").await?;
+
+            update_release_downloads_in_database(
+                &mut conn,
+                &[
+                    (Version::new(0, 1, 0), 5),
+                    (Version::new(0, 2, 0), 7),
+                    // versions we don't know about are ignored
+                    (Version::new(0, 3, 0), 9),
+                ],
+                crate_id,
+            )
+            .await?;
+
+            let downloads: Vec<_> = sqlx::query!(
+                "SELECT version, downloads
+                 FROM releases
+                 WHERE crate_id = $1
+                 ORDER BY version",
+                crate_id.0
+            )
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|row| (row.version, row.downloads))
+            .collect();
+
+            assert_eq!(
+                downloads,
+                vec![("0.1.0".into(), Some(5)), ("0.2.0".into(), Some(7))]
+            );
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn add_new_owners_and_delete_old() {
         async_wrapper(|env| async move {
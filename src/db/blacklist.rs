@@ -8,10 +8,29 @@ enum BlacklistError {
 
     #[error("crate {0} is not on the blacklist")]
     CrateNotOnBlacklist(String),
+
+    #[error("pattern {0} is already on the blacklist")]
+    PatternAlreadyOnBlacklist(String),
+
+    #[error("pattern {0} is not on the blacklist")]
+    PatternNotOnBlacklist(String),
 }
 
-/// Returns whether the given name is blacklisted.
+/// Returns whether the given name is blacklisted, either by name or by a pattern.
 pub async fn is_blacklisted(conn: &mut sqlx::PgConnection, name: &str) -> Result<bool> {
+    Ok(sqlx::query_scalar!(
+        r#"SELECT
+            EXISTS(SELECT 1 FROM blacklisted_crates WHERE crate_name = $1) OR
+            EXISTS(SELECT 1 FROM blacklisted_crate_patterns WHERE $1 LIKE pattern)
+            as "blacklisted!";"#,
+        name
+    )
+    .fetch_one(conn)
+    .await?)
+}
+
+/// Returns whether the exact crate name is on the blacklist.
+async fn is_crate_on_blacklist(conn: &mut sqlx::PgConnection, name: &str) -> Result<bool> {
     Ok(sqlx::query_scalar!(
         r#"SELECT COUNT(*) as "count!" FROM blacklisted_crates WHERE crate_name = $1;"#,
         name
@@ -34,7 +53,7 @@ pub async fn list_crates(conn: &mut sqlx::PgConnection) -> Result<Vec<String>> {
 
 /// Adds a crate to the blacklist.
 pub async fn add_crate(conn: &mut sqlx::PgConnection, name: &str) -> Result<()> {
-    if is_blacklisted(&mut *conn, name).await? {
+    if is_crate_on_blacklist(&mut *conn, name).await? {
         return Err(BlacklistError::CrateAlreadyOnBlacklist(name.into()).into());
     }
 
@@ -50,7 +69,7 @@ pub async fn add_crate(conn: &mut sqlx::PgConnection, name: &str) -> Result<()>
 
 /// Removes a crate from the blacklist.
 pub async fn remove_crate(conn: &mut sqlx::PgConnection, name: &str) -> Result<()> {
-    if !is_blacklisted(conn, name).await? {
+    if !is_crate_on_blacklist(conn, name).await? {
         return Err(BlacklistError::CrateNotOnBlacklist(name.into()).into());
     }
 
@@ -64,6 +83,53 @@ pub async fn remove_crate(conn: &mut sqlx::PgConnection, name: &str) -> Result<(
     Ok(())
 }
 
+/// Returns the patterns on the blacklist, sorted ascending.
+pub async fn list_patterns(conn: &mut sqlx::PgConnection) -> Result<Vec<String>> {
+    Ok(
+        sqlx::query_scalar!("SELECT pattern FROM blacklisted_crate_patterns ORDER BY pattern asc;")
+            .fetch(conn)
+            .try_collect()
+            .await?,
+    )
+}
+
+/// Adds a pattern to the blacklist, blacklisting all crates matching it.
+///
+/// Note: `pattern` is used in a `LIKE` statement, so it must follow the postgres like syntax,
+/// the same way as the build queue priority patterns.
+pub async fn add_pattern(conn: &mut sqlx::PgConnection, pattern: &str) -> Result<()> {
+    let inserted = sqlx::query!(
+        "INSERT INTO blacklisted_crate_patterns (pattern) VALUES ($1) ON CONFLICT DO NOTHING;",
+        pattern
+    )
+    .execute(conn)
+    .await?
+    .rows_affected();
+
+    if inserted == 0 {
+        return Err(BlacklistError::PatternAlreadyOnBlacklist(pattern.into()).into());
+    }
+
+    Ok(())
+}
+
+/// Removes a pattern from the blacklist.
+pub async fn remove_pattern(conn: &mut sqlx::PgConnection, pattern: &str) -> Result<()> {
+    let deleted = sqlx::query!(
+        "DELETE FROM blacklisted_crate_patterns WHERE pattern = $1;",
+        pattern
+    )
+    .execute(conn)
+    .await?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err(BlacklistError::PatternNotOnBlacklist(pattern.into()).into());
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -120,4 +186,42 @@ mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn test_blacklist_pattern() {
+        crate::test::async_wrapper(|env| async move {
+            let mut conn = env.async_db().await.async_conn().await;
+
+            add_pattern(&mut conn, "evil-%").await?;
+            assert_eq!(list_patterns(&mut conn).await?, vec!["evil-%"]);
+
+            assert!(is_blacklisted(&mut conn, "evil-123").await?);
+            assert!(!is_blacklisted(&mut conn, "evil").await?);
+            assert!(!is_blacklisted(&mut conn, "not-evil-123").await?);
+
+            // exact entries still work next to patterns
+            add_crate(&mut conn, "evil").await?;
+            assert!(is_blacklisted(&mut conn, "evil").await?);
+            assert_eq!(list_crates(&mut conn).await?, vec!["evil"]);
+
+            remove_pattern(&mut conn, "evil-%").await?;
+            assert!(!is_blacklisted(&mut conn, "evil-123").await?);
+            assert!(list_patterns(&mut conn).await?.is_empty());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_add_pattern_twice_and_remove_missing() {
+        crate::test::async_wrapper(|env| async move {
+            let mut conn = env.async_db().await.async_conn().await;
+
+            add_pattern(&mut conn, "evil-%").await?;
+            assert!(add_pattern(&mut conn, "evil-%").await.is_err());
+            assert!(remove_pattern(&mut conn, "spam-%").await.is_err());
+
+            Ok(())
+        });
+    }
 }
@@ -0,0 +1,106 @@
+//! Admin-managed redirects for crates that were superseded by a crate with another name.
+use crate::error::Result;
+
+#[derive(Debug, thiserror::Error)]
+enum CrateRedirectError {
+    #[error("crate {0} is already redirected")]
+    CrateAlreadyRedirected(String),
+
+    #[error("crate {0} is not redirected")]
+    CrateNotRedirected(String),
+}
+
+/// Returns the name of the crate the given crate name redirects to, if any.
+pub async fn get_redirect(conn: &mut sqlx::PgConnection, from: &str) -> Result<Option<String>> {
+    Ok(sqlx::query_scalar!(
+        "SELECT to_name
+         FROM crate_redirects
+         WHERE normalize_crate_name(from_name) = normalize_crate_name($1);",
+        from
+    )
+    .fetch_optional(conn)
+    .await?)
+}
+
+/// Adds a redirect from one crate name to another.
+pub async fn add_redirect(conn: &mut sqlx::PgConnection, from: &str, to: &str) -> Result<()> {
+    if get_redirect(&mut *conn, from).await?.is_some() {
+        return Err(CrateRedirectError::CrateAlreadyRedirected(from.into()).into());
+    }
+
+    sqlx::query!(
+        "INSERT INTO crate_redirects (from_name, to_name) VALUES ($1, $2);",
+        from,
+        to
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Removes the redirect for a crate name.
+pub async fn remove_redirect(conn: &mut sqlx::PgConnection, from: &str) -> Result<()> {
+    if get_redirect(&mut *conn, from).await?.is_none() {
+        return Err(CrateRedirectError::CrateNotRedirected(from.into()).into());
+    }
+
+    sqlx::query!(
+        "DELETE FROM crate_redirects
+         WHERE normalize_crate_name(from_name) = normalize_crate_name($1);",
+        from
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_and_remove_redirect() {
+        crate::test::async_wrapper(|env| async move {
+            let mut conn = env.async_db().await.async_conn().await;
+
+            assert_eq!(get_redirect(&mut conn, "old-crate").await?, None);
+            add_redirect(&mut conn, "old-crate", "new-crate").await?;
+            assert_eq!(
+                get_redirect(&mut conn, "old-crate").await?.as_deref(),
+                Some("new-crate")
+            );
+            assert_eq!(
+                get_redirect(&mut conn, "old_crate").await?.as_deref(),
+                Some("new-crate")
+            );
+            remove_redirect(&mut conn, "old-crate").await?;
+            assert_eq!(get_redirect(&mut conn, "old-crate").await?, None);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_add_twice() {
+        crate::test::async_wrapper(|env| async move {
+            let mut conn = env.async_db().await.async_conn().await;
+
+            add_redirect(&mut conn, "old-crate", "new-crate").await?;
+            assert!(add_redirect(&mut conn, "old-crate", "other-crate")
+                .await
+                .is_err());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_remove_non_existing_redirect() {
+        crate::test::async_wrapper(|env| async move {
+            let mut conn = env.async_db().await.async_conn().await;
+
+            assert!(remove_redirect(&mut conn, "old-crate").await.is_err());
+            Ok(())
+        });
+    }
+}
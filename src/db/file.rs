@@ -33,6 +33,15 @@ impl FileEntry {
 }
 
 pub(crate) fn detect_mime(file_path: impl AsRef<Path>) -> Mime {
+    // assets of newer rustdoc versions, browsers refuse to load them with a generic type.
+    match file_path.as_ref().extension().and_then(OsStr::to_str) {
+        Some("mjs") => return mime::TEXT_JAVASCRIPT,
+        Some("wasm") => return mimes::APPLICATION_WASM.clone(),
+        Some("map") => return mime::APPLICATION_JSON,
+        Some("woff2") => return mimes::FONT_WOFF2.clone(),
+        _ => {}
+    }
+
     let mime = mime_guess::from_path(file_path.as_ref())
         .first()
         .unwrap_or(mime::TEXT_PLAIN);
@@ -8,6 +8,8 @@ macro_rules! mime {
 }
 
 mime!(APPLICATION_ZIP, "application/zip");
+mime!(APPLICATION_WASM, "application/wasm");
+mime!(FONT_WOFF2, "font/woff2");
 mime!(TEXT_MARKDOWN, "text/markdown");
 mime!(TEXT_RUST, "text/rust");
 mime!(TEXT_TOML, "text/toml");
@@ -4,8 +4,10 @@ use sqlx::migrate::{Migrate, Migrator};
 
 pub use self::add_package::update_latest_version_id;
 pub(crate) use self::add_package::{
-    add_doc_coverage, finish_build, finish_release, initialize_build, initialize_crate,
-    initialize_release, update_build_with_error,
+    add_doc_coverage, finish_build, finish_release, has_successful_build_with_rustc,
+    initialize_build, initialize_crate, initialize_release, update_build_cargo_args,
+    update_build_notes, update_build_resource_usage, update_build_rustdoc_warnings,
+    update_build_skipped, update_build_with_error, BuildResourceUsage,
 };
 pub use self::{
     add_package::{
@@ -15,15 +17,20 @@ pub use self::{
     file::{add_path_into_database, add_path_into_remote_archive},
     overrides::Overrides,
     pool::{AsyncPoolClient, Pool, PoolError},
+    reverse_dependencies::update_reverse_dependency_counts,
+    toolchain_history::set_toolchain,
 };
 
 mod add_package;
 pub mod blacklist;
+pub mod crate_redirects;
 pub mod delete;
 pub(crate) mod file;
 pub(crate) mod mimes;
 mod overrides;
 mod pool;
+mod reverse_dependencies;
+pub(crate) mod toolchain_history;
 pub(crate) mod types;
 
 static MIGRATOR: Migrator = sqlx::migrate!();
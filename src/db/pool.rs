@@ -1,11 +1,15 @@
 use crate::metrics::InstanceMetrics;
 use crate::Config;
 use futures_util::{future::BoxFuture, stream::BoxStream};
-use sqlx::{postgres::PgPoolOptions, Executor};
+use log::LevelFilter;
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    ConnectOptions as _, Executor,
+};
 use std::{
     ops::{Deref, DerefMut},
+    str::FromStr,
     sync::Arc,
-    time::Duration,
 };
 use tokio::runtime::Runtime;
 use tracing::debug;
@@ -48,17 +52,17 @@ impl Pool {
         metrics: Arc<InstanceMetrics>,
         schema: &str,
     ) -> Result<Pool, PoolError> {
-        let acquire_timeout = Duration::from_secs(30);
-        let max_lifetime = Duration::from_secs(30 * 60);
-        let idle_timeout = Duration::from_secs(10 * 60);
+        let connect_options = PgConnectOptions::from_str(&config.database_url)
+            .map_err(PoolError::AsyncPoolCreationFailed)?
+            .log_slow_statements(LevelFilter::Warn, config.slow_query_threshold);
 
         let _guard = runtime.enter();
         let async_pool = PgPoolOptions::new()
             .max_connections(config.max_pool_size)
             .min_connections(config.min_pool_idle)
-            .max_lifetime(max_lifetime)
-            .acquire_timeout(acquire_timeout)
-            .idle_timeout(idle_timeout)
+            .max_lifetime(config.database_max_lifetime)
+            .acquire_timeout(config.database_acquire_timeout)
+            .idle_timeout(config.database_idle_timeout)
             .after_connect({
                 let schema = schema.to_owned();
                 move |conn, _meta| {
@@ -79,8 +83,7 @@ impl Pool {
                     })
                 }
             })
-            .connect_lazy(&config.database_url)
-            .map_err(PoolError::AsyncPoolCreationFailed)?;
+            .connect_lazy_with(connect_options);
 
         Ok(Pool {
             async_pool,
@@ -98,7 +101,10 @@ impl Pool {
             }),
             Err(err) => {
                 self.metrics.failed_db_connections.inc();
-                Err(PoolError::AsyncClientError(err))
+                match err {
+                    sqlx::Error::PoolTimedOut => Err(PoolError::AcquireTimeout),
+                    err => Err(PoolError::AsyncClientError(err)),
+                }
             }
         }
     }
@@ -206,4 +212,108 @@ pub enum PoolError {
 
     #[error("failed to get a database connection")]
     AsyncClientError(#[source] sqlx::Error),
+
+    #[error("timed out waiting for a free database connection")]
+    AcquireTimeout,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PoolError;
+    use crate::test::async_wrapper;
+    use std::{
+        fmt,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use tracing::{
+        field::{Field, Visit},
+        Event, Level, Subscriber,
+    };
+    use tracing_subscriber::{layer::Context, prelude::*, Layer};
+
+    /// collects the statements of all slow query warnings emitted by sqlx.
+    #[derive(Clone, Default)]
+    struct SlowQueries(Arc<Mutex<Vec<String>>>);
+
+    impl<S: Subscriber> Layer<S> for SlowQueries {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            let metadata = event.metadata();
+            if metadata.target() == "sqlx::query" && *metadata.level() == Level::WARN {
+                let mut visitor = StatementVisitor::default();
+                event.record(&mut visitor);
+                self.0.lock().unwrap().push(visitor.0);
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct StatementVisitor(String);
+
+    impl Visit for StatementVisitor {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            if field.name() == "db.statement" {
+                self.0 = value.to_owned();
+            }
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if field.name() == "db.statement" {
+                self.0 = format!("{value:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn slow_queries_are_logged() {
+        async_wrapper(|env| async move {
+            env.override_config(|config| {
+                config.slow_query_threshold = Duration::from_millis(100);
+            });
+
+            let mut conn = env.async_db().await.async_conn().await;
+
+            let slow_queries = SlowQueries::default();
+            let _guard = tracing::subscriber::set_default(
+                tracing_subscriber::registry().with(slow_queries.clone()),
+            );
+
+            sqlx::query("SELECT 1").execute(&mut *conn).await?;
+            assert!(slow_queries.0.lock().unwrap().is_empty());
+
+            sqlx::query("SELECT pg_sleep(0.3)")
+                .execute(&mut *conn)
+                .await?;
+            let logged = slow_queries.0.lock().unwrap().clone();
+            assert_eq!(logged.len(), 1);
+            assert!(logged[0].contains("pg_sleep"));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn exhausted_pool_times_out() {
+        async_wrapper(|env| async move {
+            env.override_config(|config| {
+                config.max_pool_size = 1;
+                config.min_pool_idle = 0;
+                config.database_acquire_timeout = Duration::from_millis(100);
+            });
+
+            let pool = env.async_db().await.pool();
+            let held = pool.get_async().await?;
+
+            assert!(matches!(
+                pool.get_async().await,
+                Err(PoolError::AcquireTimeout)
+            ));
+            assert_eq!(env.instance_metrics().failed_db_connections.get(), 1);
+
+            drop(held);
+            pool.get_async().await?;
+
+            Ok(())
+        })
+    }
 }
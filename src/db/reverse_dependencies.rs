@@ -0,0 +1,106 @@
+use anyhow::Result;
+
+/// Recalculate `crates.reverse_dependency_count`, the number of crates whose
+/// latest release depends on the crate. Dev-dependencies are not counted.
+///
+/// Returns the number of crates whose count changed.
+pub async fn update_reverse_dependency_counts(conn: &mut sqlx::PgConnection) -> Result<u64> {
+    Ok(sqlx::query!(
+        r#"
+        WITH counts AS (
+            SELECT
+                dependency.value ->> 0 AS name,
+                COUNT(DISTINCT crates.id)::INTEGER AS count
+            FROM crates
+            INNER JOIN releases ON releases.id = crates.latest_version_id
+            CROSS JOIN json_array_elements(
+                CASE
+                    WHEN json_typeof(releases.dependencies) = 'array' THEN releases.dependencies
+                    ELSE '[]'::json
+                END
+            ) AS dependency
+            WHERE dependency.value ->> 2 IS DISTINCT FROM 'dev'
+            GROUP BY dependency.value ->> 0
+        )
+        UPDATE crates
+        SET reverse_dependency_count = COALESCE(counts.count, 0)
+        FROM crates AS c
+        LEFT JOIN counts ON counts.name = c.name
+        WHERE
+            crates.id = c.id AND
+            crates.reverse_dependency_count <> COALESCE(counts.count, 0)
+        "#
+    )
+    .execute(&mut *conn)
+    .await?
+    .rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::*;
+    use crate::utils::Dependency;
+
+    #[test]
+    fn counts_crates_depending_on_latest_release() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("1.0.0")
+                .create()
+                .await?;
+            for name in ["bar", "baz"] {
+                env.fake_release()
+                    .await
+                    .name(name)
+                    .version("1.0.0")
+                    .add_dependency(Dependency::new("foo".into(), "1.0.0".into()))
+                    .create()
+                    .await?;
+            }
+            // only dev-dependencies don't count
+            env.fake_release()
+                .await
+                .name("dev-only")
+                .version("1.0.0")
+                .add_dependency(
+                    Dependency::new("foo".into(), "1.0.0".into()).set_kind("dev".into()),
+                )
+                .create()
+                .await?;
+            // only older releases depending on foo don't count
+            env.fake_release()
+                .await
+                .name("former")
+                .version("1.0.0")
+                .add_dependency(Dependency::new("foo".into(), "1.0.0".into()))
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("former")
+                .version("2.0.0")
+                .create()
+                .await?;
+
+            let mut conn = env.async_db().await.async_conn().await;
+            update_reverse_dependency_counts(&mut conn).await?;
+
+            let count = |name: &'static str| {
+                sqlx::query_scalar!(
+                    "SELECT reverse_dependency_count FROM crates WHERE name = $1",
+                    name
+                )
+            };
+            assert_eq!(count("foo").fetch_one(&mut *conn).await?, 2);
+            assert_eq!(count("bar").fetch_one(&mut *conn).await?, 0);
+
+            // nothing changes when running it again
+            assert_eq!(update_reverse_dependency_counts(&mut conn).await?, 0);
+
+            Ok(())
+        })
+    }
+}
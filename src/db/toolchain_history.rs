@@ -0,0 +1,81 @@
+use crate::utils::{set_config, ConfigName};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::Connection as _;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ToolchainChange {
+    pub(crate) toolchain: String,
+    pub(crate) changed_at: DateTime<Utc>,
+}
+
+/// Set the toolchain used for new builds, and record the change in the
+/// append-only `toolchain_history`.
+pub async fn set_toolchain(conn: &mut sqlx::PgConnection, toolchain: &str) -> Result<()> {
+    let mut transaction = conn.begin().await?;
+
+    set_config(&mut transaction, ConfigName::Toolchain, toolchain).await?;
+    sqlx::query!(
+        "INSERT INTO toolchain_history (toolchain) VALUES ($1)",
+        toolchain
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// The most recent toolchain changes, newest first.
+pub(crate) async fn toolchain_history(
+    conn: &mut sqlx::PgConnection,
+    limit: i64,
+) -> Result<Vec<ToolchainChange>> {
+    Ok(sqlx::query_as!(
+        ToolchainChange,
+        "SELECT toolchain, changed_at
+         FROM toolchain_history
+         ORDER BY id DESC
+         LIMIT $1",
+        limit,
+    )
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::async_wrapper;
+    use crate::utils::get_config;
+
+    #[test]
+    fn set_toolchain_appends_history() {
+        async_wrapper(|env| async move {
+            let mut conn = env.async_db().await.async_conn().await;
+            assert!(toolchain_history(&mut conn, 10).await?.is_empty());
+
+            set_toolchain(&mut conn, "nightly-2025-01-01").await?;
+            set_toolchain(&mut conn, "nightly-2025-02-01").await?;
+
+            let history = toolchain_history(&mut conn, 10).await?;
+            assert_eq!(
+                history
+                    .iter()
+                    .map(|change| change.toolchain.as_str())
+                    .collect::<Vec<_>>(),
+                ["nightly-2025-02-01", "nightly-2025-01-01"]
+            );
+            assert!(history[0].changed_at >= history[1].changed_at);
+
+            assert_eq!(
+                get_config::<String>(&mut conn, ConfigName::Toolchain)
+                    .await?
+                    .as_deref(),
+                Some("nightly-2025-02-01")
+            );
+
+            Ok(())
+        })
+    }
+}
@@ -24,6 +24,9 @@ pub(crate) enum BuildStatus {
     Success,
     Failure,
     InProgress,
+    /// the build was intentionally not performed, for example
+    /// because the crate is blacklisted.
+    Skipped,
 }
 
 impl BuildStatus {
@@ -38,6 +41,7 @@ impl PartialEq<&str> for BuildStatus {
             Self::Success => *other == "success",
             Self::Failure => *other == "failure",
             Self::InProgress => *other == "in_progress",
+            Self::Skipped => *other == "skipped",
         }
     }
 }
@@ -50,6 +54,7 @@ mod tests {
     #[test_case(BuildStatus::Success, "success")]
     #[test_case(BuildStatus::Failure, "failure")]
     #[test_case(BuildStatus::InProgress, "in_progress")]
+    #[test_case(BuildStatus::Skipped, "skipped")]
     fn test_build_status_serialization(status: BuildStatus, expected: &str) {
         let serialized = serde_json::to_string(&status).unwrap();
         assert_eq!(serialized, format!("\"{}\"", expected));
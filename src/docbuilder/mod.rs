@@ -2,5 +2,5 @@ mod limits;
 mod rustwide_builder;
 
 pub(crate) use self::limits::Limits;
-pub(crate) use self::rustwide_builder::DocCoverage;
 pub use self::rustwide_builder::{BuildPackageSummary, PackageKind, RustwideBuilder};
+pub(crate) use self::rustwide_builder::{DocCoverage, RustdocWarnings};
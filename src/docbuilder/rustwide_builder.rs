@@ -1,7 +1,9 @@
 use crate::db::{
-    add_doc_coverage, add_path_into_remote_archive, finish_build, finish_release, initialize_build,
-    initialize_crate, initialize_release, types::BuildStatus, update_build_with_error,
-    update_crate_data_in_database, Pool,
+    add_doc_coverage, add_path_into_remote_archive, finish_build, finish_release,
+    has_successful_build_with_rustc, initialize_build, initialize_crate, initialize_release,
+    types::BuildStatus, update_build_cargo_args, update_build_notes, update_build_resource_usage,
+    update_build_rustdoc_warnings, update_build_skipped, update_build_with_error,
+    update_crate_data_in_database, BuildResourceUsage, Pool,
 };
 use crate::db::{
     file::{add_path_into_database, file_list_to_json},
@@ -10,6 +12,7 @@ use crate::db::{
 use crate::db::{CrateId, ReleaseId};
 use crate::docbuilder::Limits;
 use crate::error::Result;
+use crate::registry_api::ReleaseData;
 use crate::repositories::RepositoryStatsUpdater;
 use crate::storage::{rustdoc_archive_path, source_archive_path};
 use crate::utils::{
@@ -21,14 +24,18 @@ use crate::{db::blacklist::is_blacklisted, utils::MetadataPackage};
 use crate::{AsyncStorage, Config, Context, InstanceMetrics, RegistryApi, Storage};
 use anyhow::{anyhow, bail, Context as _, Error};
 use docsrs_metadata::{BuildTargets, Metadata, DEFAULT_TARGETS, HOST_TARGET};
+use once_cell::sync::Lazy;
 use regex::Regex;
 use rustwide::cmd::{Command, CommandError, SandboxBuilder, SandboxImage};
 use rustwide::logging::{self, LogStorage};
 use rustwide::toolchain::ToolchainError;
 use rustwide::{AlternativeRegistry, Build, Crate, Toolchain, Workspace, WorkspaceBuilder};
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::io::{Read as _, Seek as _, SeekFrom, Write as _};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Instant;
 use tokio::runtime::Runtime;
@@ -97,6 +104,7 @@ pub struct RustwideBuilder {
     registry_api: Arc<RegistryApi>,
     repository_stats_updater: Arc<RepositoryStatsUpdater>,
     workspace_initialize_time: Instant,
+    output_dir: Option<PathBuf>,
 }
 
 impl RustwideBuilder {
@@ -121,9 +129,16 @@ impl RustwideBuilder {
             registry_api: context.registry_api()?,
             repository_stats_updater: context.repository_stats_updater()?,
             workspace_initialize_time: Instant::now(),
+            output_dir: None,
         })
     }
 
+    /// Additionally write the unpacked documentation and a `build-report.json`
+    /// into this directory after each build, for local inspection.
+    pub fn set_output_dir(&mut self, output_dir: Option<PathBuf>) {
+        self.output_dir = output_dir;
+    }
+
     pub fn reinitialize_workspace_if_interval_passed<C: Context>(
         &mut self,
         context: &C,
@@ -410,9 +425,38 @@ impl RustwideBuilder {
 
         if is_blacklisted {
             info!("skipping build of {}, crate has been blacklisted", name);
+            self.runtime.block_on(async {
+                let mut conn = self.db.get_async().await?;
+                update_build_skipped(&mut conn, build_id, "crate has been blacklisted").await
+            })?;
             return Ok(false);
         }
 
+        if self.config.skip_up_to_date_builds {
+            let rustc_version = self.rustc_version()?;
+            let up_to_date = self.runtime.block_on(async {
+                let mut conn = self.db.get_async().await?;
+                has_successful_build_with_rustc(&mut conn, release_id, &rustc_version).await
+            })?;
+
+            if up_to_date {
+                info!(
+                    "skipping build of {} {}, already built successfully with {}",
+                    name, version, rustc_version
+                );
+                self.runtime.block_on(async {
+                    let mut conn = self.db.get_async().await?;
+                    update_build_skipped(
+                        &mut conn,
+                        build_id,
+                        &format!("already built successfully with {rustc_version}"),
+                    )
+                    .await
+                })?;
+                return Ok(false);
+            }
+        }
+
         let limits = self.get_limits(name)?;
         #[cfg(target_os = "linux")]
         if !self.config.disable_memory_limit {
@@ -453,7 +497,16 @@ impl RustwideBuilder {
 
             let krate = match kind {
                 PackageKind::Local(path) => Crate::local(path),
-                PackageKind::CratesIo => Crate::crates_io(name, version),
+                // crates from a custom registry index are downloaded from the `dl`
+                // endpoint that index lists in its `config.json`, like cargo does.
+                PackageKind::CratesIo => match self.config.registry_url {
+                    Some(ref registry_url) => Crate::registry(
+                        AlternativeRegistry::new(registry_url.as_str()),
+                        name,
+                        version,
+                    ),
+                    None => Crate::crates_io(name, version),
+                },
                 PackageKind::Registry(registry) => {
                     Crate::registry(AlternativeRegistry::new(registry), name, version)
                 }
@@ -465,6 +518,13 @@ impl RustwideBuilder {
         fs::create_dir_all(&self.config.temp_dir)?;
         let local_storage = tempfile::tempdir_in(&self.config.temp_dir)?;
 
+        let build_start = Instant::now();
+        let mut memory_tracker = self
+            .config
+            .build_cgroup_path
+            .as_deref()
+            .and_then(PeakMemoryTracker::start);
+
         let successful = build_dir
             .build(&self.toolchain, &krate, self.prepare_sandbox(&limits))
             .run(|build| {
@@ -488,8 +548,33 @@ impl RustwideBuilder {
                     default_target,
                     other_targets,
                 } = metadata.targets(self.config.include_default_targets);
+                // sorted, so the targets we build when we have to limit them
+                // don't change between builds.
+                let mut other_targets: Vec<&str> = other_targets.into_iter().collect();
+                other_targets.sort_unstable();
+                let mut notes: Vec<String> = [
+                    toolchain_mismatch_note(&metadata, &self.toolchain),
+                    target_limit_note(&other_targets, limits.targets()),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                for note in &notes {
+                    warn!("{}", note);
+                }
+                let fallback_target = self
+                    .config
+                    .fallback_target
+                    .clone()
+                    .filter(|target| target != default_target && !metadata.proc_macro);
+                let fallback_target = fallback_target.as_deref();
                 let mut targets = vec![default_target];
                 targets.extend(&other_targets);
+                if let Some(fallback_target) = fallback_target {
+                    if !targets.contains(&fallback_target) {
+                        targets.push(fallback_target);
+                    }
+                }
 
                 {
                     let _span = info_span!("fetch_build_std_dependencies").entered();
@@ -501,6 +586,7 @@ impl RustwideBuilder {
                 let mut successful_targets = Vec::new();
 
                 // Perform an initial build
+                let default_target_start = Instant::now();
                 let mut res =
                     self.execute_build(default_target, true, build, &limits, &metadata, false)?;
 
@@ -527,24 +613,56 @@ impl RustwideBuilder {
                         self.execute_build(default_target, true, build, &limits, &metadata, false)?;
                 }
 
+                let mut target_build_logs = HashMap::new();
+
+                // Some crates only fail on their default target, so we try the fallback
+                // target before giving up. When it succeeds, it becomes the default target
+                // of the release.
+                if let Some(fallback_target) = fallback_target.filter(|_| !res.result.successful) {
+                    info!(
+                        "build for default target {} failed, trying fallback target {}",
+                        default_target, fallback_target
+                    );
+                    let fallback_res = self.execute_build(
+                        fallback_target,
+                        true,
+                        build,
+                        &limits,
+                        &metadata,
+                        false,
+                    )?;
+                    if fallback_res.result.successful {
+                        let failed_res = std::mem::replace(&mut res, fallback_res);
+                        target_build_logs.insert(default_target, failed_res.build_log);
+                    } else {
+                        target_build_logs.insert(fallback_target, fallback_res.build_log);
+                    }
+                }
+
+                let mut target_reports = vec![TargetReport {
+                    target: res.target.clone(),
+                    successful: res.result.successful,
+                    duration_secs: default_target_start.elapsed().as_secs_f64(),
+                    rustdoc_warnings: res.rustdoc_warnings.count(),
+                }];
+
                 if res.result.successful {
                     if let Some(name) = res.cargo_metadata.root().library_name() {
                         let host_target = build.host_target_dir();
                         has_docs = host_target
-                            .join(default_target)
+                            .join(&res.target)
                             .join("doc")
                             .join(name)
                             .is_dir();
                     }
                 }
 
-                let mut target_build_logs = HashMap::new();
                 let documentation_size = if has_docs {
                     debug!("adding documentation for the default target to the database");
                     self.copy_docs(
                         &build.host_target_dir(),
                         local_storage.path(),
-                        default_target,
+                        &res.target,
                         true,
                     )?;
 
@@ -552,8 +670,13 @@ impl RustwideBuilder {
 
                     // Then build the documentation for all the targets
                     // Limit the number of targets so that no one can try to build all 200000 possible targets
-                    for target in other_targets.into_iter().take(limits.targets()) {
+                    for target in other_targets
+                        .into_iter()
+                        .filter(|&target| target != res.target)
+                        .take(limits.targets())
+                    {
                         debug!("building package {} {} for {}", name, version, target);
+                        let target_start = Instant::now();
                         let target_res = self.build_target(
                             target,
                             build,
@@ -562,6 +685,12 @@ impl RustwideBuilder {
                             &mut successful_targets,
                             &metadata,
                         )?;
+                        target_reports.push(TargetReport {
+                            target: target.to_string(),
+                            successful: successful_targets.iter().any(|t| t == target),
+                            duration_secs: target_start.elapsed().as_secs_f64(),
+                            rustdoc_warnings: target_res.rustdoc_warnings.count(),
+                        });
                         target_build_logs.insert(target, target_res.build_log);
                     }
                     let (file_list, new_alg) =
@@ -597,9 +726,34 @@ impl RustwideBuilder {
                     None,
                 ))?;
 
+                self.runtime.block_on(update_build_resource_usage(
+                    &mut async_conn,
+                    build_id,
+                    BuildResourceUsage {
+                        duration: build_start.elapsed(),
+                        peak_memory: memory_tracker.as_mut().and_then(PeakMemoryTracker::peak),
+                    },
+                ))?;
+
+                self.runtime.block_on(update_build_rustdoc_warnings(
+                    &mut async_conn,
+                    build_id,
+                    &res.rustdoc_warnings,
+                ))?;
+
+                self.runtime.block_on(update_build_cargo_args(
+                    &mut async_conn,
+                    build_id,
+                    &res.cargo_args,
+                ))?;
+
+                notes.extend(build_failure_notes(&res, &limits));
+                self.runtime
+                    .block_on(update_build_notes(&mut async_conn, build_id, &notes))?;
+
                 {
                     let _span = info_span!("store_build_logs").entered();
-                    let build_log_path = format!("build-logs/{build_id}/{default_target}.txt");
+                    let build_log_path = format!("build-logs/{build_id}/{}.txt", res.target);
                     self.storage.store_one(build_log_path, res.build_log)?;
                     for (target, log) in target_build_logs {
                         let build_log_path = format!("build-logs/{build_id}/{target}.txt");
@@ -607,6 +761,25 @@ impl RustwideBuilder {
                     }
                 }
 
+                if let Some(output_dir) = &self.output_dir {
+                    write_output_dir(
+                        output_dir,
+                        has_docs.then_some(local_storage.path()),
+                        &BuildReport {
+                            name,
+                            version,
+                            successful: res.result.successful,
+                            rustc_version: &res.result.rustc_version,
+                            docsrs_version: &res.result.docsrs_version,
+                            default_target: &res.target,
+                            duration_secs: build_start.elapsed().as_secs_f64(),
+                            rustdoc_warnings: res.rustdoc_warnings.count(),
+                            documentation_size,
+                            targets: target_reports,
+                        },
+                    )?;
+                }
+
                 if res.result.successful {
                     self.metrics.successful_builds.inc();
                 } else if res.cargo_metadata.root().is_library() {
@@ -615,23 +788,37 @@ impl RustwideBuilder {
                     self.metrics.non_library_builds.inc();
                 }
 
-                let release_data = if !is_local {
+                // the crate data includes the data of all releases, so one request covers both
+                let crate_data = if !is_local {
                     match self
                         .runtime
-                        .block_on(self.registry_api.get_release_data(name, version))
-                        .with_context(|| {
-                            format!("could not fetch releases-data for {name}-{version}")
-                        }) {
+                        .block_on(self.registry_api.get_crate_data(name))
+                    {
                         Ok(data) => Some(data),
                         Err(err) => {
-                            report_error(&err);
+                            report_error(&err.context(format!(
+                                "could not fetch crate data for {name}"
+                            )));
                             None
                         }
                     }
                 } else {
                     None
-                }
-                .unwrap_or_default();
+                };
+
+                let release_data = match crate_data
+                    .as_ref()
+                    .map(|data| data.release_data(version))
+                {
+                    Some(Ok(data)) => data,
+                    Some(Err(err)) => {
+                        report_error(&err.context(format!(
+                            "could not fetch releases-data for {name}-{version}"
+                        )));
+                        ReleaseData::default()
+                    }
+                    None => ReleaseData::default(),
+                };
 
                 let cargo_metadata = res.cargo_metadata.root();
                 let repository = self.get_repo(cargo_metadata)?;
@@ -683,18 +870,12 @@ impl RustwideBuilder {
                 }
 
                 // Some crates.io crate data is mutable, so we proactively update it during a release
-                if !is_local {
-                    match self
-                        .runtime
-                        .block_on(self.registry_api.get_crate_data(name))
-                    {
-                        Ok(crate_data) => self.runtime.block_on(update_crate_data_in_database(
-                            &mut async_conn,
-                            name,
-                            &crate_data,
-                        ))?,
-                        Err(err) => warn!("{:#?}", err),
-                    }
+                if let Some(crate_data) = crate_data {
+                    self.runtime.block_on(update_crate_data_in_database(
+                        &mut async_conn,
+                        name,
+                        &crate_data,
+                    ))?;
                 }
 
                 if res.result.successful {
@@ -778,7 +959,8 @@ impl RustwideBuilder {
             items_with_examples: 0,
         };
 
-        self.prepare_command(build, target, metadata, limits, rustdoc_flags)?
+        let (command, _) = self.prepare_command(build, target, metadata, limits, rustdoc_flags)?;
+        command
             .process_lines(&mut |line, _| {
                 if line.starts_with('{') && line.ends_with('}') {
                     let parsed = match serde_json::from_str::<HashMap<String, FileCoverage>>(line) {
@@ -835,6 +1017,8 @@ impl RustwideBuilder {
         let mut storage = LogStorage::new(log::LevelFilter::Info);
         storage.set_max_size(limits.max_log_size());
 
+        let mut rustdoc_warnings = RustdocWarnings::default();
+
         // we have to run coverage before the doc-build because currently it
         // deletes the doc-target folder.
         // https://github.com/rust-lang/cargo/issues/9447
@@ -847,12 +1031,40 @@ impl RustwideBuilder {
             }
         };
 
-        let successful = {
+        let mut cargo_args = Vec::new();
+        let (successful, timed_out) = {
             let _span = info_span!("cargo_build", target = %target, is_default_target).entered();
             logging::capture(&storage, || {
-                self.prepare_command(build, target, metadata, limits, rustdoc_flags)
-                    .and_then(|command| command.run().map_err(Error::from))
-                    .is_ok()
+                match self
+                    .prepare_command(build, target, metadata, limits, rustdoc_flags)
+                    .and_then(|(command, args)| {
+                        cargo_args = args;
+                        // cargo's JSON messages give us the lint names of the diagnostics,
+                        // the rendered diagnostics are put back into the build log.
+                        command
+                            .args(&["--message-format=json"])
+                            .process_lines(&mut |line, actions| match rustdoc_warnings
+                                .process_line(line)
+                            {
+                                None => actions.remove_line(),
+                                Some(Cow::Owned(rendered)) => {
+                                    actions.replace_with_lines(rendered.lines())
+                                }
+                                Some(Cow::Borrowed(_)) => {}
+                            })
+                            .run()
+                            .map_err(Error::from)
+                    }) {
+                    Ok(()) => (true, false),
+                    Err(err) => {
+                        let timed_out =
+                            matches!(err.downcast_ref(), Some(CommandError::Timeout(_)));
+                        if timed_out {
+                            log::error!("{}", timeout_message(limits));
+                        }
+                        (false, timed_out)
+                    }
+                }
             })
         };
 
@@ -883,6 +1095,9 @@ impl RustwideBuilder {
             cargo_metadata,
             build_log: storage.to_string(),
             target: target.to_string(),
+            timed_out,
+            rustdoc_warnings,
+            cargo_args,
         })
     }
 
@@ -893,7 +1108,7 @@ impl RustwideBuilder {
         metadata: &Metadata,
         limits: &Limits,
         mut rustdoc_flags_extras: Vec<String>,
-    ) -> Result<Command<'ws, 'pl>> {
+    ) -> Result<(Command<'ws, 'pl>, Vec<String>)> {
         // Add docs.rs specific arguments
         let mut cargo_args = vec![
             "--offline".into(),
@@ -960,7 +1175,7 @@ impl RustwideBuilder {
             command = command.env(key, val);
         }
 
-        Ok(command.args(&cargo_args))
+        Ok((command.args(&cargo_args), cargo_args))
     }
 
     #[instrument(skip(self))]
@@ -1000,6 +1215,220 @@ struct FullBuildResult {
     cargo_metadata: CargoMetadata,
     doc_coverage: Option<DocCoverage>,
     build_log: String,
+    /// the build was killed because it exceeded the crate's timeout limit
+    timed_out: bool,
+    rustdoc_warnings: RustdocWarnings,
+    /// the arguments cargo was invoked with for the doc build
+    cargo_args: Vec<String>,
+}
+
+/// Summary of a build, written to `build-report.json` when the builder has
+/// an output directory configured.
+#[derive(Debug, Serialize)]
+struct BuildReport<'a> {
+    name: &'a str,
+    version: &'a str,
+    successful: bool,
+    rustc_version: &'a str,
+    docsrs_version: &'a str,
+    default_target: &'a str,
+    duration_secs: f64,
+    rustdoc_warnings: i32,
+    documentation_size: Option<u64>,
+    targets: Vec<TargetReport>,
+}
+
+#[derive(Debug, Serialize)]
+struct TargetReport {
+    target: String,
+    successful: bool,
+    duration_secs: f64,
+    rustdoc_warnings: i32,
+}
+
+/// Write the unpacked docs (if any) and the build report into `output_dir`,
+/// replacing the results of a previous build.
+fn write_output_dir(output_dir: &Path, docs: Option<&Path>, report: &BuildReport) -> Result<()> {
+    let doc_dir = output_dir.join("doc");
+    if doc_dir.exists() {
+        fs::remove_dir_all(&doc_dir)?;
+    }
+    fs::create_dir_all(output_dir)?;
+    if let Some(docs) = docs {
+        copy_dir_all(docs, &doc_dir)?;
+    }
+
+    let report_path = output_dir.join("build-report.json");
+    serde_json::to_writer_pretty(fs::File::create(&report_path)?, report)?;
+    info!("wrote build report to {}", report_path.display());
+    Ok(())
+}
+
+fn timeout_message(limits: &Limits) -> String {
+    format!(
+        "build timed out after {}",
+        humantime::format_duration(limits.timeout())
+    )
+}
+
+/// log lines of failed builds that usually mean a native library or
+/// its headers are missing in the build environment.
+static MISSING_SYSTEM_DEPENDENCY: Lazy<Vec<Regex>> = Lazy::new(|| {
+    [
+        r"cannot find -l(?P<dep>[\w.+-]+)",
+        r"Package (?P<dep>[\w.+-]+) was not found in the pkg-config search path",
+        r"fatal error: (?P<dep>[\w./+-]+\.h): No such file or directory",
+        r"Could not run `(?P<dep>pkg-config)",
+    ]
+    .iter()
+    .map(|re| Regex::new(re).unwrap())
+    .collect()
+});
+
+fn missing_system_dependency_hint(build_log: &str) -> Option<String> {
+    let dependency = MISSING_SYSTEM_DEPENDENCY
+        .iter()
+        .find_map(|re| re.captures(build_log))?
+        .name("dep")?
+        .as_str()
+        .to_owned();
+
+    Some(format!(
+        "build may require system dependencies that are missing in the build environment \
+         (`{dependency}` not found), \
+         see https://docs.rs/about/builds#missing-dependencies"
+    ))
+}
+
+/// note for crates asking for a specific toolchain in their docs.rs metadata,
+/// which we can't honor yet.
+fn toolchain_mismatch_note(metadata: &Metadata, active: &Toolchain) -> Option<String> {
+    let requested = metadata.toolchain()?;
+    let active = active.to_string();
+    (requested != active).then(|| {
+        format!(
+            "crate requested the `{requested}` toolchain, \
+             but docs.rs can't pin toolchains per crate and used `{active}`"
+        )
+    })
+}
+
+/// note for crates requesting more targets than we build for them.
+///
+/// The default target is always built, the other targets are capped at `limit`.
+fn target_limit_note(other_targets: &[&str], limit: usize) -> Option<String> {
+    (other_targets.len() > limit).then(|| {
+        format!(
+            "crate requested {} targets, limited to {}",
+            other_targets.len() + 1,
+            limit + 1
+        )
+    })
+}
+
+/// notes explaining why a build failed, when we can tell.
+fn build_failure_notes(res: &FullBuildResult, limits: &Limits) -> Vec<String> {
+    if res.result.successful {
+        return Vec::new();
+    }
+
+    [
+        res.timed_out.then(|| timeout_message(limits)),
+        missing_system_dependency_hint(&res.build_log),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Tracks the peak memory usage of the cgroup our build sandboxes run in.
+///
+/// With cgroup v2, writing to `memory.peak` resets the peak value seen through
+/// that file descriptor, so we keep the file open for the duration of a build.
+/// Kernels without support for the reset just report the peak since the cgroup
+/// was created.
+struct PeakMemoryTracker(fs::File);
+
+impl PeakMemoryTracker {
+    fn start(cgroup_path: &Path) -> Option<Self> {
+        let path = cgroup_path.join("memory.peak");
+        let mut file = match fs::OpenOptions::new().read(true).write(true).open(&path) {
+            Ok(file) => file,
+            Err(err) => {
+                warn!(?path, ?err, "could not open cgroup memory stats");
+                return None;
+            }
+        };
+        if let Err(err) = file.write_all(b"reset\n") {
+            debug!(?err, "could not reset peak memory of build cgroup");
+        }
+        Some(Self(file))
+    }
+
+    /// peak memory in bytes since the tracker was started.
+    fn peak(&mut self) -> Option<u64> {
+        let mut content = String::new();
+        self.0.seek(SeekFrom::Start(0)).ok()?;
+        self.0.read_to_string(&mut content).ok()?;
+        content.trim().parse().ok()
+    }
+}
+
+/// Warnings emitted while documenting a crate, counted by lint name.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub(crate) struct RustdocWarnings(pub(crate) BTreeMap<String, i32>);
+
+impl RustdocWarnings {
+    /// Total number of warnings over all lints.
+    fn count(&self) -> i32 {
+        self.0.values().sum()
+    }
+
+    /// Handle a line of cargo's `--message-format=json` output.
+    ///
+    /// Returns the line as it should appear in the build log: the line itself when it's not
+    /// a JSON message, the human readable rendering of diagnostics, and `None` for all other
+    /// messages.
+    fn process_line<'a>(&mut self, line: &'a str) -> Option<Cow<'a, str>> {
+        #[derive(Deserialize)]
+        #[serde(tag = "reason", rename_all = "kebab-case")]
+        enum Message {
+            CompilerMessage {
+                message: Diagnostic,
+            },
+            #[serde(other)]
+            Other,
+        }
+
+        #[derive(Deserialize)]
+        struct Diagnostic {
+            level: String,
+            code: Option<DiagnosticCode>,
+            rendered: Option<String>,
+        }
+
+        #[derive(Deserialize)]
+        struct DiagnosticCode {
+            code: String,
+        }
+
+        if !line.starts_with('{') {
+            return Some(Cow::Borrowed(line));
+        }
+
+        match serde_json::from_str::<Message>(line) {
+            Ok(Message::CompilerMessage { message }) => {
+                // summaries like "3 warnings emitted" don't have a lint name.
+                if let (true, Some(code)) = (message.level == "warning", message.code) {
+                    *self.0.entry(code.code).or_default() += 1;
+                }
+                message.rendered.map(Cow::Owned)
+            }
+            Ok(Message::Other) => None,
+            Err(_) => Some(Cow::Borrowed(line)),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -1044,10 +1473,11 @@ mod tests {
     use std::iter;
 
     use super::*;
-    use crate::db::types::Feature;
-    use crate::registry_api::ReleaseData;
+    use crate::db::{types::Feature, Overrides};
     use crate::storage::CompressionAlgorithm;
     use crate::test::{wrapper, AxumRouterTestExt, TestEnvironment};
+    use std::time::Duration;
+    use test_case::test_case;
 
     fn get_features(
         env: &TestEnvironment,
@@ -1138,7 +1568,8 @@ mod tests {
                         b.build_status::TEXT as build_status,
                         b.docsrs_version,
                         b.rustc_version,
-                        b.documentation_size
+                        b.documentation_size,
+                        b.cargo_args
                     FROM
                         crates as c
                         INNER JOIN releases AS r ON c.id = r.crate_id
@@ -1163,6 +1594,12 @@ mod tests {
             assert_eq!(row.build_status.unwrap(), "success");
             assert!(row.source_size > 0);
             assert!(row.documentation_size.unwrap() > 0);
+            assert!(row
+                .cargo_args
+                .unwrap()
+                .iter()
+                .any(|arg| arg.starts_with("build.rustdocflags=")
+                    && arg.contains(r#""--cfg", "docsrs""#)));
 
             let mut targets: Vec<String> = row
                 .doc_targets
@@ -1432,6 +1869,54 @@ mod tests {
         });
     }
 
+    #[test]
+    #[ignore]
+    fn test_build_writes_output_dir() {
+        wrapper(|env| {
+            let crate_ = DUMMY_CRATE_NAME;
+            let version = DUMMY_CRATE_VERSION;
+            let output_dir = tempfile::tempdir()?;
+
+            let mut builder = RustwideBuilder::init(env).unwrap();
+            builder.update_toolchain()?;
+            builder.set_output_dir(Some(output_dir.path().to_path_buf()));
+            assert!(
+                builder
+                    .build_package(crate_, version, PackageKind::CratesIo)?
+                    .successful
+            );
+
+            let crate_path = crate_.replace('-', "_");
+            assert!(output_dir
+                .path()
+                .join("doc")
+                .join(&crate_path)
+                .join("index.html")
+                .is_file());
+
+            let report: serde_json::Value =
+                serde_json::from_slice(&fs::read(output_dir.path().join("build-report.json"))?)?;
+            assert_eq!(report["name"], crate_);
+            assert_eq!(report["version"], version);
+            assert_eq!(report["successful"], true);
+            assert_eq!(report["rustc_version"], builder.rustc_version()?);
+            assert_eq!(report["default_target"], HOST_TARGET);
+            assert!(report["duration_secs"].as_f64().unwrap() > 0.0);
+            assert!(report["rustdoc_warnings"].is_number());
+            assert!(report["documentation_size"].as_u64().unwrap() > 0);
+
+            let targets = report["targets"].as_array().unwrap();
+            assert_eq!(targets[0]["target"], HOST_TARGET);
+            assert_eq!(targets[0]["successful"], true);
+            for target in targets {
+                assert!(target["duration_secs"].is_number());
+                assert!(target["rustdoc_warnings"].is_number());
+            }
+
+            Ok(())
+        });
+    }
+
     #[test]
     #[ignore]
     fn test_cross_compile_non_host_default() {
@@ -1692,6 +2177,365 @@ mod tests {
         })
     }
 
+    #[test]
+    #[ignore]
+    fn test_fallback_target_when_default_target_fails() {
+        wrapper(|env| {
+            let fallback_target = "x86_64-unknown-linux-gnu";
+            env.override_config(|cfg| {
+                cfg.include_default_targets = false;
+                cfg.fallback_target = Some(fallback_target.into());
+            });
+
+            let mut builder = RustwideBuilder::init(env)?;
+            builder.update_toolchain()?;
+            // the crate fails to compile on its windows default target
+            assert!(
+                builder
+                    .build_local_package(Path::new("tests/crates/fallback-target"))?
+                    .successful
+            );
+
+            let row = env.runtime().block_on(async {
+                let mut conn = env.async_db().await.async_conn().await;
+                sqlx::query!(
+                    "SELECT r.rustdoc_status, r.default_target
+                     FROM crates AS c
+                     INNER JOIN releases AS r ON c.id = r.crate_id
+                     WHERE c.name = $1",
+                    "fallback-target",
+                )
+                .fetch_one(&mut *conn)
+                .await
+            })?;
+            assert_eq!(row.rustdoc_status, Some(true));
+            assert_eq!(row.default_target.as_deref(), Some(fallback_target));
+
+            let doc_archive = rustdoc_archive_path("fallback-target", "0.1.0");
+            assert!(env.storage().exists_in_archive(
+                &doc_archive,
+                None,
+                "fallback_target/index.html"
+            )?);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    #[ignore]
+    fn test_target_limit() {
+        wrapper(|env| {
+            let crate_ = "many-targets";
+            env.runtime().block_on(async {
+                let mut conn = env.async_db().await.async_conn().await;
+                Overrides::save(
+                    &mut conn,
+                    crate_,
+                    Overrides {
+                        targets: Some(1),
+                        ..Overrides::default()
+                    },
+                )
+                .await
+            })?;
+
+            let mut builder = RustwideBuilder::init(env)?;
+            builder.update_toolchain()?;
+            assert!(
+                builder
+                    .build_local_package(Path::new("tests/crates/many-targets"))?
+                    .successful
+            );
+
+            let row = env.runtime().block_on(async {
+                let mut conn = env.async_db().await.async_conn().await;
+                sqlx::query!(
+                    "SELECT r.doc_targets, b.notes
+                     FROM crates AS c
+                     INNER JOIN releases AS r ON c.id = r.crate_id
+                     INNER JOIN builds AS b ON b.rid = r.id
+                     WHERE c.name = $1",
+                    crate_,
+                )
+                .fetch_one(&mut *conn)
+                .await
+            })?;
+
+            // the default target, and the first of the other targets in sort order
+            let mut doc_targets: Vec<String> = serde_json::from_value(row.doc_targets.unwrap())?;
+            doc_targets.sort();
+            assert_eq!(
+                doc_targets,
+                ["aarch64-unknown-linux-gnu", "x86_64-unknown-linux-gnu"]
+            );
+            assert_eq!(
+                row.notes.as_deref(),
+                Some(&["crate requested 4 targets, limited to 2".to_owned()][..])
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test_case(
+        "= note: /usr/bin/ld: cannot find -lfoo: No such file or directory",
+        Some("foo")
+    )]
+    #[test_case(
+        "Package libsodium was not found in the pkg-config search path.",
+        Some("libsodium")
+    )]
+    #[test_case(
+        "src/shim.c:1:10: fatal error: openssl/ssl.h: No such file or directory",
+        Some("openssl/ssl.h")
+    )]
+    #[test_case("error[E0425]: cannot find value `x` in this scope", None)]
+    fn test_missing_system_dependency_hint(log: &str, dependency: Option<&str>) {
+        let hint = missing_system_dependency_hint(&format!("[INFO] running `cargo doc`\n{log}\n"));
+        match dependency {
+            Some(dependency) => {
+                let hint = hint.expect("missing hint");
+                assert!(hint.contains(&format!("`{dependency}` not found")));
+                assert!(hint.contains("/about/builds#missing-dependencies"));
+            }
+            None => assert!(hint.is_none()),
+        }
+    }
+
+    #[test]
+    fn test_toolchain_mismatch_note() -> Result<()> {
+        let metadata: Metadata = r#"
+            [package]
+            name = "test"
+
+            [package.metadata.docs.rs]
+            toolchain = "nightly-2024-01-01"
+        "#
+        .parse()?;
+
+        let note =
+            toolchain_mismatch_note(&metadata, &Toolchain::dist("nightly")).expect("missing note");
+        assert!(note.contains("`nightly-2024-01-01`"));
+        assert!(note.contains("used `nightly`"));
+
+        assert!(
+            toolchain_mismatch_note(&metadata, &Toolchain::dist("nightly-2024-01-01")).is_none()
+        );
+        assert!(
+            toolchain_mismatch_note(&Metadata::default(), &Toolchain::dist("nightly")).is_none()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_target_limit_note() -> Result<()> {
+        let metadata: Metadata = r#"
+            [package]
+            name = "test"
+
+            [package.metadata.docs.rs]
+            default-target = "x86_64-unknown-linux-gnu"
+            targets = [
+                "x86_64-unknown-linux-gnu",
+                "i686-unknown-linux-gnu",
+                "x86_64-pc-windows-msvc",
+                "aarch64-apple-darwin",
+            ]
+        "#
+        .parse()?;
+        let BuildTargets { other_targets, .. } = metadata.targets(true);
+        let other_targets: Vec<_> = other_targets.into_iter().collect();
+        assert_eq!(other_targets.len(), 3);
+
+        assert_eq!(
+            target_limit_note(&other_targets, 1).as_deref(),
+            Some("crate requested 4 targets, limited to 2")
+        );
+        assert!(target_limit_note(&other_targets, 3).is_none());
+        assert!(target_limit_note(&other_targets, 10).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rustdoc_warnings() {
+        let mut warnings = RustdocWarnings::default();
+
+        let broken_link = r#"{"reason":"compiler-message","message":{"level":"warning","code":{"code":"rustdoc::broken_intra_doc_links","explanation":null},"rendered":"warning: unresolved link to `Foo`\n"}}"#;
+        let missing_docs = r#"{"reason":"compiler-message","message":{"level":"warning","code":{"code":"missing_docs","explanation":null},"rendered":"warning: missing documentation for a struct\n"}}"#;
+        let summary = r#"{"reason":"compiler-message","message":{"level":"warning","code":null,"rendered":"warning: 3 warnings emitted\n"}}"#;
+        let artifact = r#"{"reason":"compiler-artifact","package_id":"foo 0.1.0"}"#;
+
+        assert_eq!(
+            warnings.process_line(broken_link).as_deref(),
+            Some("warning: unresolved link to `Foo`\n")
+        );
+        warnings.process_line(broken_link);
+        warnings.process_line(missing_docs);
+        assert_eq!(
+            warnings.process_line(summary).as_deref(),
+            Some("warning: 3 warnings emitted\n")
+        );
+        assert_eq!(warnings.process_line(artifact), None);
+        assert_eq!(
+            warnings
+                .process_line("   Documenting foo v0.1.0")
+                .as_deref(),
+            Some("   Documenting foo v0.1.0")
+        );
+
+        assert_eq!(
+            warnings.0,
+            BTreeMap::from([
+                ("missing_docs".to_string(), 1),
+                ("rustdoc::broken_intra_doc_links".to_string(), 2),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_peak_memory_tracker() -> Result<()> {
+        let cgroup = tempfile::tempdir()?;
+        let stats = cgroup.path().join("memory.peak");
+        fs::write(&stats, "0\n")?;
+
+        let mut tracker = PeakMemoryTracker::start(cgroup.path()).expect("tracker");
+        // the kernel would update the value in place during the build
+        fs::write(&stats, "536870912\n")?;
+        assert_eq!(tracker.peak(), Some(512 * 1024 * 1024));
+
+        assert!(PeakMemoryTracker::start(&cgroup.path().join("missing")).is_none());
+        Ok(())
+    }
+
+    #[test]
+    #[ignore]
+    fn test_skip_up_to_date_builds() {
+        wrapper(|env| {
+            env.override_config(|cfg| cfg.skip_up_to_date_builds = true);
+
+            let crate_ = DUMMY_CRATE_NAME;
+            let version = DUMMY_CRATE_VERSION;
+            let build_statuses = || {
+                env.runtime().block_on(async {
+                    let mut conn = env.async_db().await.async_conn().await;
+                    sqlx::query_scalar!(
+                        r#"SELECT b.build_status as "build_status: BuildStatus"
+                           FROM crates as c
+                           INNER JOIN releases as r on c.id = r.crate_id
+                           INNER JOIN builds as b on b.rid = r.id
+                           WHERE c.name = $1 and r.version = $2
+                           ORDER BY b.id"#,
+                        crate_,
+                        version,
+                    )
+                    .fetch_all(&mut *conn)
+                    .await
+                })
+            };
+
+            let mut builder = RustwideBuilder::init(env)?;
+            builder.update_toolchain()?;
+
+            assert!(
+                builder
+                    .build_package(crate_, version, PackageKind::CratesIo)?
+                    .successful
+            );
+            // built with the current toolchain, is skipped
+            builder.build_package(crate_, version, PackageKind::CratesIo)?;
+            assert_eq!(
+                build_statuses()?,
+                vec![BuildStatus::Success, BuildStatus::Skipped]
+            );
+
+            // pretend the existing build used an older toolchain
+            env.runtime().block_on(async {
+                let mut conn = env.async_db().await.async_conn().await;
+                sqlx::query!(
+                    "UPDATE builds SET rustc_version = 'rustc 1.0.0 (a59de37e9 2015-05-13)'"
+                )
+                .execute(&mut *conn)
+                .await
+            })?;
+            assert!(
+                builder
+                    .build_package(crate_, version, PackageKind::CratesIo)?
+                    .successful
+            );
+            assert_eq!(
+                build_statuses()?,
+                vec![
+                    BuildStatus::Success,
+                    BuildStatus::Skipped,
+                    BuildStatus::Success
+                ]
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    #[ignore]
+    fn test_build_timeout_override() {
+        wrapper(|env| {
+            let crate_ = "slow-build";
+            let version = "0.1.0";
+            env.runtime().block_on(async {
+                let mut conn = env.async_db().await.async_conn().await;
+                Overrides::save(
+                    &mut conn,
+                    crate_,
+                    Overrides {
+                        timeout: Some(Duration::from_secs(5)),
+                        ..Overrides::default()
+                    },
+                )
+                .await
+            })?;
+
+            let mut builder = RustwideBuilder::init(env)?;
+            builder.update_toolchain()?;
+            // the build script sleeps far longer than the overridden timeout
+            assert!(
+                !builder
+                    .build_local_package(Path::new("tests/crates/slow-build"))?
+                    .successful
+            );
+
+            let row = env.runtime().block_on(async {
+                let mut conn = env.async_db().await.async_conn().await;
+                sqlx::query!(
+                    r#"SELECT
+                       rustc_version,
+                       docsrs_version,
+                       build_status as "build_status: BuildStatus",
+                       errors,
+                       notes
+                       FROM
+                       crates as c
+                       INNER JOIN releases as r on c.id = r.crate_id
+                       INNER JOIN builds as b on b.rid = r.id
+                       WHERE c.name = $1 and r.version = $2"#,
+                    crate_,
+                    version,
+                )
+                .fetch_one(&mut *conn)
+                .await
+            })?;
+
+            assert_eq!(row.build_status, BuildStatus::Failure);
+            assert!(row.errors.is_none());
+            assert_eq!(
+                row.notes.as_deref(),
+                Some(&["build timed out after 5s".to_owned()][..])
+            );
+
+            Ok(())
+        })
+    }
+
     #[test]
     #[ignore]
     fn test_workspace_reinitialize_at_once() {
@@ -1,17 +1,34 @@
 use crate::error::Result;
 use crate::utils::report_error;
+use crate::Config;
 use anyhow::Context;
 use crates_index_diff::gix;
 use std::path::PathBuf;
 use std::process::Command;
 use std::sync::atomic::AtomicBool;
 
+/// The git URL of the crates.io registry index, used when no custom registry is configured.
+const CRATES_IO_INDEX_URL: &str = "https://github.com/rust-lang/crates.io-index";
+
 pub struct Index {
     path: PathBuf,
     repository_url: Option<String>,
 }
 
 impl Index {
+    /// Open the registry index configured in `config`.
+    ///
+    /// Uses `registry_url` when set so alternate registries can be watched,
+    /// and falls back to the crates.io index otherwise.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let path = config.registry_index_path.clone();
+        if let Some(registry_url) = config.registry_url.clone() {
+            Self::from_url(path, registry_url)
+        } else {
+            Self::new(path)
+        }
+    }
+
     pub fn from_url(path: PathBuf, url: String) -> Result<Self> {
         crates_index_diff::Index::from_path_or_cloned_with_options(
             &path,
@@ -62,7 +79,7 @@ impl Index {
         let repo_url = self
             .repository_url
             .as_deref()
-            .unwrap_or("https://github.com/rust-lang/crates.io-index");
+            .unwrap_or(CRATES_IO_INDEX_URL);
         let mut index = crates_index::GitIndex::with_path(&self.path, repo_url)?;
         index.update()?;
         Ok(index)
@@ -85,3 +102,56 @@ impl Index {
         self.repository_url.as_deref()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::wrapper;
+    use std::path::Path;
+
+    fn init_empty_repository(path: &Path) -> Result<()> {
+        let status = Command::new("git")
+            .args(["init", "--quiet"])
+            .arg(path)
+            .status()?;
+        anyhow::ensure!(status.success(), "git init failed");
+        Ok(())
+    }
+
+    #[test]
+    fn from_config_uses_custom_registry_url() {
+        wrapper(|env| {
+            let dir = tempfile::tempdir()?;
+            init_empty_repository(dir.path())?;
+
+            env.override_config(|config| {
+                config.registry_index_path = dir.path().to_owned();
+                config.registry_url = Some("https://registry.example.com/git/index".into());
+            });
+
+            let index = Index::from_config(&env.config())?;
+            assert_eq!(
+                index.repository_url(),
+                Some("https://registry.example.com/git/index")
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn from_config_defaults_to_crates_io() {
+        wrapper(|env| {
+            let dir = tempfile::tempdir()?;
+            init_empty_repository(dir.path())?;
+
+            env.override_config(|config| {
+                config.registry_index_path = dir.path().to_owned();
+                config.registry_url = None;
+            });
+
+            let index = Index::from_config(&env.config())?;
+            assert_eq!(index.repository_url(), None);
+            Ok(())
+        })
+    }
+}
@@ -37,6 +37,12 @@ mod target {
     //! [`crate::target::TargetAtom`] is an interned string type for rustc targets, such as
     //! `x86_64-unknown-linux-gnu`. See the [`string_cache`] docs for usage examples.
     include!(concat!(env!("OUT_DIR"), "/target_atom.rs"));
+
+    /// whether the target is in the `rustc --print target-list` output at build time.
+    pub(crate) fn is_known_target(target: &str) -> bool {
+        use string_cache::StaticAtomSet as _;
+        TargetAtomStaticSet::get().atoms.contains(&target)
+    }
 }
 
 use web::page::GlobalAlert;
@@ -105,6 +105,10 @@ metrics! {
         pub(crate) routes_visited: IntCounterVec["route"],
         /// The response times of various docs.rs routes
         pub(crate) response_time: HistogramVec["route"],
+        /// The number of responses served with each cache policy
+        pub(crate) cache_policy: IntCounterVec["policy"],
+        /// How crate name and version requests were resolved
+        pub(crate) version_match_outcomes: IntCounterVec["outcome"],
 
         /// Count of recently accessed crates
         pub(crate) recent_crates: IntGaugeVec["duration"],
@@ -132,6 +136,11 @@ metrics! {
 
         /// the number of "I'm feeling lucky" searches for crates
         pub(crate) im_feeling_lucky_searches: IntCounter,
+
+        /// The number of GitHub API calls left in the current rate limit window
+        pub(crate) github_rate_limit_remaining: IntGauge,
+        /// When the current GitHub rate limit window resets, as a unix timestamp
+        pub(crate) github_rate_limit_reset: IntGauge,
     }
 
     // The Rust prometheus library treats the namespace as the "prefix" of the metric name: a
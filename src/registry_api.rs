@@ -1,7 +1,13 @@
-use crate::{error::Result, utils::retry_async};
+use crate::{
+    error::Result,
+    utils::{retry_async, sized_buffer::read_json_limited},
+};
 use anyhow::{anyhow, bail, Context};
 use chrono::{DateTime, Utc};
-use reqwest::header::{HeaderValue, ACCEPT, USER_AGENT};
+use reqwest::{
+    header::{HeaderValue, ACCEPT, USER_AGENT},
+    StatusCode,
+};
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use std::fmt;
@@ -18,15 +24,34 @@ const APP_USER_AGENT: &str = concat!(
 pub struct RegistryApi {
     api_base: Url,
     max_retries: u32,
+    max_body_size: usize,
     client: reqwest::Client,
 }
 
 #[derive(Debug)]
 pub struct CrateData {
     pub(crate) owners: Vec<CrateOwner>,
+    /// registry data for all versions of the crate
+    pub(crate) releases: Vec<(Version, ReleaseData)>,
+    /// keywords of the crate, as shown on crates.io
+    pub(crate) keywords: Vec<String>,
+    /// category slugs of the crate, as shown on crates.io
+    pub(crate) categories: Vec<String>,
 }
 
-#[derive(Debug)]
+impl CrateData {
+    /// Returns the registry data of a single version of the crate
+    pub(crate) fn release_data(&self, version: &str) -> Result<ReleaseData> {
+        let version = Version::parse(version)?;
+        self.releases
+            .iter()
+            .find(|(num, _)| *num == version)
+            .map(|(_, data)| data.clone())
+            .with_context(|| anyhow!("Could not find version in response"))
+    }
+}
+
+#[derive(Debug, Clone)]
 pub(crate) struct ReleaseData {
     pub(crate) release_time: DateTime<Utc>,
     pub(crate) yanked: bool,
@@ -88,8 +113,35 @@ pub(crate) struct Search {
     pub(crate) meta: SearchMeta,
 }
 
+#[derive(Deserialize)]
+struct VersionData {
+    num: Version,
+    #[serde(default = "Utc::now")]
+    created_at: DateTime<Utc>,
+    #[serde(default)]
+    yanked: bool,
+    #[serde(default)]
+    downloads: i32,
+}
+
+#[derive(Deserialize)]
+struct CrateResponse {
+    #[serde(rename = "crate")]
+    krate: CrateInfo,
+    #[serde(default)]
+    versions: Vec<VersionData>,
+}
+
+#[derive(Deserialize)]
+struct CrateInfo {
+    #[serde(default)]
+    keywords: Option<Vec<String>>,
+    #[serde(default)]
+    categories: Option<Vec<String>>,
+}
+
 impl RegistryApi {
-    pub fn new(api_base: Url, max_retries: u32) -> Result<Self> {
+    pub fn new(api_base: Url, max_retries: u32, max_body_size: usize) -> Result<Self> {
         let headers = vec![
             (USER_AGENT, HeaderValue::from_static(APP_USER_AGENT)),
             (ACCEPT, HeaderValue::from_static("application/json")),
@@ -105,6 +157,7 @@ impl RegistryApi {
             api_base,
             client,
             max_retries,
+            max_body_size,
         })
     }
 
@@ -115,54 +168,46 @@ impl RegistryApi {
             .await
             .context(format!("Failed to get owners for {name}"))?;
 
-        Ok(CrateData { owners })
-    }
-
-    #[instrument(skip(self))]
-    pub(crate) async fn get_release_data(&self, name: &str, version: &str) -> Result<ReleaseData> {
-        let (release_time, yanked, downloads) = self
-            .get_release_time_yanked_downloads(name, version)
+        // versions, keywords and categories all come from the same response
+        let response = self
+            .get_crate(name)
             .await
-            .context(format!("Failed to get crate data for {name}-{version}"))?;
+            .context(format!("Failed to get crate data for {name}"))?;
 
-        Ok(ReleaseData {
-            release_time,
-            yanked,
-            downloads,
+        let releases = response
+            .versions
+            .into_iter()
+            .map(|version| {
+                (
+                    version.num,
+                    ReleaseData {
+                        release_time: version.created_at,
+                        yanked: version.yanked,
+                        downloads: version.downloads,
+                    },
+                )
+            })
+            .collect();
+
+        Ok(CrateData {
+            owners,
+            releases,
+            keywords: response.krate.keywords.unwrap_or_default(),
+            categories: response.krate.categories.unwrap_or_default(),
         })
     }
 
-    /// Get release_time, yanked and downloads from the registry's API
-    async fn get_release_time_yanked_downloads(
-        &self,
-        name: &str,
-        version: &str,
-    ) -> Result<(DateTime<Utc>, bool, i32)> {
+    /// Fetch a crate with all its versions from the registry's API
+    async fn get_crate(&self, name: &str) -> Result<CrateResponse> {
         let url = {
             let mut url = self.api_base.clone();
             url.path_segments_mut()
                 .map_err(|()| anyhow!("Invalid API url"))?
-                .extend(&["api", "v1", "crates", name, "versions"]);
+                .extend(&["api", "v1", "crates", name]);
             url
         };
 
-        #[derive(Deserialize)]
-        struct Response {
-            versions: Vec<VersionData>,
-        }
-
-        #[derive(Deserialize)]
-        struct VersionData {
-            num: Version,
-            #[serde(default = "Utc::now")]
-            created_at: DateTime<Utc>,
-            #[serde(default)]
-            yanked: bool,
-            #[serde(default)]
-            downloads: i32,
-        }
-
-        let response: Response = retry_async(
+        let response = retry_async(
             || async {
                 Ok(self
                     .client
@@ -172,19 +217,11 @@ impl RegistryApi {
                     .error_for_status()?)
             },
             self.max_retries,
+            is_retryable,
         )
-        .await?
-        .json()
         .await?;
 
-        let version = Version::parse(version)?;
-        let version = response
-            .versions
-            .into_iter()
-            .find(|data| data.num == version)
-            .with_context(|| anyhow!("Could not find version in response"))?;
-
-        Ok((version.created_at, version.yanked, version.downloads))
+        read_json_limited(response, self.max_body_size).await
     }
 
     /// Fetch owners from the registry's API
@@ -212,7 +249,7 @@ impl RegistryApi {
             kind: Option<OwnerKind>,
         }
 
-        let response: Response = retry_async(
+        let response = retry_async(
             || async {
                 Ok(self
                     .client
@@ -222,10 +259,10 @@ impl RegistryApi {
                     .error_for_status()?)
             },
             self.max_retries,
+            is_retryable,
         )
-        .await?
-        .json()
         .await?;
+        let response: Response = read_json_limited(response, self.max_body_size).await?;
 
         let result = response
             .users
@@ -270,7 +307,7 @@ impl RegistryApi {
             url
         };
 
-        let response: SearchResponse = retry_async(
+        let response = retry_async(
             || async {
                 Ok(self
                     .client
@@ -280,10 +317,10 @@ impl RegistryApi {
                     .error_for_status()?)
             },
             self.max_retries,
+            is_retryable,
         )
-        .await?
-        .json()
         .await?;
+        let response: SearchResponse = read_json_limited(response, self.max_body_size).await?;
 
         if let Some(errors) = response.errors {
             let messages: Vec<_> = errors.into_iter().map(|e| e.detail).collect();
@@ -301,3 +338,134 @@ impl RegistryApi {
         Ok(Search { crates, meta })
     }
 }
+
+/// Client errors (4xx) from the registry are permanent, so retrying them only
+/// wastes time. The exception is `429 Too Many Requests`, which is worth waiting for.
+fn is_retryable(err: &anyhow::Error) -> bool {
+    match err
+        .downcast_ref::<reqwest::Error>()
+        .and_then(reqwest::Error::status)
+    {
+        Some(status) => !status.is_client_error() || status == StatusCode::TOO_MANY_REQUESTS,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let mut crates_io = mockito::Server::new_async().await;
+        let mock = crates_io
+            .mock("GET", "/api/v1/crates/nonexistent/owners")
+            .with_status(404)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let api = RegistryApi::new(crates_io.url().parse().unwrap(), 3, 1024).unwrap();
+        assert!(api.get_crate_data("nonexistent").await.is_err());
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_retried_up_to_the_limit() {
+        let mut crates_io = mockito::Server::new_async().await;
+        let mock = crates_io
+            .mock("GET", "/api/v1/crates/flaky/owners")
+            .with_status(503)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let api = RegistryApi::new(crates_io.url().parse().unwrap(), 1, 1024).unwrap();
+        assert!(api.get_crate_data("flaky").await.is_err());
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn too_many_requests_is_retried() {
+        let mut crates_io = mockito::Server::new_async().await;
+        let mock = crates_io
+            .mock("GET", "/api/v1/crates/popular/owners")
+            .with_status(429)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let api = RegistryApi::new(crates_io.url().parse().unwrap(), 1, 1024).unwrap();
+        assert!(api.get_crate_data("popular").await.is_err());
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn keywords_and_categories_are_parsed() {
+        let mut crates_io = mockito::Server::new_async().await;
+        crates_io
+            .mock("GET", "/api/v1/crates/foo/owners")
+            .with_body(r#"{"users": []}"#)
+            .create_async()
+            .await;
+        let versions = crates_io
+            .mock("GET", "/api/v1/crates/foo/versions")
+            .expect(0)
+            .create_async()
+            .await;
+        crates_io
+            .mock("GET", "/api/v1/crates/foo")
+            .with_body(
+                r#"{
+                    "crate": {
+                        "id": "foo",
+                        "keywords": ["parser", "no-std"],
+                        "categories": ["parsing", "development-tools::testing"]
+                    },
+                    "versions": [{"num": "0.1.0", "downloads": 42}]
+                }"#,
+            )
+            .create_async()
+            .await;
+
+        let api = RegistryApi::new(crates_io.url().parse().unwrap(), 0, 1024).unwrap();
+        let data = api.get_crate_data("foo").await.unwrap();
+
+        // everything but the owners is read from the single crate response
+        versions.assert_async().await;
+        let release = data.release_data("0.1.0").unwrap();
+        assert_eq!(release.downloads, 42);
+        assert!(!release.yanked);
+        assert!(data.release_data("0.2.0").is_err());
+
+        assert_eq!(data.keywords, vec!["parser", "no-std"]);
+        assert_eq!(
+            data.categories,
+            vec!["parsing", "development-tools::testing"]
+        );
+    }
+
+    #[tokio::test]
+    async fn oversized_responses_are_rejected() {
+        let mut crates_io = mockito::Server::new_async().await;
+        crates_io
+            .mock("GET", "/api/v1/crates/huge/owners")
+            .with_body(format!(
+                r#"{{"users": [], "padding": "{}"}}"#,
+                "x".repeat(2048)
+            ))
+            .create_async()
+            .await;
+
+        let api = RegistryApi::new(crates_io.url().parse().unwrap(), 0, 1024).unwrap();
+        let err = api.get_crate_data("huge").await.unwrap_err();
+        assert!(
+            err.downcast_ref::<crate::error::SizeLimitReached>()
+                .is_some(),
+            "{err:?}"
+        );
+    }
+}
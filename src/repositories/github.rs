@@ -1,5 +1,6 @@
 use crate::error::Result;
-use crate::Config;
+use crate::utils::sized_buffer::read_json_limited;
+use crate::{Config, InstanceMetrics};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use reqwest::{
@@ -7,6 +8,7 @@ use reqwest::{
     Client as HttpClient,
 };
 use serde::Deserialize;
+use std::sync::Arc;
 use tracing::{trace, warn};
 
 use crate::repositories::{
@@ -47,18 +49,21 @@ pub struct GitHub {
     endpoint: String,
     client: HttpClient,
     github_updater_min_rate_limit: u32,
+    max_body_size: usize,
+    metrics: Arc<InstanceMetrics>,
 }
 
 impl GitHub {
     /// Returns `Err` if the access token has invalid syntax (but *not* if it isn't authorized).
     /// Returns `Ok(None)` if there is no access token.
-    pub fn new(config: &Config) -> Result<Option<Self>> {
-        Self::with_custom_endpoint(config, "https://api.github.com/graphql")
+    pub fn new(config: &Config, metrics: Arc<InstanceMetrics>) -> Result<Option<Self>> {
+        Self::with_custom_endpoint(config, "https://api.github.com/graphql", metrics)
     }
 
     pub fn with_custom_endpoint<E: AsRef<str>>(
         config: &Config,
         endpoint: E,
+        metrics: Arc<InstanceMetrics>,
     ) -> Result<Option<Self>> {
         let mut headers = HeaderMap::new();
         headers.insert(USER_AGENT, HeaderValue::from_static(APP_USER_AGENT));
@@ -80,6 +85,8 @@ impl GitHub {
             client,
             endpoint: endpoint.as_ref().to_owned(),
             github_updater_min_rate_limit: config.github_updater_min_rate_limit,
+            max_body_size: config.max_upstream_body_bytes,
+            metrics,
         }))
     }
 }
@@ -191,7 +198,7 @@ impl GitHub {
         query: &str,
         variables: impl serde::Serialize,
     ) -> Result<GraphResponse<T>> {
-        Ok(self
+        let response = self
             .client
             .post(&self.endpoint)
             .json(&serde_json::json!({
@@ -199,10 +206,29 @@ impl GitHub {
                 "variables": variables,
             }))
             .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?)
+            .await?;
+
+        self.record_rate_limit(response.headers());
+
+        read_json_limited(response.error_for_status()?, self.max_body_size).await
+    }
+
+    /// Record how much of the API rate limit is left, so we can alert before we
+    /// run out of calls instead of only noticing when the updater stops.
+    fn record_rate_limit(&self, headers: &HeaderMap) {
+        let parse = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<i64>().ok())
+        };
+
+        if let Some(remaining) = parse("x-ratelimit-remaining") {
+            self.metrics.github_rate_limit_remaining.set(remaining);
+        }
+        if let Some(reset) = parse("x-ratelimit-reset") {
+            self.metrics.github_rate_limit_reset.set(reset);
+        }
     }
 }
 
@@ -266,15 +292,20 @@ struct GraphIssues {
 
 #[cfg(test)]
 mod tests {
-    use super::{Config, GitHub};
+    use super::{Config, GitHub, InstanceMetrics};
     use crate::repositories::updater::{repository_name, RepositoryForge};
     use crate::repositories::RateLimitReached;
+    use std::sync::Arc;
 
-    async fn mock_server_and_github(config: &Config) -> (mockito::ServerGuard, GitHub) {
+    async fn mock_server_and_github(
+        config: &Config,
+        metrics: Arc<InstanceMetrics>,
+    ) -> (mockito::ServerGuard, GitHub) {
         let server = mockito::Server::new_async().await;
-        let updater = GitHub::with_custom_endpoint(config, format!("{}/graphql", server.url()))
-            .expect("GitHub::new failed")
-            .unwrap();
+        let updater =
+            GitHub::with_custom_endpoint(config, format!("{}/graphql", server.url()), metrics)
+                .expect("GitHub::new failed")
+                .unwrap();
 
         (server, updater)
     }
@@ -284,7 +315,8 @@ mod tests {
         crate::test::async_wrapper(|env| async move {
             let mut config = env.base_config();
             config.github_accesstoken = Some("qsjdnfqdq".to_owned());
-            let (mut server, updater) = mock_server_and_github(&config).await;
+            let (mut server, updater) =
+                mock_server_and_github(&config, env.instance_metrics()).await;
 
             let _m1 = server
                 .mock("POST", "/graphql")
@@ -307,7 +339,8 @@ mod tests {
         crate::test::async_wrapper(|env| async move {
             let mut config = env.base_config();
             config.github_accesstoken = Some("qsjdnfqdq".to_owned());
-            let (mut server, updater) = mock_server_and_github(&config).await;
+            let (mut server, updater) =
+                mock_server_and_github(&config, env.instance_metrics()).await;
 
             let _m1 = server
                 .mock("POST", "/graphql")
@@ -328,7 +361,8 @@ mod tests {
         crate::test::async_wrapper(|env| async move {
             let mut config = env.base_config();
             config.github_accesstoken = Some("qsjdnfqdq".to_owned());
-            let (mut server, updater) = mock_server_and_github(&config).await;
+            let (mut server, updater) =
+                mock_server_and_github(&config, env.instance_metrics()).await;
 
             let _m1 = server
                 .mock("POST", "/graphql")
@@ -355,7 +389,8 @@ mod tests {
         crate::test::async_wrapper(|env| async move {
             let mut config = env.base_config();
             config.github_accesstoken = Some("qsjdnfqdq".to_owned());
-            let (mut server, updater) = mock_server_and_github(&config).await;
+            let (mut server, updater) =
+                mock_server_and_github(&config, env.instance_metrics()).await;
 
             let _m1 = server
                 .mock("POST", "/graphql")
@@ -384,4 +419,33 @@ mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn records_rate_limit_headers() {
+        crate::test::async_wrapper(|env| async move {
+            let mut config = env.base_config();
+            config.github_accesstoken = Some("qsjdnfqdq".to_owned());
+            let metrics = env.instance_metrics();
+            let (mut server, updater) = mock_server_and_github(&config, metrics.clone()).await;
+
+            let _m1 = server
+                .mock("POST", "/graphql")
+                .with_header("content-type", "application/json")
+                .with_header("x-ratelimit-remaining", "4321")
+                .with_header("x-ratelimit-reset", "1700000000")
+                .with_body(r#"{"data": {"repository": null}}"#)
+                .create();
+
+            updater
+                .fetch_repository(
+                    &repository_name("https://github.com/foo/bar").expect("repository_name failed"),
+                )
+                .await
+                .expect("fetch_repository failed");
+
+            assert_eq!(metrics.github_rate_limit_remaining.get(), 4321);
+            assert_eq!(metrics.github_rate_limit_reset.get(), 1700000000);
+            Ok(())
+        });
+    }
 }
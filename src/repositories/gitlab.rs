@@ -1,4 +1,5 @@
 use crate::error::Result;
+use crate::utils::sized_buffer::read_json_limited;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use reqwest::{
@@ -45,17 +46,28 @@ pub struct GitLab {
     client: HttpClient,
     host: &'static str,
     endpoint: String,
+    max_body_size: usize,
 }
 
 impl GitLab {
-    pub fn new(host: &'static str, access_token: &Option<String>) -> Result<Self> {
-        Self::with_custom_endpoint(host, access_token, format!("https://{}/api/graphql", host))
+    pub fn new(
+        host: &'static str,
+        access_token: &Option<String>,
+        max_body_size: usize,
+    ) -> Result<Self> {
+        Self::with_custom_endpoint(
+            host,
+            access_token,
+            format!("https://{}/api/graphql", host),
+            max_body_size,
+        )
     }
 
     pub fn with_custom_endpoint<E: AsRef<str>>(
         host: &'static str,
         access_token: &Option<String>,
         endpoint: E,
+        max_body_size: usize,
     ) -> Result<Self> {
         let mut headers = HeaderMap::new();
         headers.insert(USER_AGENT, HeaderValue::from_static(APP_USER_AGENT));
@@ -78,6 +90,7 @@ impl GitLab {
             client,
             host,
             endpoint: endpoint.as_ref().to_string(),
+            max_body_size,
         })
     }
 }
@@ -203,7 +216,10 @@ impl GitLab {
             .headers()
             .get("RateLimit-Remaining")
             .and_then(|x| usize::from_str(x.to_str().ok()?).ok());
-        Ok((res.json().await?, rate_limit))
+        Ok((
+            read_json_limited(res, self.max_body_size).await?,
+            rate_limit,
+        ))
     }
 }
 
@@ -274,6 +290,7 @@ mod tests {
             "gitlab.com",
             &None,
             format!("{}/api/graphql", server.url()),
+            1024 * 1024,
         )
         .expect("GitLab::new failed");
 
@@ -1,7 +1,7 @@
 use crate::error::Result;
 use crate::repositories::{GitHub, GitLab, RateLimitReached};
 use crate::utils::MetadataPackage;
-use crate::{db::Pool, Config};
+use crate::{db::Pool, Config, InstanceMetrics};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use futures_util::stream::TryStreamExt;
@@ -9,6 +9,7 @@ use once_cell::sync::Lazy;
 use regex::Regex;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::sync::Arc;
 use tracing::{debug, info, trace, warn};
 
 #[async_trait]
@@ -67,15 +68,23 @@ impl fmt::Debug for RepositoryStatsUpdater {
 }
 
 impl RepositoryStatsUpdater {
-    pub fn new(config: &Config, pool: Pool) -> Self {
+    pub fn new(config: &Config, pool: Pool, metrics: Arc<InstanceMetrics>) -> Self {
         let mut updaters: Vec<Box<dyn RepositoryForge + Send + Sync>> = Vec::new();
-        if let Ok(Some(updater)) = GitHub::new(config) {
+        if let Ok(Some(updater)) = GitHub::new(config, metrics) {
             updaters.push(Box::new(updater));
         }
-        if let Ok(updater) = GitLab::new("gitlab.com", &config.gitlab_accesstoken) {
+        if let Ok(updater) = GitLab::new(
+            "gitlab.com",
+            &config.gitlab_accesstoken,
+            config.max_upstream_body_bytes,
+        ) {
             updaters.push(Box::new(updater));
         }
-        if let Ok(updater) = GitLab::new("gitlab.freedesktop.org", &None) {
+        if let Ok(updater) = GitLab::new(
+            "gitlab.freedesktop.org",
+            &None,
+            config.max_upstream_body_bytes,
+        ) {
             updaters.push(Box::new(updater));
         }
         Self { updaters, pool }
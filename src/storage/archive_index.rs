@@ -9,6 +9,7 @@ use tracing::instrument;
 pub(crate) struct FileInfo {
     range: FileRange,
     compression: CompressionAlgorithm,
+    size: Option<u64>,
 }
 
 impl FileInfo {
@@ -18,6 +19,12 @@ impl FileInfo {
     pub(crate) fn compression(&self) -> CompressionAlgorithm {
         self.compression
     }
+    /// the uncompressed size of the file.
+    ///
+    /// `None` for indexes created before we started storing the size.
+    pub(crate) fn size(&self) -> Option<u64> {
+        self.size
+    }
 }
 
 /// create an archive index based on a zipfile.
@@ -43,7 +50,8 @@ pub(crate) fn create<R: io::Read + io::Seek, P: AsRef<Path> + std::fmt::Debug>(
                 path TEXT UNIQUE,
                 start INTEGER,
                 end INTEGER,
-                compression INTEGER
+                compression INTEGER,
+                size INTEGER
             );
             ",
         (),
@@ -56,7 +64,7 @@ pub(crate) fn create<R: io::Read + io::Seek, P: AsRef<Path> + std::fmt::Debug>(
         let zf = archive.by_index(i)?;
 
         conn.execute(
-            "INSERT INTO files (path, start, end, compression) VALUES (?, ?, ?, ?)",
+            "INSERT INTO files (path, start, end, compression, size) VALUES (?, ?, ?, ?, ?)",
             (
                 zf.name(),
                 zf.data_start(),
@@ -65,6 +73,7 @@ pub(crate) fn create<R: io::Read + io::Seek, P: AsRef<Path> + std::fmt::Debug>(
                     zip::CompressionMethod::Bzip2 => compression_bzip,
                     c => bail!("unsupported compression algorithm {} in zip-file", c),
                 },
+                zf.size(),
             ),
         )?;
     }
@@ -74,33 +83,87 @@ pub(crate) fn create<R: io::Read + io::Seek, P: AsRef<Path> + std::fmt::Debug>(
     Ok(())
 }
 
+fn compression_from_row(
+    row: &rusqlite::Row<'_>,
+    idx: usize,
+) -> rusqlite::Result<CompressionAlgorithm> {
+    let compression: i32 = row.get(idx)?;
+    compression.try_into().map_err(|value| {
+        rusqlite::Error::FromSqlConversionFailure(
+            idx,
+            rusqlite::types::Type::Integer,
+            format!("invalid compression algorithm '{}' in database", value).into(),
+        )
+    })
+}
+
+/// older archive indexes don't have the `size` column,
+/// for these we select `NULL` instead.
+fn size_column(conn: &Connection) -> Result<&'static str> {
+    let has_size: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info('files') WHERE name = 'size')",
+        (),
+        |row| row.get(0),
+    )?;
+    Ok(if has_size { "size" } else { "NULL" })
+}
+
 fn find_in_sqlite_index(conn: &Connection, search_for: &str) -> Result<Option<FileInfo>> {
-    let mut stmt = conn.prepare(
+    let mut stmt = conn.prepare(&format!(
         "
-        SELECT start, end, compression
+        SELECT start, end, compression, {}
         FROM files
         WHERE path = ?
         ",
-    )?;
+        size_column(conn)?
+    ))?;
 
     stmt.query_row((search_for,), |row| {
-        let compression: i32 = row.get(2)?;
-
         Ok(FileInfo {
             range: row.get(0)?..=row.get(1)?,
-            compression: compression.try_into().map_err(|value| {
-                rusqlite::Error::FromSqlConversionFailure(
-                    2,
-                    rusqlite::types::Type::Integer,
-                    format!("invalid compression algorithm '{}' in database", value).into(),
-                )
-            })?,
+            compression: compression_from_row(row, 2)?,
+            size: row.get(3)?,
         })
     })
     .optional()
     .context("error fetching SQLite data")
 }
 
+/// list all files in the archive index, ordered by their path.
+#[instrument]
+pub(crate) fn list_files<P: AsRef<Path> + std::fmt::Debug>(
+    archive_index_path: P,
+) -> Result<Vec<(String, FileInfo)>> {
+    let connection = Connection::open_with_flags(
+        archive_index_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    let mut stmt = connection.prepare(&format!(
+        "
+        SELECT path, start, end, compression, {}
+        FROM files
+        ORDER BY path
+        ",
+        size_column(&connection)?
+    ))?;
+
+    let files = stmt
+        .query_map((), |row| {
+            Ok((
+                row.get(0)?,
+                FileInfo {
+                    range: row.get(1)?..=row.get(2)?,
+                    compression: compression_from_row(row, 3)?,
+                    size: row.get(4)?,
+                },
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("error fetching SQLite data")?;
+
+    Ok(files)
+}
+
 #[instrument]
 pub(crate) fn find_in_file<P: AsRef<Path> + std::fmt::Debug>(
     archive_index_path: P,
@@ -149,12 +212,56 @@ mod tests {
 
         assert_eq!(fi.range, FileRange::new(39, 459));
         assert_eq!(fi.compression, CompressionAlgorithm::Bzip2);
+        assert_eq!(fi.size, Some(255));
 
         assert!(find_in_file(&tempfile, "some_other_file",)
             .unwrap()
             .is_none());
     }
 
+    #[test]
+    fn index_list_files() {
+        let mut tf = create_test_archive(3);
+
+        let tempfile = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        create(&mut tf, &tempfile).unwrap();
+
+        let files = list_files(&tempfile).unwrap();
+        assert_eq!(
+            files
+                .iter()
+                .map(|(path, _)| path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["testfile0", "testfile1", "testfile2"]
+        );
+        assert_eq!(
+            files[0].1,
+            find_in_file(&tempfile, "testfile0").unwrap().unwrap()
+        );
+    }
+
+    #[test]
+    fn index_without_size_column() {
+        let mut tf = create_test_archive(1);
+
+        let tempfile = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        create(&mut tf, &tempfile).unwrap();
+
+        // simulate an index created before we stored the uncompressed size
+        Connection::open(&tempfile)
+            .unwrap()
+            .execute("ALTER TABLE files DROP COLUMN size", ())
+            .unwrap();
+
+        let fi = find_in_file(&tempfile, "testfile0").unwrap().unwrap();
+        assert_eq!(fi.range, FileRange::new(39, 459));
+        assert_eq!(fi.size, None);
+
+        let files = list_files(&tempfile).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].1.size(), None);
+    }
+
     #[test]
     fn archive_with_more_than_65k_files() {
         let mut tf = create_test_archive(100_000);
@@ -74,9 +74,12 @@ impl DatabaseBackend {
             date_updated: DateTime<Utc>,
             compression: Option<i32>,
             content: Option<Vec<u8>>,
+            content_hash: Option<String>,
             is_too_big: bool,
         }
 
+        // the recorded hash covers the whole file, not the requested range
+        let is_range = range.is_some();
         let result = if let Some(r) = range {
             // when we only want to get a range we can validate already if the range is small enough
             if (r.end() - r.start() + 1) > max_size as u64 {
@@ -91,7 +94,7 @@ impl DatabaseBackend {
             sqlx::query_as!(
                 Result,
                 r#"SELECT
-                     path, mime, date_updated, compression,
+                     path, mime, date_updated, compression, content_hash,
                      substring(content from $2 for $3) as content,
                      FALSE as "is_too_big!"
                  FROM files
@@ -109,7 +112,7 @@ impl DatabaseBackend {
             sqlx::query_as!(
                 Result,
                 r#"SELECT
-                     path, mime, date_updated, compression,
+                     path, mime, date_updated, compression, content_hash,
                      (CASE WHEN LENGTH(content) <= $2 THEN content ELSE NULL END) AS content,
                      (LENGTH(content) > $2) AS "is_too_big!"
                  FROM files
@@ -143,6 +146,7 @@ impl DatabaseBackend {
             date_updated: result.date_updated,
             content: result.content.unwrap_or_default(),
             compression,
+            content_hash: result.content_hash.filter(|_| !is_range),
         })
     }
 
@@ -152,14 +156,16 @@ impl DatabaseBackend {
         for blob in batch {
             let compression = blob.compression.map(|alg| alg as i32);
             sqlx::query!(
-                "INSERT INTO files (path, mime, content, compression)
-                 VALUES ($1, $2, $3, $4)
+                "INSERT INTO files (path, mime, content, compression, content_hash)
+                 VALUES ($1, $2, $3, $4, $5)
                  ON CONFLICT (path) DO UPDATE
-                    SET mime = EXCLUDED.mime, content = EXCLUDED.content, compression = EXCLUDED.compression",
+                    SET mime = EXCLUDED.mime, content = EXCLUDED.content, compression = EXCLUDED.compression,
+                        content_hash = EXCLUDED.content_hash",
                 &blob.path,
                 &blob.mime.to_string(),
                 &blob.content,
                 compression,
+                super::content_hash(&blob.content),
             )
             .execute(&mut *trans).await?;
             self.metrics.uploaded_files_total.inc();
@@ -12,22 +12,26 @@ use crate::{
         mimes, BuildId, Pool,
     },
     error::Result,
-    utils::spawn_blocking,
+    utils::{spawn_blocking, spawn_blocking_with_timeout},
     Config, InstanceMetrics,
 };
 use anyhow::anyhow;
+use async_stream::try_stream;
 use chrono::{DateTime, Utc};
 use fn_error_context::context;
-use futures_util::stream::BoxStream;
+use futures_util::stream::{self, BoxStream, StreamExt as _, TryStreamExt as _};
 use mime::Mime;
 use path_slash::PathExt;
+use sha2::{Digest as _, Sha256};
 use std::iter;
 use std::{
+    collections::HashMap,
     fmt, fs,
     io::{self, BufReader},
     ops::RangeInclusive,
     path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 use tokio::{io::AsyncWriteExt, runtime::Runtime};
 use tracing::{error, info_span, instrument, trace};
@@ -35,10 +39,24 @@ use walkdir::WalkDir;
 
 type FileRange = RangeInclusive<u64>;
 
+/// how long we wait for a lookup in a local archive index before failing the request.
+/// These lookups are on the hot path of serving rustdoc pages.
+const ARCHIVE_INDEX_LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// how many existence checks `exists_batch` runs concurrently.
+const EXISTS_BATCH_CONCURRENCY: usize = 16;
+
 #[derive(Debug, thiserror::Error)]
 #[error("path not found")]
 pub(crate) struct PathNotFoundError;
 
+/// The stored content of a blob doesn't match the content hash recorded when it was written.
+#[derive(Debug, thiserror::Error)]
+#[error("content of {path} doesn't match its stored content hash")]
+pub(crate) struct IntegrityError {
+    pub(crate) path: String,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub(crate) struct Blob {
     pub(crate) path: String,
@@ -46,12 +64,36 @@ pub(crate) struct Blob {
     pub(crate) date_updated: DateTime<Utc>,
     pub(crate) content: Vec<u8>,
     pub(crate) compression: Option<CompressionAlgorithm>,
+    /// hex-encoded SHA-256 of the stored (possibly compressed) content, as recorded
+    /// when the blob was written. `None` for range reads and for blobs stored before
+    /// we recorded hashes.
+    /// This field is ignored by the backend on write, it's computed from `content`.
+    ///
+    /// The hash is recorded for every blob, but only checked by
+    /// [`AsyncStorage::get_verified`], which right now is only used to download
+    /// archive indexes. A corrupted index would otherwise be cached locally and
+    /// break every file lookup in its archive.
+    pub(crate) content_hash: Option<String>,
 }
 
 impl Blob {
     pub(crate) fn is_empty(&self) -> bool {
         self.mime == "application/x-empty"
     }
+
+    fn verify_content_hash(&self) -> Result<(), IntegrityError> {
+        match &self.content_hash {
+            Some(hash) if *hash != content_hash(&self.content) => Err(IntegrityError {
+                path: self.path.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// the content hash we record for stored blobs.
+fn content_hash(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
 }
 
 pub fn get_file_list<P: AsRef<Path>>(path: P) -> Box<dyn Iterator<Item = Result<PathBuf>>> {
@@ -149,6 +191,18 @@ impl AsyncStorage {
         }
     }
 
+    /// Check the existence of many paths, with a bounded number of checks in flight.
+    #[instrument(skip_all, fields(count = paths.len()))]
+    pub(crate) async fn exists_batch(&self, paths: &[String]) -> Result<HashMap<String, bool>> {
+        stream::iter(paths)
+            .map(|path| async move {
+                Ok::<_, anyhow::Error>((path.clone(), self.exists(path).await?))
+            })
+            .buffer_unordered(EXISTS_BATCH_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+
     #[instrument]
     pub(crate) async fn get_public_access(&self, path: &str) -> Result<bool> {
         match &self.backend {
@@ -262,9 +316,11 @@ impl AsyncStorage {
         {
             Ok(index_filename) => Ok({
                 let path = path.to_owned();
-                spawn_blocking(move || {
-                    Ok(archive_index::find_in_file(index_filename, &path)?.is_some())
-                })
+                spawn_blocking_with_timeout(
+                    "archive index lookup",
+                    ARCHIVE_INDEX_LOOKUP_TIMEOUT,
+                    move || Ok(archive_index::find_in_file(index_filename, &path)?.is_some()),
+                )
                 .await?
             }),
             Err(err) => {
@@ -279,10 +335,27 @@ impl AsyncStorage {
 
     #[instrument]
     pub(crate) async fn get(&self, path: &str, max_size: usize) -> Result<Blob> {
+        self.get_inner(path, max_size, false).await
+    }
+
+    /// Like [`Self::get`], but fails with an [`IntegrityError`] when the stored content
+    /// doesn't match the content hash recorded when it was written.
+    ///
+    /// Blobs stored before we recorded content hashes can't be verified and are
+    /// returned as-is.
+    #[instrument]
+    pub(crate) async fn get_verified(&self, path: &str, max_size: usize) -> Result<Blob> {
+        self.get_inner(path, max_size, true).await
+    }
+
+    async fn get_inner(&self, path: &str, max_size: usize, verify: bool) -> Result<Blob> {
         let mut blob = match &self.backend {
             StorageBackend::Database(db) => db.get(path, max_size, None).await,
             StorageBackend::S3(s3) => s3.get(path, max_size, None).await,
         }?;
+        if verify {
+            blob.verify_content_hash()?;
+        }
         if let Some(alg) = blob.compression {
             blob.content = decompress(blob.content.as_slice(), alg, max_size)?;
             blob.compression = None;
@@ -326,7 +399,10 @@ impl AsyncStorage {
         ));
 
         if !local_index_path.exists() {
-            let index_content = self.get(&remote_index_path, usize::MAX).await?.content;
+            let index_content = self
+                .get_verified(&remote_index_path, usize::MAX)
+                .await?
+                .content;
 
             tokio::fs::create_dir_all(
                 local_index_path
@@ -363,7 +439,12 @@ impl AsyncStorage {
 
         let info = {
             let path = path.to_owned();
-            spawn_blocking(move || archive_index::find_in_file(index_filename, &path)).await
+            spawn_blocking_with_timeout(
+                "archive index lookup",
+                ARCHIVE_INDEX_LOOKUP_TIMEOUT,
+                move || archive_index::find_in_file(index_filename, &path),
+            )
+            .await
         }?
         .ok_or(PathNotFoundError)?;
 
@@ -383,7 +464,93 @@ impl AsyncStorage {
             date_updated: blob.date_updated,
             content: blob.content,
             compression: None,
+            content_hash: None,
+        })
+    }
+
+    /// Stream an uncompressed tar of the files in a release's rustdoc archive
+    /// for which `include` returns `true`.
+    ///
+    /// The archive index is fetched before the stream is returned, so a missing
+    /// archive surfaces as a [`PathNotFoundError`] here instead of in the middle
+    /// of the stream. The same goes for files that are larger than
+    /// `max_rustdoc_tarball_size` in total, which fail with [`SizeLimitReached`].
+    /// Any later error aborts the stream, and with it the chunked response.
+    ///
+    /// Archive indexes created before we stored the uncompressed sizes only
+    /// allow checking the compressed sizes upfront, for these the limit can
+    /// still be hit while streaming.
+    ///
+    /// [`SizeLimitReached`]: crate::error::SizeLimitReached
+    #[instrument(skip(self, include))]
+    pub(crate) async fn stream_rustdoc_tarball(
+        self: Arc<Self>,
+        name: &str,
+        version: &str,
+        latest_build_id: Option<BuildId>,
+        include: impl Fn(&str) -> bool + Send + 'static,
+    ) -> Result<BoxStream<'static, Result<Vec<u8>>>> {
+        let archive_path = rustdoc_archive_path(name, version);
+        let index_filename = self
+            .download_archive_index(&archive_path, latest_build_id)
+            .await?;
+
+        let files = spawn_blocking(move || {
+            Ok(archive_index::list_files(index_filename)?
+                .into_iter()
+                .filter(|(path, _)| include(path))
+                .collect::<Vec<_>>())
         })
+        .await?;
+
+        let max_size = self.config.max_rustdoc_tarball_size;
+        let total_size: u64 = files
+            .iter()
+            .map(|(_, info)| {
+                info.size().unwrap_or_else(|| {
+                    // older indexes only know the compressed size,
+                    // which is the least the tar will contain.
+                    info.range().end() - info.range().start() + 1
+                })
+            })
+            .sum();
+        if total_size > max_size as u64 {
+            return Err(crate::error::SizeLimitReached.into());
+        }
+
+        Ok(try_stream! {
+            let mut builder = tar::Builder::new(Vec::new());
+            let mut total_size = 0;
+            for (path, info) in files {
+                // the total size is already checked, so we don't apply the
+                // per-file limit of `max_file_size_for` here.
+                let blob = self
+                    .get_range(
+                        &archive_path,
+                        max_size,
+                        info.range(),
+                        Some(info.compression()),
+                    )
+                    .await?;
+
+                total_size += blob.content.len();
+                if total_size > max_size {
+                    Err(crate::error::SizeLimitReached)?;
+                }
+
+                let mut header = tar::Header::new_gnu();
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_size(blob.content.len() as u64);
+                header.set_mode(0o644);
+                header.set_mtime(blob.date_updated.timestamp().try_into().unwrap_or(0));
+                builder.append_data(&mut header, &path, blob.content.as_slice())?;
+
+                yield std::mem::take(builder.get_mut());
+            }
+            // writes the end-of-archive marker
+            yield builder.into_inner()?;
+        }
+        .boxed())
     }
 
     #[instrument(skip(self))]
@@ -464,6 +631,7 @@ impl AsyncStorage {
                 mime: mimes::APPLICATION_ZIP.clone(),
                 content: zip_content,
                 compression: None,
+                content_hash: None,
                 date_updated: Utc::now(),
             },
             Blob {
@@ -471,6 +639,7 @@ impl AsyncStorage {
                 mime: mime::APPLICATION_OCTET_STREAM,
                 content: compressed_index_content,
                 compression: Some(alg),
+                content_hash: None,
                 date_updated: Utc::now(),
             },
         ])
@@ -521,6 +690,7 @@ impl AsyncStorage {
                         mime,
                         content,
                         compression: Some(alg),
+                        content_hash: None,
                         // this field is ignored by the backend
                         date_updated: Utc::now(),
                     });
@@ -558,6 +728,7 @@ impl AsyncStorage {
             mime,
             content,
             compression: Some(alg),
+            content_hash: None,
             // this field is ignored by the backend
             date_updated: Utc::now(),
         }])
@@ -627,6 +798,11 @@ impl Storage {
         self.runtime.block_on(self.inner.exists(path))
     }
 
+    #[cfg(test)]
+    pub(crate) fn exists_batch(&self, paths: &[String]) -> Result<HashMap<String, bool>> {
+        self.runtime.block_on(self.inner.exists_batch(paths))
+    }
+
     pub(crate) fn get_public_access(&self, path: &str) -> Result<bool> {
         self.runtime.block_on(self.inner.get_public_access(path))
     }
@@ -703,6 +879,12 @@ impl Storage {
         self.runtime.block_on(self.inner.get(path, max_size))
     }
 
+    #[cfg(test)]
+    pub(crate) fn get_verified(&self, path: &str, max_size: usize) -> Result<Blob> {
+        self.runtime
+            .block_on(self.inner.get_verified(path, max_size))
+    }
+
     pub(super) fn get_range(
         &self,
         path: &str,
@@ -778,7 +960,6 @@ impl Storage {
     /// purely for testing purposes since it collects all files into a Vec.
     #[cfg(test)]
     pub(crate) fn list_prefix(&self, prefix: &str) -> impl Iterator<Item = Result<String>> {
-        use futures_util::stream::StreamExt;
         self.runtime
             .block_on(async {
                 self.inner
@@ -850,12 +1031,73 @@ mod test {
         check_mime("hello.txt", "text/plain");
         check_mime("file.rs", "text/rust");
         check_mime("important.svg", "image/svg+xml");
+        check_mime("module.mjs", "text/javascript");
+        check_mime("module.wasm", "application/wasm");
+        check_mime("search.js.map", "application/json");
+        check_mime("font.woff2", "font/woff2");
     }
 
     fn check_mime(path: &str, expected_mime: &str) {
         let detected_mime = detect_mime(Path::new(&path));
         assert_eq!(detected_mime, expected_mime);
     }
+
+    #[test]
+    fn test_verify_content_hash() {
+        let mut blob = Blob {
+            path: "foo/bar.txt".into(),
+            mime: mime::TEXT_PLAIN,
+            date_updated: Utc::now(),
+            content: b"test content\n".to_vec(),
+            compression: None,
+            content_hash: None,
+        };
+        // blobs stored before we recorded hashes can't be verified
+        assert!(blob.verify_content_hash().is_ok());
+
+        blob.content_hash = Some(content_hash(&blob.content));
+        assert!(blob.verify_content_hash().is_ok());
+
+        blob.content_hash = Some(content_hash(b"other content"));
+        assert_eq!(blob.verify_content_hash().unwrap_err().path, "foo/bar.txt");
+    }
+
+    #[test]
+    fn test_get_verified_detects_corrupted_content() {
+        crate::test::async_wrapper(|env| async move {
+            env.override_config(|config| config.storage_backend = StorageKind::Database);
+            let storage = env.async_storage().await;
+            storage.store_one("foo/bar.txt", b"test content\n").await?;
+
+            let mut conn = env.async_db().await.async_conn().await;
+            sqlx::query!(
+                "UPDATE files SET content = $2 WHERE path = $1",
+                "foo/bar.txt",
+                &compress(&b"corrupted\n"[..], CompressionAlgorithm::default())?,
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            // unverified reads still serve whatever is stored
+            assert_eq!(
+                storage.get("foo/bar.txt", usize::MAX).await?.content,
+                b"corrupted\n"
+            );
+
+            let err = storage
+                .get_verified("foo/bar.txt", usize::MAX)
+                .await
+                .unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<IntegrityError>()
+                    .expect("expected an integrity error")
+                    .path,
+                "foo/bar.txt"
+            );
+
+            Ok(())
+        });
+    }
 }
 
 /// Backend tests are a set of tests executed on all the supported storage backends. They ensure
@@ -882,6 +1124,7 @@ mod backend_tests {
             date_updated: Utc::now(),
             content: "Hello world!".into(),
             compression: None,
+            content_hash: None,
         };
         storage.store_blobs(vec![blob])?;
         assert!(storage.exists("path/to/file.txt")?);
@@ -889,6 +1132,36 @@ mod backend_tests {
         Ok(())
     }
 
+    fn test_exists_batch(storage: &Storage) -> Result<()> {
+        let existing: Vec<String> = (0..20).map(|i| format!("batch/exists-{i}.txt")).collect();
+        let missing: Vec<String> = (0..20).map(|i| format!("batch/missing-{i}.txt")).collect();
+
+        storage.store_blobs(
+            existing
+                .iter()
+                .map(|path| Blob {
+                    path: path.clone(),
+                    mime: mime::TEXT_PLAIN,
+                    date_updated: Utc::now(),
+                    content: "Hello world!".into(),
+                    compression: None,
+                    content_hash: None,
+                })
+                .collect(),
+        )?;
+
+        let paths: Vec<String> = existing.iter().chain(missing.iter()).cloned().collect();
+        let result = storage.exists_batch(&paths)?;
+
+        assert_eq!(result.len(), paths.len());
+        assert!(existing.iter().all(|path| result[path]));
+        assert!(missing.iter().all(|path| !result[path]));
+
+        assert!(storage.exists_batch(&[])?.is_empty());
+
+        Ok(())
+    }
+
     fn test_set_public(storage: &Storage) -> Result<()> {
         let path: &str = "foo/bar.txt";
 
@@ -897,6 +1170,7 @@ mod backend_tests {
             mime: mime::TEXT_PLAIN,
             date_updated: Utc::now(),
             compression: None,
+            content_hash: None,
             content: b"test content\n".to_vec(),
         }])?;
 
@@ -924,6 +1198,7 @@ mod backend_tests {
             mime: mime::TEXT_PLAIN,
             date_updated: Utc::now(),
             compression: None,
+            content_hash: None,
             content: b"test content\n".to_vec(),
         };
 
@@ -932,6 +1207,10 @@ mod backend_tests {
         let found = storage.get(path, usize::MAX)?;
         assert_eq!(blob.mime, found.mime);
         assert_eq!(blob.content, found.content);
+        assert_eq!(found.content_hash, Some(content_hash(&blob.content)));
+
+        let verified = storage.get_verified(path, usize::MAX)?;
+        assert_eq!(blob.content, verified.content);
 
         // default visibility is private
         assert!(!storage.get_public_access(path)?);
@@ -959,6 +1238,7 @@ mod backend_tests {
             mime: mime::TEXT_PLAIN,
             date_updated: Utc::now(),
             compression: None,
+            content_hash: None,
             content: b"test content\n".to_vec(),
         };
 
@@ -976,6 +1256,13 @@ mod backend_tests {
                 .get_range("foo/bar.txt", usize::MAX, 5..=12, None)?
                 .content
         );
+        // the recorded hash only covers the whole file
+        assert_eq!(
+            storage
+                .get_range("foo/bar.txt", usize::MAX, 0..=4, None)?
+                .content_hash,
+            None
+        );
 
         for path in &["bar.txt", "baz.txt", "foo/baz.txt"] {
             assert!(storage
@@ -999,6 +1286,7 @@ mod backend_tests {
                     mime: mime::TEXT_PLAIN,
                     date_updated: Utc::now(),
                     compression: None,
+                    content_hash: None,
                     content: b"test content\n".to_vec(),
                 })
                 .collect(),
@@ -1041,6 +1329,7 @@ mod backend_tests {
             date_updated: Utc::now(),
             content: vec![0; MAX_SIZE],
             compression: None,
+            content_hash: None,
         };
         let big_blob = Blob {
             path: "big-blob.bin".into(),
@@ -1048,6 +1337,7 @@ mod backend_tests {
             date_updated: Utc::now(),
             content: vec![0; MAX_SIZE * 2],
             compression: None,
+            content_hash: None,
         };
 
         storage.store_blobs(vec![small_blob.clone(), big_blob])?;
@@ -1082,6 +1372,7 @@ mod backend_tests {
                 mime: mime::TEXT_PLAIN,
                 date_updated: Utc::now(),
                 compression: None,
+                content_hash: None,
                 content: b"Hello world!\n".to_vec(),
             })
             .collect::<Vec<_>>();
@@ -1227,6 +1518,7 @@ mod backend_tests {
                     path: format!("{i}.rs"),
                     date_updated: now,
                     compression: None,
+                    content_hash: None,
                 }
             })
             .collect();
@@ -1287,6 +1579,7 @@ mod backend_tests {
                     path: (*path).to_string(),
                     content: b"foo\n".to_vec(),
                     compression: None,
+                    content_hash: None,
                     mime: mime::TEXT_PLAIN,
                     date_updated: Utc::now(),
                 })
@@ -1366,6 +1659,7 @@ mod backend_tests {
         tests {
             test_batched_uploads,
             test_exists,
+            test_exists_batch,
             test_get_object,
             test_get_range,
             test_get_too_big,
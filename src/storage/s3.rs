@@ -21,6 +21,8 @@ use tracing::{error, warn};
 
 const PUBLIC_ACCESS_TAG: &str = "static-cloudfront-access";
 const PUBLIC_ACCESS_VALUE: &str = "allow";
+/// user metadata key for the content hash recorded on write
+const CONTENT_HASH_METADATA: &str = "content-sha256";
 
 // error codes to check for when trying to determine if an error is
 // a "NOT FOUND" error.
@@ -180,6 +182,8 @@ impl S3Backend {
         max_size: usize,
         range: Option<FileRange>,
     ) -> Result<Blob, Error> {
+        // the recorded hash covers the whole object, not the requested range
+        let is_range = range.is_some();
         let res = self
             .client
             .get_object()
@@ -211,6 +215,10 @@ impl S3Backend {
             .unwrap_or_else(Utc::now);
 
         let compression = res.content_encoding.and_then(|s| s.parse().ok());
+        let content_hash = res
+            .metadata
+            .and_then(|mut metadata| metadata.remove(CONTENT_HASH_METADATA))
+            .filter(|_| !is_range);
 
         Ok(Blob {
             path: path.into(),
@@ -223,6 +231,7 @@ impl S3Backend {
             date_updated,
             content: content.into_inner(),
             compression,
+            content_hash,
         })
     }
 
@@ -239,6 +248,7 @@ impl S3Backend {
                         .body(blob.content.clone().into())
                         .content_type(blob.mime.to_string())
                         .set_content_encoding(blob.compression.map(|alg| alg.to_string()))
+                        .metadata(CONTENT_HASH_METADATA, super::content_hash(&blob.content))
                         .send()
                         .map_ok(|_| {
                             self.metrics.uploaded_files_total.inc();
@@ -3,9 +3,10 @@ use super::TestDatabase;
 use crate::db::file::{file_list_to_json, FileEntry};
 use crate::db::types::BuildStatus;
 use crate::db::{
-    initialize_build, initialize_crate, initialize_release, update_build_status, BuildId, ReleaseId,
+    initialize_build, initialize_crate, initialize_release, update_build_status, BuildId,
+    BuildResourceUsage, ReleaseId,
 };
-use crate::docbuilder::DocCoverage;
+use crate::docbuilder::{DocCoverage, RustdocWarnings};
 use crate::error::Result;
 use crate::registry_api::{CrateData, CrateOwner, ReleaseData};
 use crate::storage::{
@@ -81,6 +82,10 @@ pub(crate) struct FakeBuild {
     rustc_version: String,
     docsrs_version: String,
     build_status: BuildStatus,
+    resource_usage: Option<BuildResourceUsage>,
+    rustdoc_warnings: Option<RustdocWarnings>,
+    cargo_args: Option<Vec<String>>,
+    notes: Vec<String>,
 }
 
 const DEFAULT_CONTENT: &[u8] =
@@ -125,7 +130,12 @@ impl<'a> FakeRelease<'a> {
             rustdoc_files: Vec::new(),
             doc_targets: Vec::new(),
             default_target: None,
-            registry_crate_data: CrateData { owners: Vec::new() },
+            registry_crate_data: CrateData {
+                owners: Vec::new(),
+                releases: Vec::new(),
+                keywords: Vec::new(),
+                categories: Vec::new(),
+            },
             registry_release_data: ReleaseData {
                 release_time: Utc::now(),
                 yanked: false,
@@ -187,6 +197,18 @@ impl<'a> FakeRelease<'a> {
         }
     }
 
+    pub(crate) fn build_result_skipped(self) -> Self {
+        assert!(
+            self.builds.is_none(),
+            "cannot use custom builds with build_result_skipped"
+        );
+        Self {
+            has_docs: false,
+            builds: Some(vec![FakeBuild::default().build_status(BuildStatus::Skipped)]),
+            ..self
+        }
+    }
+
     pub(crate) fn builds(self, builds: Vec<FakeBuild>) -> Self {
         assert!(self.builds.is_none());
         assert!(!builds.is_empty());
@@ -209,6 +231,11 @@ impl<'a> FakeRelease<'a> {
         self
     }
 
+    pub(crate) fn downloads(mut self, new: i32) -> Self {
+        self.registry_release_data.downloads = new;
+        self
+    }
+
     pub(crate) fn archive_storage(mut self, new: bool) -> Self {
         self.archive_storage = new;
         self
@@ -257,6 +284,15 @@ impl<'a> FakeRelease<'a> {
         self
     }
 
+    pub(crate) fn proc_macro(mut self, proc_macro: bool) -> Self {
+        if proc_macro {
+            for target in self.package.targets.iter_mut() {
+                target.crate_types = vec!["proc-macro".into()];
+            }
+        }
+        self
+    }
+
     pub(crate) fn keywords(mut self, keywords: Vec<String>) -> Self {
         self.package.keywords = keywords;
         self
@@ -288,6 +324,16 @@ impl<'a> FakeRelease<'a> {
         self
     }
 
+    pub(crate) fn registry_keyword(mut self, keyword: impl Into<String>) -> Self {
+        self.registry_crate_data.keywords.push(keyword.into());
+        self
+    }
+
+    pub(crate) fn registry_category(mut self, category: impl Into<String>) -> Self {
+        self.registry_crate_data.categories.push(category.into());
+        self
+    }
+
     pub(crate) fn doc_coverage(self, doc_coverage: DocCoverage) -> Self {
         Self {
             doc_coverage: Some(doc_coverage),
@@ -635,6 +681,33 @@ impl FakeBuild {
         }
     }
 
+    pub(crate) fn resource_usage(self, resource_usage: BuildResourceUsage) -> Self {
+        Self {
+            resource_usage: Some(resource_usage),
+            ..self
+        }
+    }
+
+    pub(crate) fn rustdoc_warning(mut self, lint: impl Into<String>, count: i32) -> Self {
+        self.rustdoc_warnings
+            .get_or_insert_with(RustdocWarnings::default)
+            .0
+            .insert(lint.into(), count);
+        self
+    }
+
+    pub(crate) fn cargo_args(self, cargo_args: Vec<String>) -> Self {
+        Self {
+            cargo_args: Some(cargo_args),
+            ..self
+        }
+    }
+
+    pub(crate) fn note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
     async fn create(
         &self,
         conn: &mut sqlx::PgConnection,
@@ -655,6 +728,23 @@ impl FakeBuild {
         )
         .await?;
 
+        if let Some(resource_usage) = self.resource_usage {
+            crate::db::update_build_resource_usage(&mut *conn, build_id, resource_usage).await?;
+        }
+
+        if let Some(rustdoc_warnings) = &self.rustdoc_warnings {
+            crate::db::update_build_rustdoc_warnings(&mut *conn, build_id, rustdoc_warnings)
+                .await?;
+        }
+
+        if let Some(cargo_args) = &self.cargo_args {
+            crate::db::update_build_cargo_args(&mut *conn, build_id, cargo_args).await?;
+        }
+
+        if !self.notes.is_empty() {
+            crate::db::update_build_notes(&mut *conn, build_id, &self.notes).await?;
+        }
+
         if let Some(db_build_log) = self.db_build_log.as_deref() {
             sqlx::query!(
                 "UPDATE builds SET output = $2 WHERE id = $1",
@@ -694,6 +784,10 @@ impl Default for FakeBuild {
             rustc_version: "rustc 2.0.0-nightly (000000000 1970-01-01)".into(),
             docsrs_version: "docs.rs 1.0.0 (000000000 1970-01-01)".into(),
             build_status: BuildStatus::Success,
+            resource_usage: None,
+            rustdoc_warnings: None,
+            cargo_args: None,
+            notes: Vec::new(),
         }
     }
 }
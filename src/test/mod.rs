@@ -550,8 +550,7 @@ impl TestEnvironment {
         self.index
             .get_or_init(|| {
                 Arc::new(
-                    Index::new(self.config().registry_index_path.clone())
-                        .expect("failed to initialize the index"),
+                    Index::from_config(&self.config()).expect("failed to initialize the index"),
                 )
             })
             .clone()
@@ -564,6 +563,7 @@ impl TestEnvironment {
                     RegistryApi::new(
                         self.config().registry_api_host.clone(),
                         self.config().crates_io_api_call_retries,
+                        self.config().max_upstream_body_bytes,
                     )
                     .expect("failed to initialize the registry api"),
                 )
@@ -577,6 +577,7 @@ impl TestEnvironment {
                 Arc::new(RepositoryStatsUpdater::new(
                     &self.config(),
                     self.pool().expect("failed to get the pool"),
+                    self.instance_metrics(),
                 ))
             })
             .clone()
@@ -85,6 +85,12 @@ impl Package {
         self.library_target().is_some()
     }
 
+    pub(crate) fn is_proc_macro(&self) -> bool {
+        self.targets
+            .iter()
+            .any(|target| target.crate_types.iter().any(|kind| kind == "proc-macro"))
+    }
+
     fn normalize_package_name(&self, name: &str) -> String {
         name.replace('-', "_")
     }
@@ -151,6 +157,12 @@ impl Dependency {
         self.optional = optional;
         self
     }
+
+    #[cfg(test)]
+    pub fn set_kind(mut self, kind: String) -> Self {
+        self.kind = Some(kind);
+        self
+    }
 }
 
 #[derive(Deserialize, Serialize)]
@@ -59,6 +59,28 @@ pub(super) async fn load(conn: &mut sqlx::PgConnection, config: &Config) -> Resu
     Ok(crates)
 }
 
+/// the `(name, version)` of all releases whose docs should be in a rustdoc archive in storage.
+pub(super) async fn load_archived_releases(
+    conn: &mut sqlx::PgConnection,
+) -> Result<Vec<(String, String)>> {
+    Ok(sqlx::query!(
+        r#"SELECT
+            crates.name,
+            releases.version
+         FROM crates
+         INNER JOIN releases ON releases.crate_id = crates.id
+         WHERE
+            releases.rustdoc_status = true AND
+            releases.archive_storage = true
+         ORDER BY crates.name, releases.version"#
+    )
+    .fetch_all(conn)
+    .await?
+    .into_iter()
+    .map(|row| (row.name, row.version))
+    .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
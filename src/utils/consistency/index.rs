@@ -2,32 +2,146 @@ use super::data::{Crate, Crates, Release, Releases};
 use crate::Index;
 use anyhow::Result;
 use rayon::iter::ParallelIterator;
+use std::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use tracing::warn;
 
-pub(super) fn load(index: &Index) -> Result<Crates> {
-    let mut result: Crates = index
-        .crates()?
-        .crates_parallel()
-        .map(|krate| {
-            krate.map(|krate| {
-                let mut releases: Releases = krate
-                    .versions()
-                    .iter()
-                    .map(|version| Release {
-                        version: version.version().into(),
-                        yanked: Some(version.is_yanked()),
-                    })
-                    .collect();
-                releases.sort_by(|lhs, rhs| lhs.version.cmp(&rhs.version));
-
-                Crate {
-                    name: krate.name().into(),
-                    releases,
-                }
-            })
+/// The crates we could read from the index.
+#[derive(Debug)]
+pub(super) struct IndexCrates {
+    pub(super) crates: Crates,
+    /// number of index entries that couldn't be parsed and were skipped.
+    pub(super) skipped: usize,
+}
+
+pub(super) fn load(index: &Index) -> Result<IndexCrates> {
+    Ok(collect_crates(index.crates()?.crates_parallel()))
+}
+
+/// Collects the parsed index entries, skipping and logging the ones that can't be parsed.
+///
+/// crates-index doesn't tell us which crate an unparsable entry belongs to, so when
+/// entries were skipped, any crate missing from the result might just be in one of
+/// them. Callers must not treat crates missing from the index as removed then.
+fn collect_crates<E>(
+    crates: impl ParallelIterator<Item = Result<crates_index::Crate, E>>,
+) -> IndexCrates
+where
+    E: fmt::Debug + Send,
+{
+    let skipped = AtomicUsize::new(0);
+    let mut result: Crates = crates
+        .filter_map(|krate| match krate {
+            Ok(krate) => Some(crate_from_index(&krate)),
+            Err(err) => {
+                warn!(?err, "skipping index entry that could not be parsed");
+                skipped.fetch_add(1, Ordering::Relaxed);
+                None
+            }
         })
-        .collect::<Result<_, _>>()?;
+        .collect();
 
     result.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
 
-    Ok(result)
+    IndexCrates {
+        crates: result,
+        skipped: skipped.into_inner(),
+    }
+}
+
+fn crate_from_index(krate: &crates_index::Crate) -> Crate {
+    let mut releases: Releases = krate
+        .versions()
+        .iter()
+        .map(|version| Release {
+            version: version.version().into(),
+            yanked: Some(version.is_yanked()),
+        })
+        .collect();
+    releases.sort_by(|lhs, rhs| lhs.version.cmp(&rhs.version));
+
+    Crate {
+        name: krate.name().into(),
+        releases,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rayon::iter::IntoParallelIterator;
+
+    fn index_crate(name: &str) -> crates_index::Crate {
+        let line = format!(
+            r#"{{"name":"{name}","vers":"0.1.0","deps":[],"cksum":"0000000000000000000000000000000000000000000000000000000000000000","features":{{}},"yanked":false}}"#
+        );
+        crates_index::Crate::from_slice(line.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn collect_sorts_crates() {
+        let result = collect_crates(
+            vec![Ok::<_, &str>(index_crate("b")), Ok(index_crate("a"))].into_par_iter(),
+        );
+        assert_eq!(
+            result
+                .crates
+                .iter()
+                .map(|krate| &krate.name)
+                .collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+        assert_eq!(result.skipped, 0);
+    }
+
+    #[test]
+    fn unparsable_entry_is_skipped() {
+        let result = collect_crates(
+            vec![
+                Ok(index_crate("a")),
+                Err("broken entry"),
+                Ok(index_crate("b")),
+            ]
+            .into_par_iter(),
+        );
+        assert_eq!(
+            result
+                .crates
+                .iter()
+                .map(|krate| &krate.name)
+                .collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+        assert_eq!(result.skipped, 1);
+    }
+
+    #[test]
+    fn index_entries_with_unknown_fields() {
+        let lines = concat!(
+            r#"{"name":"krate","vers":"0.1.0","deps":[],"cksum":"0000000000000000000000000000000000000000000000000000000000000000","features":{},"yanked":false}"#,
+            "\n",
+            r#"{"name":"krate","vers":"0.2.0","deps":[],"cksum":"0000000000000000000000000000000000000000000000000000000000000000","features":{},"yanked":true,"v":99,"some_future_field":{"nested":[1,2,3]}}"#,
+            "\n",
+        );
+
+        let krate = crates_index::Crate::from_slice(lines.as_bytes()).unwrap();
+        assert_eq!(
+            crate_from_index(&krate),
+            Crate {
+                name: "krate".into(),
+                releases: vec![
+                    Release {
+                        version: "0.1.0".into(),
+                        yanked: Some(false),
+                    },
+                    Release {
+                        version: "0.2.0".into(),
+                        yanked: Some(true),
+                    },
+                ],
+            }
+        );
+    }
 }
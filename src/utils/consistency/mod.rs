@@ -1,6 +1,12 @@
-use crate::{db::delete, utils::spawn_blocking, Context};
+use crate::{
+    db::delete,
+    storage::{rustdoc_archive_path, AsyncStorage},
+    utils::spawn_blocking,
+    Context,
+};
 use anyhow::{Context as _, Result};
 use itertools::Itertools;
+use std::fmt;
 use tracing::{info, warn};
 
 mod data;
@@ -10,6 +16,12 @@ mod index;
 
 const BUILD_PRIORITY: i32 = 15;
 
+/// how many example differences are listed per category in a [`ConsistencyReport`].
+const REPORT_SAMPLE_SIZE: usize = 10;
+
+/// how many rustdoc archives are checked with one `exists_batch` call.
+const STORAGE_CHECK_CHUNK_SIZE: usize = 1000;
+
 /// consistency check
 ///
 /// will compare our database with the local crates.io index and
@@ -25,23 +37,7 @@ const BUILD_PRIORITY: i32 = 15;
 /// Even when activities fail, the command can just be re-run. While the diff calculation will
 /// be repeated, we won't re-execute fixing activities.
 pub async fn run_check<C: Context>(ctx: &C, dry_run: bool) -> Result<()> {
-    let index = ctx.index()?;
-
-    info!("Loading data from database...");
-    let mut conn = ctx.async_pool().await?.get_async().await?;
-    let db_data = db::load(&mut conn, &*ctx.config()?)
-        .await
-        .context("Loading crate data from database for consistency check")?;
-
-    tracing::info!("Loading data from index...");
-    let index_data = spawn_blocking({
-        let index = index.clone();
-        move || index::load(&index)
-    })
-    .await
-    .context("Loading crate data from index for consistency check")?;
-
-    let diff = diff::calculate_diff(db_data.iter(), index_data.iter());
+    let diff = load_diff(ctx).await?;
     let result = handle_diff(ctx, diff.iter(), dry_run).await?;
 
     println!("============");
@@ -72,6 +68,179 @@ pub async fn run_check<C: Context>(ctx: &C, dry_run: bool) -> Result<()> {
     Ok(())
 }
 
+/// Compares our database with the local crates.io index and reports the drift,
+/// without queueing builds or changing anything.
+///
+/// With `check_storage`, the report also lists releases whose rustdoc archive is
+/// missing from storage. This checks every archived release, so it takes a while.
+pub async fn run_report<C: Context>(ctx: &C, check_storage: bool) -> Result<ConsistencyReport> {
+    let diff = load_diff(ctx).await?;
+    let mut report = ConsistencyReport::from_diff(diff.iter());
+
+    if check_storage {
+        info!("Checking rustdoc archives in storage...");
+        let mut conn = ctx.async_pool().await?.get_async().await?;
+        let releases = db::load_archived_releases(&mut conn)
+            .await
+            .context("Loading archived releases from database for consistency check")?;
+        report.missing_in_storage =
+            Some(missing_in_storage(&*ctx.async_storage().await?, &releases).await?);
+    }
+
+    Ok(report)
+}
+
+/// the releases from `releases` whose rustdoc archive doesn't exist in storage.
+async fn missing_in_storage(
+    storage: &AsyncStorage,
+    releases: &[(String, String)],
+) -> Result<DriftSummary> {
+    let mut summary = DriftSummary::default();
+    for chunk in releases.chunks(STORAGE_CHECK_CHUNK_SIZE) {
+        let paths: Vec<_> = chunk
+            .iter()
+            .map(|(name, version)| rustdoc_archive_path(name, version))
+            .collect();
+        let exists = storage.exists_batch(&paths).await?;
+
+        for ((name, version), path) in chunk.iter().zip(&paths) {
+            if !exists[path] {
+                summary.add(|| format!("{name} {version}"));
+            }
+        }
+    }
+    Ok(summary)
+}
+
+async fn load_diff<C: Context>(ctx: &C) -> Result<Vec<diff::Difference>> {
+    let index = ctx.index()?;
+
+    info!("Loading data from database...");
+    let mut conn = ctx.async_pool().await?.get_async().await?;
+    let db_data = db::load(&mut conn, &*ctx.config()?)
+        .await
+        .context("Loading crate data from database for consistency check")?;
+
+    tracing::info!("Loading data from index...");
+    let index_data = spawn_blocking({
+        let index = index.clone();
+        move || index::load(&index)
+    })
+    .await
+    .context("Loading crate data from index for consistency check")?;
+
+    let diff = diff::calculate_diff(db_data.iter(), index_data.crates.iter());
+    if index_data.skipped > 0 {
+        warn!(
+            skipped = index_data.skipped,
+            "skipped unparsable index entries, ignoring crates and releases missing from the index"
+        );
+        return Ok(without_missing_in_index(diff));
+    }
+    Ok(diff)
+}
+
+/// drops the differences for crates and releases that are missing from the index.
+///
+/// Used when we had to skip index entries: we don't know which crates they belong to,
+/// so a crate missing from the index might just be in a skipped entry, and we must
+/// not delete it.
+fn without_missing_in_index(diff: Vec<diff::Difference>) -> Vec<diff::Difference> {
+    diff.into_iter()
+        .filter(|difference| {
+            !matches!(
+                difference,
+                diff::Difference::CrateNotInIndex(_) | diff::Difference::ReleaseNotInIndex(_, _)
+            )
+        })
+        .collect()
+}
+
+/// A group of differences found by the consistency check.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DriftSummary {
+    /// number of differences in this group
+    pub count: usize,
+    /// the first few differences, as `name` for whole crates or `name version` for releases.
+    pub samples: Vec<String>,
+}
+
+impl DriftSummary {
+    fn add(&mut self, sample: impl FnOnce() -> String) {
+        self.count += 1;
+        if self.samples.len() < REPORT_SAMPLE_SIZE {
+            self.samples.push(sample());
+        }
+    }
+}
+
+/// Drift between our database and the crates.io index.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ConsistencyReport {
+    /// crates and releases that are in the index, but not in our database
+    pub in_index_not_in_db: DriftSummary,
+    /// crates and releases that are in our database, but not in the index
+    pub in_db_not_in_index: DriftSummary,
+    /// releases with a different yank-state in our database and the index
+    pub yank_mismatch: DriftSummary,
+    /// releases with docs in our database, but without a rustdoc archive in storage.
+    /// Only checked when requested.
+    pub missing_in_storage: Option<DriftSummary>,
+}
+
+impl ConsistencyReport {
+    fn from_diff<'a>(diff: impl Iterator<Item = &'a diff::Difference>) -> Self {
+        let mut report = Self::default();
+        for difference in diff {
+            match difference {
+                diff::Difference::CrateNotInDb(name, _) => {
+                    report.in_index_not_in_db.add(|| name.clone())
+                }
+                diff::Difference::ReleaseNotInDb(name, version) => report
+                    .in_index_not_in_db
+                    .add(|| format!("{name} {version}")),
+                diff::Difference::CrateNotInIndex(name) => {
+                    report.in_db_not_in_index.add(|| name.clone())
+                }
+                diff::Difference::ReleaseNotInIndex(name, version) => report
+                    .in_db_not_in_index
+                    .add(|| format!("{name} {version}")),
+                diff::Difference::ReleaseYank(name, version, _) => {
+                    report.yank_mismatch.add(|| format!("{name} {version}"))
+                }
+            }
+        }
+        report
+    }
+}
+
+impl fmt::Display for ConsistencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (title, summary) in [
+            ("in index, not in db", Some(&self.in_index_not_in_db)),
+            ("in db, not in index", Some(&self.in_db_not_in_index)),
+            ("yank-state mismatch", Some(&self.yank_mismatch)),
+            ("missing in storage", self.missing_in_storage.as_ref()),
+        ]
+        .into_iter()
+        .filter_map(|(title, summary)| Some((title, summary?)))
+        {
+            writeln!(f, "{title:19} => {:4}", summary.count)?;
+            for sample in &summary.samples {
+                writeln!(f, "    {sample}")?;
+            }
+            if summary.count > summary.samples.len() {
+                writeln!(
+                    f,
+                    "    ... and {} more",
+                    summary.count - summary.samples.len()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Default)]
 struct HandleResult {
     builds_queued: u32,
@@ -342,4 +511,152 @@ mod tests {
             Ok(())
         })
     }
+
+    #[test]
+    fn test_report() {
+        async_wrapper(|env| async move {
+            for (name, version, yanked) in [
+                ("krate-a", "0.1.0", false),
+                ("krate-a", "0.2.0", false),
+                ("krate-b", "0.1.0", true),
+                ("krate-c", "0.1.0", false),
+            ] {
+                env.fake_release()
+                    .await
+                    .name(name)
+                    .version(version)
+                    .yanked(yanked)
+                    .create()
+                    .await?;
+            }
+
+            let release = |version: &str, yanked: bool| data::Release {
+                version: version.into(),
+                yanked: Some(yanked),
+            };
+            let index_data = vec![
+                data::Crate {
+                    name: "krate-a".into(),
+                    releases: vec![release("0.1.0", false), release("0.3.0", false)],
+                },
+                data::Crate {
+                    name: "krate-b".into(),
+                    releases: vec![release("0.1.0", false)],
+                },
+                data::Crate {
+                    name: "krate-d".into(),
+                    releases: vec![release("1.0.0", false)],
+                },
+            ];
+
+            let mut conn = env.async_db().await.async_conn().await;
+            let db_data = db::load(&mut conn, &env.config()).await?;
+            let diff = diff::calculate_diff(db_data.iter(), index_data.iter());
+            let report = ConsistencyReport::from_diff(diff.iter());
+
+            assert_eq!(
+                report,
+                ConsistencyReport {
+                    in_index_not_in_db: DriftSummary {
+                        count: 2,
+                        samples: vec!["krate-a 0.3.0".into(), "krate-d".into()],
+                    },
+                    in_db_not_in_index: DriftSummary {
+                        count: 2,
+                        samples: vec!["krate-a 0.2.0".into(), "krate-c".into()],
+                    },
+                    yank_mismatch: DriftSummary {
+                        count: 1,
+                        samples: vec!["krate-b 0.1.0".into()],
+                    },
+                    missing_in_storage: None,
+                }
+            );
+
+            // nothing was fixed
+            assert!(env
+                .async_build_queue()
+                .await
+                .queued_crates()
+                .await?
+                .is_empty());
+            assert_eq!(count(&env, "SELECT count(*) FROM releases").await?, 4);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_report_missing_in_storage() {
+        async_wrapper(|env| async move {
+            for name in ["krate-a", "krate-b"] {
+                env.fake_release()
+                    .await
+                    .name(name)
+                    .version("0.1.0")
+                    .archive_storage(true)
+                    .create()
+                    .await?;
+            }
+            // releases without archive storage aren't checked
+            env.fake_release()
+                .await
+                .name("krate-c")
+                .version("0.1.0")
+                .archive_storage(false)
+                .create()
+                .await?;
+
+            let storage = env.async_storage().await;
+            storage
+                .delete_prefix(&rustdoc_archive_path("krate-b", "0.1.0"))
+                .await?;
+
+            let mut conn = env.async_db().await.async_conn().await;
+            let releases = db::load_archived_releases(&mut conn).await?;
+            assert_eq!(releases.len(), 2);
+
+            assert_eq!(
+                missing_in_storage(&storage, &releases).await?,
+                DriftSummary {
+                    count: 1,
+                    samples: vec!["krate-b 0.1.0".into()],
+                }
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_without_missing_in_index() {
+        let diff = vec![
+            Difference::CrateNotInIndex("krate-a".into()),
+            Difference::CrateNotInDb("krate-b".into(), vec!["0.1.0".into()]),
+            Difference::ReleaseNotInIndex("krate-c".into(), "0.1.0".into()),
+            Difference::ReleaseNotInDb("krate-c".into(), "0.2.0".into()),
+            Difference::ReleaseYank("krate-c".into(), "0.3.0".into(), true),
+        ];
+
+        assert_eq!(
+            without_missing_in_index(diff),
+            vec![
+                Difference::CrateNotInDb("krate-b".into(), vec!["0.1.0".into()]),
+                Difference::ReleaseNotInDb("krate-c".into(), "0.2.0".into()),
+                Difference::ReleaseYank("krate-c".into(), "0.3.0".into(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report_limits_samples() {
+        let diff: Vec<_> = (0..12)
+            .map(|i| Difference::ReleaseNotInDb("krate".into(), format!("0.1.{i}")))
+            .collect();
+
+        let report = ConsistencyReport::from_diff(diff.iter());
+        assert_eq!(report.in_index_not_in_db.count, 12);
+        assert_eq!(report.in_index_not_in_db.samples.len(), REPORT_SAMPLE_SIZE);
+        assert!(report.to_string().contains("... and 2 more"));
+    }
 }
@@ -3,7 +3,7 @@
 //! This daemon will start web server, track new packages and build them
 
 use crate::{
-    cdn, queue_rebuilds,
+    cdn, db, queue_rebuilds,
     utils::{queue_builder, report_error},
     web::start_web_server,
     AsyncBuildQueue, Config, Context, Index, RustwideBuilder,
@@ -91,6 +91,26 @@ pub fn start_background_repository_stats_updater<C: Context>(context: &C) -> Res
     Ok(())
 }
 
+pub fn start_background_reverse_dependency_updater<C: Context>(context: &C) -> Result<(), Error> {
+    let pool = context.pool()?;
+    let runtime = context.runtime()?;
+    async_cron(
+        &runtime,
+        "reverse dependency count updater",
+        Duration::from_secs(60 * 60),
+        move || {
+            let pool = pool.clone();
+            async move {
+                let mut conn = pool.get_async().await?;
+                let updated = db::update_reverse_dependency_counts(&mut conn).await?;
+                debug!(updated, "updated reverse dependency counts");
+                Ok(())
+            }
+        },
+    );
+    Ok(())
+}
+
 pub fn start_background_queue_rebuild<C: Context>(context: &C) -> Result<(), Error> {
     let runtime = context.runtime()?;
     let pool = context.pool()?;
@@ -211,6 +231,7 @@ pub fn start_daemon<C: Context + Send + Sync + 'static>(
         .unwrap();
 
     start_background_repository_stats_updater(&*context)?;
+    start_background_reverse_dependency_updater(&*context)?;
     start_background_cdn_invalidator(&*context)?;
     start_background_queue_rebuild(&*context)?;
 
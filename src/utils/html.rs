@@ -57,34 +57,46 @@ pub(crate) fn rewrite_lol(
         Ok(())
     };
 
-    let settings = Settings {
-        element_content_handlers: vec![
-            // Append `style.css` stylesheet after all head elements.
-            element!("head", |head: &mut Element| {
-                head.append(&head_html, ContentType::Html);
+    let mut element_content_handlers = vec![
+        // Append `style.css` stylesheet after all head elements.
+        element!("head", |head: &mut Element| {
+            head.append(&head_html, ContentType::Html);
+            Ok(())
+        }),
+        element!("body", body_handler),
+        // Append `vendored.css` before `rustdoc.css`, so that the duplicate copy of
+        // `normalize.css` will be overridden by the later version.
+        //
+        // Later rustdoc has `#mainThemeStyle` that could be used, but pre-2018 docs
+        // don't have this:
+        //
+        // https://github.com/rust-lang/rust/commit/003b2bc1c65251ec2fc80b78ed91c43fb35402ec
+        //
+        // Pre-2018 rustdoc also didn't have the resource suffix, but docs.rs was using a fork
+        // that had implemented it already then, so we can assume the css files are
+        // `<some path>/rustdoc-<some suffix>.css` and use the `-` to distinguish from the
+        // `rustdoc.static` path.
+        element!(
+            "link[rel='stylesheet'][href*='rustdoc-']",
+            |rustdoc_css: &mut Element| {
+                rustdoc_css.before(&vendored_html, ContentType::Html);
                 Ok(())
-            }),
-            element!("body", body_handler),
-            // Append `vendored.css` before `rustdoc.css`, so that the duplicate copy of
-            // `normalize.css` will be overridden by the later version.
-            //
-            // Later rustdoc has `#mainThemeStyle` that could be used, but pre-2018 docs
-            // don't have this:
-            //
-            // https://github.com/rust-lang/rust/commit/003b2bc1c65251ec2fc80b78ed91c43fb35402ec
-            //
-            // Pre-2018 rustdoc also didn't have the resource suffix, but docs.rs was using a fork
-            // that had implemented it already then, so we can assume the css files are
-            // `<some path>/rustdoc-<some suffix>.css` and use the `-` to distinguish from the
-            // `rustdoc.static` path.
-            element!(
-                "link[rel='stylesheet'][href*='rustdoc-']",
-                |rustdoc_css: &mut Element| {
-                    rustdoc_css.before(&vendored_html, ContentType::Html);
-                    Ok(())
-                }
-            ),
-        ],
+            }
+        ),
+    ];
+
+    // Preselect the theme requested in the URL, both for rustdoc and for our own topbar,
+    // so it's applied without waiting for the theme scripts.
+    if let Some(theme) = data.theme {
+        element_content_handlers.push(element!("html", move |html: &mut Element| {
+            html.set_attribute("data-theme", theme)?;
+            html.set_attribute("data-docs-rs-theme", theme)?;
+            Ok(())
+        }));
+    }
+
+    let settings = Settings {
+        element_content_handlers,
         memory_settings: MemorySettings {
             max_allowed_memory_usage,
             ..MemorySettings::default()
@@ -108,6 +120,7 @@ pub(crate) fn rewrite_lol(
 #[cfg(test)]
 mod test {
     use crate::test::{async_wrapper, AxumResponseTestExt, AxumRouterTestExt};
+    use crate::web::cache::CachePolicy;
 
     #[test]
     fn rewriting_only_injects_css_once() {
@@ -151,4 +164,43 @@ mod test {
             Ok(())
         });
     }
+
+    #[test]
+    fn theme_query_param_is_applied() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("testing")
+                .version("0.1.0")
+                .rustdoc_file_with(
+                    "testing/index.html",
+                    b"<html><head></head><body></body></html>",
+                )
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            let response = web.get("/testing/0.1.0/testing/?theme=dark").await?;
+            response.assert_cache_control(CachePolicy::NoCaching, &env.config());
+            let output = response.text().await?;
+            assert!(output.contains(r#"data-theme="dark""#));
+            assert!(output.contains(r#"data-docs-rs-theme="dark""#));
+
+            for path in [
+                "/testing/0.1.0/testing/?theme=solarized",
+                "/testing/0.1.0/testing/",
+            ] {
+                let response = web.get(path).await?;
+                response.assert_cache_control(
+                    CachePolicy::ForeverInCdnAndStaleInBrowser,
+                    &env.config(),
+                );
+                let output = response.text().await?;
+                assert!(!output.contains("data-theme="));
+                assert!(!output.contains("data-docs-rs-theme="));
+            }
+
+            Ok(())
+        });
+    }
 }
@@ -5,8 +5,8 @@ pub(crate) use self::copy::copy_dir_all;
 pub use self::daemon::{start_daemon, watch_registry};
 pub(crate) use self::html::rewrite_lol;
 pub use self::queue::{
-    get_crate_pattern_and_priority, get_crate_priority, list_crate_priorities,
-    remove_crate_priority, set_crate_priority,
+    crate_priorities_to_json, get_crate_pattern_and_priority, get_crate_priority,
+    list_crate_priorities, remove_crate_priority, set_crate_priority,
 };
 pub use self::queue_builder::queue_builder;
 pub(crate) use self::rustc_version::{get_correct_docsrs_style_file, parse_rustc_version};
@@ -47,6 +47,7 @@ pub enum ConfigName {
     RustcVersion,
     LastSeenIndexReference,
     QueueLocked,
+    QueueLockReason,
     Toolchain,
 }
 
@@ -128,6 +129,24 @@ where
     }
 }
 
+/// like [`spawn_blocking`], but gives up waiting for `f` after `timeout`.
+///
+/// The blocking thread can't be cancelled and might keep running in the
+/// background, but the caller is freed and gets an error.
+pub(crate) async fn spawn_blocking_with_timeout<F, R>(
+    name: &str,
+    timeout: Duration,
+    f: F,
+) -> Result<R>
+where
+    F: FnOnce() -> Result<R> + Send + 'static,
+    R: Send + 'static,
+{
+    tokio::time::timeout(timeout, spawn_blocking(f))
+        .await
+        .map_err(|_| anyhow::anyhow!("blocking task `{name}` timed out after {timeout:?}"))?
+}
+
 pub(crate) fn retry<T>(mut f: impl FnMut() -> Result<T>, max_attempts: u32) -> Result<T> {
     for attempt in 1.. {
         match f() {
@@ -149,7 +168,13 @@ pub(crate) fn retry<T>(mut f: impl FnMut() -> Result<T>, max_attempts: u32) -> R
     unreachable!()
 }
 
-pub(crate) async fn retry_async<T, Fut, F: FnMut() -> Fut>(mut f: F, max_attempts: u32) -> Result<T>
+/// Retry `f` with exponential backoff, but only for errors where `is_retryable`
+/// returns `true`. Other errors are returned immediately.
+pub(crate) async fn retry_async<T, Fut, F: FnMut() -> Fut>(
+    mut f: F,
+    max_attempts: u32,
+    is_retryable: impl Fn(&anyhow::Error) -> bool,
+) -> Result<T>
 where
     Fut: Future<Output = Result<T>>,
 {
@@ -157,7 +182,7 @@ where
         match f().await {
             Ok(result) => return Ok(result),
             Err(err) => {
-                if attempt > max_attempts {
+                if attempt > max_attempts || !is_retryable(&err) {
                     return Err(err);
                 } else {
                     let sleep_for = 2u32.pow(attempt);
@@ -182,12 +207,31 @@ mod tests {
 
     #[test_case(ConfigName::RustcVersion, "rustc_version")]
     #[test_case(ConfigName::QueueLocked, "queue_locked")]
+    #[test_case(ConfigName::QueueLockReason, "queue_lock_reason")]
     #[test_case(ConfigName::LastSeenIndexReference, "last_seen_index_reference")]
     fn test_configname_variants(variant: ConfigName, expected: &'static str) {
         let name: &'static str = variant.into();
         assert_eq!(name, expected);
     }
 
+    #[tokio::test]
+    async fn spawn_blocking_with_timeout_completes() -> Result<()> {
+        let result = spawn_blocking_with_timeout("fast", Duration::from_secs(5), || Ok(42)).await?;
+        assert_eq!(result, 42);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn spawn_blocking_with_timeout_times_out() {
+        let err = spawn_blocking_with_timeout("slow", Duration::from_millis(10), || {
+            thread::sleep(Duration::from_millis(500));
+            Ok(())
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "blocking task `slow` timed out after 10ms");
+    }
+
     #[test]
     fn test_get_config_empty() {
         async_wrapper(|env| async move {
@@ -1,6 +1,7 @@
 //! Utilities for interacting with the build queue
 use crate::error::Result;
 use futures_util::stream::TryStreamExt;
+use serde_json::{json, Value};
 
 const DEFAULT_PRIORITY: i32 = 0;
 
@@ -15,6 +16,14 @@ pub async fn list_crate_priorities(conn: &mut sqlx::PgConnection) -> Result<Vec<
     )
 }
 
+/// Render `(pattern, priority)` pairs as a JSON array of `{"pattern", "priority"}` objects
+pub fn crate_priorities_to_json(priorities: &[(String, i32)]) -> Value {
+    priorities
+        .iter()
+        .map(|(pattern, priority)| json!({ "pattern": pattern, "priority": priority }))
+        .collect()
+}
+
 /// Get the build queue priority for a crate with its matching pattern
 pub async fn get_crate_pattern_and_priority(
     conn: &mut sqlx::PgConnection,
@@ -167,6 +176,20 @@ mod tests {
         })
     }
 
+    #[test]
+    fn priorities_as_json() {
+        let priorities = vec![("docsrs-%".to_string(), -100), ("_c_".to_string(), 5)];
+
+        assert_eq!(
+            crate_priorities_to_json(&priorities),
+            json!([
+                { "pattern": "docsrs-%", "priority": -100 },
+                { "pattern": "_c_", "priority": 5 },
+            ])
+        );
+        assert_eq!(crate_priorities_to_json(&[]), json!([]));
+    }
+
     #[test]
     fn get_default_priority() {
         async_wrapper(|env| async move {
@@ -1,3 +1,5 @@
+use crate::error::{Result, SizeLimitReached};
+use serde::de::DeserializeOwned;
 use std::io::{Error as IoError, ErrorKind, Write};
 
 pub(crate) struct SizedBuffer {
@@ -43,6 +45,24 @@ impl Write for SizedBuffer {
     }
 }
 
+/// Deserialize the JSON body of an upstream response, failing with [`SizeLimitReached`]
+/// instead of buffering more than `limit` bytes.
+pub(crate) async fn read_json_limited<T: DeserializeOwned>(
+    mut response: reqwest::Response,
+    limit: usize,
+) -> Result<T> {
+    let mut buffer = SizedBuffer::new(limit);
+    if let Some(length) = response.content_length() {
+        buffer.reserve(length as usize);
+    }
+
+    while let Some(chunk) = response.chunk().await? {
+        buffer.write_all(&chunk).map_err(|_| SizeLimitReached)?;
+    }
+
+    Ok(serde_json::from_slice(&buffer.into_inner())?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
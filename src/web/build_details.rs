@@ -18,7 +18,8 @@ use futures_util::TryStreamExt;
 use rinja::Template;
 use semver::Version;
 use serde::Deserialize;
-use std::sync::Arc;
+use serde_json::Value;
+use std::{sync::Arc, time::Duration};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct BuildDetails {
@@ -27,8 +28,15 @@ pub(crate) struct BuildDetails {
     docsrs_version: Option<String>,
     build_status: BuildStatus,
     build_time: Option<DateTime<Utc>>,
+    build_duration: Option<Duration>,
+    /// peak memory usage in bytes
+    peak_memory: Option<i64>,
+    /// rustdoc warnings by description, most frequent first
+    rustdoc_warnings: Vec<(String, i64)>,
     output: String,
     errors: Option<String>,
+    /// informational notes about the build, like requested targets we didn't build
+    notes: Vec<String>,
 }
 
 #[derive(Template)]
@@ -77,8 +85,12 @@ pub(crate) async fn build_details_handler(
              builds.docsrs_version,
              builds.build_status as "build_status: BuildStatus",
              COALESCE(builds.build_finished, builds.build_started) as build_time,
+             builds.build_duration_ms,
+             builds.peak_memory,
+             builds.rustdoc_warnings,
              builds.output,
              builds.errors,
+             COALESCE(builds.notes, '{}') as "notes!",
              releases.default_target
          FROM builds
          INNER JOIN releases ON releases.id = builds.rid
@@ -150,8 +162,17 @@ pub(crate) async fn build_details_handler(
             docsrs_version: row.docsrs_version,
             build_status: row.build_status,
             build_time: row.build_time,
+            build_duration: row
+                .build_duration_ms
+                .map(|ms| Duration::from_millis(ms as u64)),
+            peak_memory: row.peak_memory,
+            rustdoc_warnings: row
+                .rustdoc_warnings
+                .map(summarize_rustdoc_warnings)
+                .unwrap_or_default(),
             output,
             errors: row.errors,
+            notes: row.notes,
         },
         all_log_filenames,
         current_filename,
@@ -160,13 +181,38 @@ pub(crate) async fn build_details_handler(
     .into_response())
 }
 
+/// Turns the stored warning counts by lint name into readable descriptions,
+/// like "broken intra-doc links".
+fn summarize_rustdoc_warnings(warnings: Value) -> Vec<(String, i64)> {
+    let mut summary: Vec<_> = warnings
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(lint, count)| {
+            let lint = lint.strip_prefix("rustdoc::").unwrap_or(lint);
+            let description = match lint {
+                "broken_intra_doc_links" => "broken intra-doc links".into(),
+                "private_intra_doc_links" => "private intra-doc links".into(),
+                "invalid_html_tags" => "invalid HTML tags".into(),
+                "bare_urls" => "bare URLs".into(),
+                lint => lint.replace('_', " "),
+            };
+            Some((description, count.as_i64()?))
+        })
+        .collect();
+    summary.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    summary
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::db::BuildResourceUsage;
     use crate::test::{
         async_wrapper, fake_release_that_failed_before_build, AxumResponseTestExt,
         AxumRouterTestExt, FakeBuild,
     };
     use kuchikiki::traits::TendrilSink;
+    use std::time::Duration;
     use test_case::test_case;
 
     fn get_all_log_links(page: &kuchikiki::NodeRef) -> Vec<(String, String)> {
@@ -213,6 +259,50 @@ mod tests {
         });
     }
 
+    #[test]
+    fn notes_of_successful_build() {
+        async_wrapper(|env| async move {
+            let release_id = env
+                .fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .builds(vec![
+                    FakeBuild::default().note("crate requested 12 targets, limited to 10")
+                ])
+                .create()
+                .await?;
+
+            let mut conn = env.async_db().await.async_conn().await;
+            let build = sqlx::query!("SELECT id, errors FROM builds WHERE rid = $1", release_id.0)
+                .fetch_one(&mut *conn)
+                .await?;
+            assert!(build.errors.is_none());
+
+            let page = kuchikiki::parse_html().one(
+                env.web_app()
+                    .await
+                    .get(&format!("/crate/foo/0.1.0/builds/{}", build.id))
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?,
+            );
+
+            let info_text = page.select("pre").unwrap().next().unwrap().text_contents();
+
+            assert!(!info_text.contains("# pre-build errors"), "{}", info_text);
+            assert!(info_text.contains("# notes"), "{}", info_text);
+            assert!(
+                info_text.contains("crate requested 12 targets, limited to 10"),
+                "{}",
+                info_text
+            );
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn test_partial_build_result_plus_default_target_from_previous_build() {
         async_wrapper(|env| async move {
@@ -291,6 +381,95 @@ mod tests {
         });
     }
 
+    #[test]
+    fn build_resource_usage() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .builds(vec![FakeBuild::default().resource_usage(
+                    BuildResourceUsage {
+                        duration: Duration::from_secs(90),
+                        peak_memory: Some(512 * 1024 * 1024),
+                    },
+                )])
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+
+            let page = kuchikiki::parse_html().one(
+                web.get("/crate/foo/0.1.0/builds")
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?,
+            );
+            let node = page.select("ul > li a.release").unwrap().next().unwrap();
+            let url = {
+                let attrs = node.attributes.borrow();
+                attrs.get("href").unwrap().to_owned()
+            };
+
+            let page = kuchikiki::parse_html().one(web.get(&url).await?.text().await?);
+            let info_text = page.select("pre").unwrap().next().unwrap().text_contents();
+
+            assert!(info_text.contains("# resource usage"), "{}", info_text);
+            assert!(
+                info_text.contains("wall time: 1.5 minutes"),
+                "{}",
+                info_text
+            );
+            assert!(info_text.contains("peak memory: 512 MiB"), "{}", info_text);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn build_rustdoc_warnings() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .builds(vec![FakeBuild::default()
+                    .rustdoc_warning("rustdoc::broken_intra_doc_links", 12)
+                    .rustdoc_warning("missing_docs", 3)])
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+
+            let page = kuchikiki::parse_html().one(
+                web.get("/crate/foo/0.1.0/builds")
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?,
+            );
+            let node = page.select("ul > li a.release").unwrap().next().unwrap();
+            let url = {
+                let attrs = node.attributes.borrow();
+                attrs.get("href").unwrap().to_owned()
+            };
+
+            let page = kuchikiki::parse_html().one(web.get(&url).await?.text().await?);
+            let info_text = page.select("pre").unwrap().next().unwrap().text_contents();
+
+            assert!(info_text.contains("# rustdoc warnings"), "{}", info_text);
+            assert!(
+                info_text.contains("12 broken intra-doc links"),
+                "{}",
+                info_text
+            );
+            assert!(info_text.contains("3 missing docs"), "{}", info_text);
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn s3_build_logs() {
         async_wrapper(|env| async move {
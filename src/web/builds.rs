@@ -14,12 +14,10 @@ use crate::{
         page::templates::{RenderRegular, RenderSolid},
         MetaData, ReqVersion,
     },
-    AsyncBuildQueue, Config,
+    AsyncBuildQueue, Config, InstanceMetrics,
 };
 use anyhow::{anyhow, Result};
-use axum::{
-    extract::Extension, http::header::ACCESS_CONTROL_ALLOW_ORIGIN, response::IntoResponse, Json,
-};
+use axum::{extract::Extension, response::IntoResponse, Json};
 use axum_extra::{
     headers::{authorization::Bearer, Authorization},
     TypedHeader,
@@ -29,7 +27,8 @@ use constant_time_eq::constant_time_eq;
 use http::StatusCode;
 use rinja::Template;
 use semver::Version;
-use std::sync::Arc;
+use sqlx::Connection as _;
+use std::{sync::Arc, time::Duration};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct Build {
@@ -39,6 +38,8 @@ pub(crate) struct Build {
     build_status: BuildStatus,
     build_time: Option<DateTime<Utc>>,
     errors: Option<String>,
+    /// informational notes about the build, like requested targets we didn't build
+    notes: Vec<String>,
 }
 
 #[derive(Template)]
@@ -63,9 +64,10 @@ impl BuildsPage {
 pub(crate) async fn build_list_handler(
     Path((name, req_version)): Path<(String, ReqVersion)>,
     mut conn: DbConnection,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
     Extension(config): Extension<Arc<Config>>,
 ) -> AxumResult<impl IntoResponse> {
-    let version = match_version(&mut conn, &name, &req_version)
+    let version = match_version(&mut conn, &metrics, &name, &req_version)
         .await?
         .assume_exact_name()?
         .into_canonical_req_version_or_else(|version| {
@@ -80,7 +82,10 @@ pub(crate) async fn build_list_handler(
         metadata: MetaData::from_crate(&mut conn, &name, &version, Some(req_version)).await?,
         builds: get_builds(&mut conn, &name, &version).await?,
         limits: Limits::for_crate(&config, &mut conn, &name).await?,
-        canonical_url: CanonicalUrl::from_path(format!("/crate/{name}/latest/builds")),
+        canonical_url: CanonicalUrl::from_path(
+            &config.external_base_url,
+            format!("/crate/{name}/latest/builds"),
+        ),
         csp_nonce: String::new(),
     }
     .into_response())
@@ -89,8 +94,9 @@ pub(crate) async fn build_list_handler(
 pub(crate) async fn build_list_json_handler(
     Path((name, req_version)): Path<(String, ReqVersion)>,
     mut conn: DbConnection,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
 ) -> AxumResult<impl IntoResponse> {
-    let version = match_version(&mut conn, &name, &req_version)
+    let version = match_version(&mut conn, &metrics, &name, &req_version)
         .await?
         .assume_exact_name()?
         .into_canonical_req_version_or_else(|version| {
@@ -103,7 +109,6 @@ pub(crate) async fn build_list_json_handler(
 
     Ok((
         Extension(CachePolicy::NoStoreMustRevalidate),
-        [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
         Json(
             get_builds(&mut conn, &name, &version)
                 .await?
@@ -176,6 +181,54 @@ async fn build_trigger_check(
     Ok(())
 }
 
+/// Triggered rebuilds are limited per crate, independent of the version.
+///
+/// The rebuild is recorded with the same conditional update that checks the limit,
+/// so concurrent requests for a crate can't both pass.
+async fn build_trigger_rate_limit(
+    conn: &mut sqlx::PgConnection,
+    name: &str,
+    interval: Duration,
+) -> AxumResult<()> {
+    let triggered = sqlx::query_scalar!(
+        "UPDATE crates
+         SET rebuild_triggered_at = NOW()
+         WHERE
+            name = $1 AND
+            (
+                rebuild_triggered_at IS NULL OR
+                rebuild_triggered_at < NOW() - make_interval(secs => $2)
+            )
+         RETURNING id",
+        name,
+        interval.as_secs_f64(),
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if triggered.is_some() {
+        return Ok(());
+    }
+
+    let last_triggered = sqlx::query_scalar!(
+        "SELECT rebuild_triggered_at FROM crates WHERE name = $1",
+        name
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .flatten();
+    let elapsed = last_triggered
+        .and_then(|last_triggered| (Utc::now() - last_triggered).to_std().ok())
+        .unwrap_or_default();
+
+    Err(AxumNope::TooManyRequests(anyhow!(
+        "a rebuild of crate {name} was triggered recently, try again in {}",
+        humantime::format_duration(Duration::from_secs(
+            interval.saturating_sub(elapsed).as_secs()
+        ))
+    )))
+}
+
 // Priority according to issue #2442; positive here as it's inverted.
 // FUTURE: move to a crate-global enum with all special priorities?
 const TRIGGERED_REBUILD_PRIORITY: i32 = 5;
@@ -209,6 +262,17 @@ pub(crate) async fn build_trigger_rebuild_handler(
         .await
         .map_err(JsonAxumNope)?;
 
+    // the rate limit is recorded in this transaction, so it's rolled back
+    // when we fail to queue the rebuild.
+    let mut transaction = conn
+        .begin()
+        .await
+        .map_err(|e| JsonAxumNope(AxumNope::InternalError(e.into())))?;
+
+    build_trigger_rate_limit(&mut transaction, &name, config.rebuild_trigger_interval)
+        .await
+        .map_err(JsonAxumNope)?;
+
     build_queue
         .add_crate(
             &name,
@@ -219,6 +283,11 @@ pub(crate) async fn build_trigger_rebuild_handler(
         .await
         .map_err(|e| JsonAxumNope(e.into()))?;
 
+    transaction
+        .commit()
+        .await
+        .map_err(|e| JsonAxumNope(AxumNope::InternalError(e.into())))?;
+
     Ok((StatusCode::CREATED, Json(serde_json::json!({}))))
 }
 
@@ -235,7 +304,8 @@ async fn get_builds(
             builds.docsrs_version,
             builds.build_status as "build_status: BuildStatus",
             COALESCE(builds.build_finished, builds.build_started) as build_time,
-            builds.errors
+            builds.errors,
+            COALESCE(builds.notes, '{}') as "notes!"
          FROM builds
          INNER JOIN releases ON releases.id = builds.rid
          INNER JOIN crates ON releases.crate_id = crates.id
@@ -343,6 +413,33 @@ mod tests {
         });
     }
 
+    #[test]
+    fn build_list_shows_notes() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .builds(vec![
+                    FakeBuild::default().note("crate requested 12 targets, limited to 10")
+                ])
+                .create()
+                .await?;
+
+            let response = env.web_app().await.get("/crate/foo/0.1.0/builds").await?;
+            let page = kuchikiki::parse_html().one(response.text().await?);
+
+            let notes: Vec<_> = page
+                .select("ul > li a.release .build-notes li")
+                .unwrap()
+                .map(|note| note.text_contents())
+                .collect();
+            assert_eq!(notes, ["crate requested 12 targets, limited to 10"]);
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn build_list_json() {
         async_wrapper(|env| async move {
@@ -470,7 +567,8 @@ mod tests {
                     json,
                     serde_json::json!({
                         "title": "Unauthorized",
-                        "message": "Endpoint is not configured"
+                        "message": "Endpoint is not configured",
+                        "status": 401
                     })
                 );
             }
@@ -504,7 +602,8 @@ mod tests {
                     json,
                     serde_json::json!({
                         "title": "Unauthorized",
-                        "message": "Missing authentication token"
+                        "message": "Missing authentication token",
+                        "status": 401
                     })
                 );
             }
@@ -527,7 +626,8 @@ mod tests {
                     json,
                     serde_json::json!({
                         "title": "Unauthorized",
-                        "message": "The token used for authentication is not valid"
+                        "message": "The token used for authentication is not valid",
+                        "status": 401
                     })
                 );
             }
@@ -575,7 +675,8 @@ mod tests {
                     json,
                     serde_json::json!({
                         "title": "Bad request",
-                        "message": "crate foo 0.1.0 already queued for rebuild"
+                        "message": "crate foo 0.1.0 already queued for rebuild",
+                        "status": 400
                     })
                 );
             }
@@ -587,6 +688,48 @@ mod tests {
         });
     }
 
+    #[test]
+    fn build_trigger_rebuild_rate_limited_per_crate() {
+        async_wrapper(|env| async move {
+            let correct_token = "foo137";
+            env.override_config(|config| config.cratesio_token = Some(correct_token.into()));
+
+            for version in ["0.1.0", "0.2.0"] {
+                env.fake_release()
+                    .await
+                    .name("foo")
+                    .version(version)
+                    .create()
+                    .await?;
+            }
+
+            let trigger = |version: &'static str| {
+                Request::builder()
+                    .uri(format!("/crate/foo/{version}/rebuild"))
+                    .method("POST")
+                    .header("Authorization", &format!("Bearer {}", correct_token))
+                    .body(Body::empty())
+                    .unwrap()
+            };
+
+            let response = env.web_app().await.oneshot(trigger("0.1.0")).await?;
+            assert_eq!(response.status(), StatusCode::CREATED);
+
+            // another version of the same crate is not queued, but was triggered too recently
+            let response = env.web_app().await.oneshot(trigger("0.2.0")).await?;
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+            let json: serde_json::Value = response.json().await?;
+            assert_eq!(json["title"], "Too many requests");
+            assert_eq!(json["status"], 429);
+
+            let build_queue = env.async_build_queue().await;
+            assert_eq!(build_queue.pending_count().await?, 1);
+            assert!(!build_queue.has_build_queued("foo", "0.2.0").await?);
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn build_empty_list() {
         async_wrapper(|env| async move {
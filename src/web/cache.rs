@@ -1,12 +1,25 @@
-use crate::config::Config;
+use super::headers::compute_etag;
+use crate::{config::Config, InstanceMetrics};
 use axum::{
-    extract::Request as AxumHttpRequest, middleware::Next, response::Response as AxumResponse,
+    body::{to_bytes, Body, HttpBody as _},
+    extract::Request as AxumHttpRequest,
+    middleware::Next,
+    response::Response as AxumResponse,
+};
+use axum_extra::headers::{HeaderMapExt, IfNoneMatch};
+use http::{
+    header::{CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE},
+    HeaderValue, StatusCode,
 };
-use http::{header::CACHE_CONTROL, HeaderValue};
 use std::sync::Arc;
+use strum::IntoEnumIterator as _;
+use tracing::error;
 
 pub static NO_CACHING: HeaderValue = HeaderValue::from_static("max-age=0");
 pub static SHORT: HeaderValue = HeaderValue::from_static("max-age=60");
+/// `s-maxage` overrides `max-age` for shared caches like our CDN.
+pub static SHORT_IN_CDN_LONG_IN_BROWSER: HeaderValue =
+    HeaderValue::from_static("max-age=3600, s-maxage=60");
 
 pub static NO_STORE_MUST_REVALIDATE: HeaderValue =
     HeaderValue::from_static("no-cache, no-store, must-revalidate, max-age=0");
@@ -14,7 +27,7 @@ pub static NO_STORE_MUST_REVALIDATE: HeaderValue =
 pub static FOREVER_IN_CDN_AND_BROWSER: HeaderValue = HeaderValue::from_static("max-age=31104000");
 
 /// defines the wanted caching behaviour for a web response.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, strum::EnumIter)]
 pub enum CachePolicy {
     /// no browser or CDN caching.
     /// In some cases the browser might still use cached content,
@@ -30,6 +43,12 @@ pub enum CachePolicy {
     /// Can be used when the content can be a _little_ outdated,
     /// while protecting against spikes in traffic.
     ShortInCdnAndBrowser,
+    /// cache for a short time in the CDN, but longer in the browser.
+    /// right now: one minute in the CDN (via `s-maxage`), one hour
+    /// in the browser.
+    /// Can be used for redirects whose target changes without a build, for
+    /// example when a release is yanked, so the CDN picks up the change quickly.
+    ShortInCdnLongInBrowser,
     /// cache forever in browser & CDN.
     /// Valid when you have hashed / versioned filenames and every rebuild would
     /// change the filename.
@@ -48,11 +67,39 @@ pub enum CachePolicy {
 }
 
 impl CachePolicy {
+    /// the `Cache-Control` header for this policy.
+    ///
+    /// Includes the `stale-while-revalidate` and `stale-if-error` directives
+    /// configured for this policy, for the CDN.
     pub fn render(&self, config: &Config) -> Option<HeaderValue> {
+        let mut directives: Vec<String> = self
+            .render_base(config)
+            .map(|value| value.to_str().unwrap().to_owned())
+            .into_iter()
+            .collect();
+
+        if let Some(seconds) = config.cdn_stale_while_revalidate.get(self.label()) {
+            // `ForeverInCdnAndStaleInBrowser` might already have its own.
+            if !directives
+                .iter()
+                .any(|directive| directive.contains("stale-while-revalidate"))
+            {
+                directives.push(format!("stale-while-revalidate={seconds}"));
+            }
+        }
+        if let Some(seconds) = config.cdn_stale_if_error.get(self.label()) {
+            directives.push(format!("stale-if-error={seconds}"));
+        }
+
+        (!directives.is_empty()).then(|| directives.join(", ").parse().unwrap())
+    }
+
+    fn render_base(&self, config: &Config) -> Option<HeaderValue> {
         match *self {
             CachePolicy::NoCaching => Some(NO_CACHING.clone()),
             CachePolicy::NoStoreMustRevalidate => Some(NO_STORE_MUST_REVALIDATE.clone()),
             CachePolicy::ShortInCdnAndBrowser => Some(SHORT.clone()),
+            CachePolicy::ShortInCdnLongInBrowser => Some(SHORT_IN_CDN_LONG_IN_BROWSER.clone()),
             CachePolicy::ForeverInCdnAndBrowser => Some(FOREVER_IN_CDN_AND_BROWSER.clone()),
             CachePolicy::ForeverInCdn => {
                 if config.cache_invalidatable_responses {
@@ -77,6 +124,24 @@ impl CachePolicy {
             }
         }
     }
+
+    /// name used as the `policy` label in the cache policy metric.
+    pub(crate) fn label(&self) -> &'static str {
+        match *self {
+            CachePolicy::NoCaching => "no_caching",
+            CachePolicy::NoStoreMustRevalidate => "no_store_must_revalidate",
+            CachePolicy::ShortInCdnAndBrowser => "short_in_cdn_and_browser",
+            CachePolicy::ShortInCdnLongInBrowser => "short_in_cdn_long_in_browser",
+            CachePolicy::ForeverInCdnAndBrowser => "forever_in_cdn_and_browser",
+            CachePolicy::ForeverInCdn => "forever_in_cdn",
+            CachePolicy::ForeverInCdnAndStaleInBrowser => "forever_in_cdn_and_stale_in_browser",
+        }
+    }
+
+    /// the policy with the given [`label`](Self::label), used to validate configuration.
+    pub(crate) fn from_label(label: &str) -> Option<Self> {
+        Self::iter().find(|policy| policy.label() == label)
+    }
 }
 
 pub(crate) async fn cache_middleware(req: AxumHttpRequest, next: Next) -> AxumResponse {
@@ -85,6 +150,11 @@ pub(crate) async fn cache_middleware(req: AxumHttpRequest, next: Next) -> AxumRe
         .get::<Arc<Config>>()
         .cloned()
         .expect("missing config extension in request");
+    let metrics = req
+        .extensions()
+        .get::<Arc<InstanceMetrics>>()
+        .cloned()
+        .expect("missing metrics extension in request");
 
     let mut response = next.run(req).await;
 
@@ -93,6 +163,11 @@ pub(crate) async fn cache_middleware(req: AxumHttpRequest, next: Next) -> AxumRe
         .get::<CachePolicy>()
         .unwrap_or(&CachePolicy::NoCaching);
 
+    metrics
+        .cache_policy
+        .with_label_values(&[cache.label()])
+        .inc();
+
     if cfg!(test) {
         assert!(
             !response.headers().contains_key(CACHE_CONTROL),
@@ -108,17 +183,91 @@ pub(crate) async fn cache_middleware(req: AxumHttpRequest, next: Next) -> AxumRe
     response
 }
 
+/// the largest JSON response we buffer to compute an `ETag` for.
+const MAX_JSON_ETAG_SIZE: u64 = 1024 * 1024;
+
+/// attach a content-based `ETag` to successful JSON responses, and answer
+/// matching conditional requests with `304 Not Modified`.
+///
+/// `If-None-Match` can hold a list of tags or `*`, over one or more header lines,
+/// any of them matching (weakly) is enough.
+///
+/// We have to buffer the body to hash it, so streamed bodies or bodies larger than
+/// [`MAX_JSON_ETAG_SIZE`] are passed through without an `ETag`.
+pub(crate) async fn json_etag_middleware(req: AxumHttpRequest, next: Next) -> AxumResponse {
+    let if_none_match = req.headers().typed_get::<IfNoneMatch>();
+
+    let response = next.run(req).await;
+
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with(mime::APPLICATION_JSON.as_ref()));
+    let is_small = response
+        .body()
+        .size_hint()
+        .exact()
+        .is_some_and(|size| size <= MAX_JSON_ETAG_SIZE);
+    if response.status() != StatusCode::OK || !is_json || !is_small {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let content = match to_bytes(body, MAX_JSON_ETAG_SIZE as usize).await {
+        Ok(content) => content,
+        Err(err) => {
+            error!(?err, "could not buffer JSON response body for etag");
+            parts.status = StatusCode::INTERNAL_SERVER_ERROR;
+            return AxumResponse::from_parts(parts, Body::empty());
+        }
+    };
+
+    let etag = compute_etag(&content);
+    parts.headers.typed_insert(etag.clone());
+
+    if if_none_match.is_some_and(|condition| !condition.precondition_passes(&etag)) {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(CONTENT_LENGTH);
+        return AxumResponse::from_parts(parts, Body::empty());
+    }
+
+    AxumResponse::from_parts(parts, Body::from(content))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test::wrapper;
+    use crate::test::{async_wrapper, wrapper, AxumResponseTestExt, AxumRouterTestExt};
+    use axum::{
+        http::{header::ETAG, header::IF_NONE_MATCH, Request},
+        routing::get,
+    };
+    use std::collections::HashMap;
     use test_case::test_case;
+    use tower::ServiceExt;
+
+    #[test]
+    fn cache_policy_from_label() {
+        for policy in CachePolicy::iter() {
+            assert_eq!(
+                CachePolicy::from_label(policy.label()).map(|found| found.label()),
+                Some(policy.label())
+            );
+        }
+        assert!(CachePolicy::from_label("forever").is_none());
+        assert!(CachePolicy::from_label("ForeverInCdn").is_none());
+    }
 
     #[test_case(CachePolicy::NoCaching, Some("max-age=0"))]
     #[test_case(
         CachePolicy::NoStoreMustRevalidate,
         Some("no-cache, no-store, must-revalidate, max-age=0")
     )]
+    #[test_case(
+        CachePolicy::ShortInCdnLongInBrowser,
+        Some("max-age=3600, s-maxage=60")
+    )]
     #[test_case(CachePolicy::ForeverInCdnAndBrowser, Some("max-age=31104000"))]
     #[test_case(CachePolicy::ForeverInCdn, None)]
     #[test_case(
@@ -135,6 +284,61 @@ mod tests {
         });
     }
 
+    #[test_case(
+        CachePolicy::ForeverInCdn,
+        Some("stale-while-revalidate=600, stale-if-error=86400")
+    )]
+    #[test_case(
+        CachePolicy::ShortInCdnLongInBrowser,
+        Some("max-age=3600, s-maxage=60, stale-if-error=3600")
+    )]
+    #[test_case(
+        CachePolicy::ForeverInCdnAndStaleInBrowser,
+        Some("stale-while-revalidate=86400")
+    )]
+    #[test_case(CachePolicy::ShortInCdnAndBrowser, Some("max-age=60"))]
+    #[test_case(CachePolicy::NoCaching, Some("max-age=0"))]
+    fn render_stale_directives(cache: CachePolicy, expected: Option<&str>) {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.cdn_stale_while_revalidate = HashMap::from([
+                    ("forever_in_cdn".into(), 600),
+                    ("forever_in_cdn_and_stale_in_browser".into(), 600),
+                ]);
+                config.cdn_stale_if_error = HashMap::from([
+                    ("forever_in_cdn".into(), 86400),
+                    ("short_in_cdn_long_in_browser".into(), 3600),
+                ]);
+            });
+
+            assert_eq!(
+                cache.render(&env.config()),
+                expected.map(|s| HeaderValue::from_str(s).unwrap())
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn stale_directives_in_cache_control_header() {
+        async_wrapper(|env| async move {
+            env.override_config(|config| {
+                config.cdn_stale_if_error =
+                    HashMap::from([("forever_in_cdn_and_browser".into(), 3600)]);
+            });
+            let web = env.web_app().await;
+
+            let response = web.assert_success("/-/static/index.js").await?;
+            assert_eq!(
+                response.headers().get(CACHE_CONTROL).unwrap(),
+                "max-age=31104000, stale-if-error=3600"
+            );
+            response.assert_cache_control(CachePolicy::ForeverInCdnAndBrowser, &env.config());
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn render_stale_without_config() {
         wrapper(|env| {
@@ -195,4 +399,188 @@ mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn cache_policy_metric_is_recorded() {
+        async_wrapper(|env| async move {
+            let web = env.web_app().await;
+
+            web.assert_success("/-/static/index.js").await?;
+            web.assert_success("/-/static/index.js").await?;
+            assert!(web
+                .get("/-/static/whoop-de-do.png")
+                .await?
+                .status()
+                .is_client_error());
+
+            let metrics = env.instance_metrics();
+            let count = |policy: CachePolicy| {
+                metrics
+                    .cache_policy
+                    .with_label_values(&[policy.label()])
+                    .get()
+            };
+            assert_eq!(count(CachePolicy::ForeverInCdnAndBrowser), 2);
+            assert_eq!(count(CachePolicy::NoCaching), 1);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn json_responses_get_an_etag() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            let path = "/crate/foo/0.1.0/status.json";
+
+            let first = web.assert_success(path).await?;
+            let etag = first
+                .headers()
+                .get(ETAG)
+                .expect("missing etag on JSON response")
+                .clone();
+            let second = web.assert_success(path).await?;
+            assert_eq!(second.headers().get(ETAG), Some(&etag));
+
+            let response = web
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri(path)
+                        .header(IF_NONE_MATCH, etag.clone())
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await?;
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(response.headers().get(ETAG), Some(&etag));
+            response.assert_cache_control(CachePolicy::NoStoreMustRevalidate, &env.config());
+            assert!(response.bytes().await?.is_empty());
+
+            let response = web
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri(path)
+                        .header(IF_NONE_MATCH, "\"something-else\"")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            Ok(())
+        });
+    }
+
+    #[test_case(&["\"something-else\", {etag}"], true; "list with match")]
+    #[test_case(&["\"something-else\", \"another-one\""], false; "list without match")]
+    #[test_case(&["\"something-else\"", "{etag}"], true; "multiple headers with match")]
+    #[test_case(&["W/{etag}"], true; "weak match")]
+    #[test_case(&["*"], true; "any")]
+    fn json_etag_if_none_match_variants(if_none_match: &[&str], not_modified: bool) {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            let path = "/crate/foo/0.1.0/status.json";
+
+            let etag = web.assert_success(path).await?.headers()[ETAG]
+                .to_str()?
+                .to_owned();
+
+            let mut request = Request::builder().uri(path);
+            for value in if_none_match {
+                request = request.header(IF_NONE_MATCH, value.replace("{etag}", &etag));
+            }
+            let response = web.clone().oneshot(request.body(Body::empty())?).await?;
+
+            if not_modified {
+                assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+                assert_eq!(response.headers()[ETAG], etag.as_str());
+            } else {
+                assert_eq!(response.status(), StatusCode::OK);
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn json_etag_if_none_match_any_needs_existing_resource() {
+        async_wrapper(|env| async move {
+            let response = env
+                .web_app()
+                .await
+                .oneshot(
+                    Request::builder()
+                        .uri("/crate/foo/0.1.0/status.json")
+                        .header(IF_NONE_MATCH, "*")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            Ok(())
+        });
+    }
+
+    #[tokio::test]
+    async fn streamed_or_large_json_has_no_etag() -> anyhow::Result<()> {
+        let json = |body: Body| ([(CONTENT_TYPE, "application/json")], body);
+        let app = axum::Router::new()
+            .route("/small", get(move || async move { json(Body::from("{}")) }))
+            .route(
+                "/streamed",
+                get(move || async move {
+                    json(Body::from_stream(futures_util::stream::iter([Ok::<
+                        _,
+                        std::io::Error,
+                    >(
+                        "{}"
+                    )])))
+                }),
+            )
+            .route(
+                "/large",
+                get(move || async move {
+                    json(Body::from(format!(
+                        "\"{}\"",
+                        "x".repeat(MAX_JSON_ETAG_SIZE as usize)
+                    )))
+                }),
+            )
+            .layer(axum::middleware::from_fn(json_etag_middleware));
+
+        for (path, has_etag) in [("/small", true), ("/streamed", false), ("/large", false)] {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri(path).body(Body::empty())?)
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK, "{path}");
+            assert_eq!(response.headers().contains_key(ETAG), has_etag, "{path}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn html_responses_have_no_etag() {
+        async_wrapper(|env| async move {
+            let web = env.web_app().await;
+            let response = web.assert_success("/").await?;
+            assert!(response.headers().get(ETAG).is_none());
+            Ok(())
+        });
+    }
 }
@@ -11,15 +11,18 @@ use crate::{
         encode_url_path,
         error::{AxumNope, AxumResult},
         extractors::{DbConnection, Path},
+        headers::CanonicalUrl,
+        markdown,
         page::templates::{filters, RenderRegular, RenderSolid},
         rustdoc::RustdocHtmlParams,
-        MatchedRelease, ReqVersion,
+        MatchedRelease, ParsedReqVersion, ReqVersion,
     },
-    AsyncStorage,
+    AsyncStorage, Config, InstanceMetrics,
 };
 use anyhow::{anyhow, Context, Result};
 use axum::{
-    extract::Extension,
+    extract::{Extension, Query},
+    http::HeaderValue,
     response::{IntoResponse, Response as AxumResponse},
 };
 use chrono::{DateTime, Utc};
@@ -29,7 +32,12 @@ use rinja::Template;
 use semver::Version;
 use serde::Deserialize;
 use serde_json::Value;
-use std::sync::Arc;
+use std::{cmp::Ordering, collections::BTreeMap, sync::Arc};
+
+/// descriptions with fewer words than this are replaced by an excerpt of the README.
+const MIN_DESCRIPTION_WORDS: usize = 2;
+/// maximum length of the README excerpt shown instead of the description.
+const README_EXCERPT_LEN: usize = 200;
 
 // TODO: Add target name and versions
 #[derive(Debug, Clone, PartialEq)]
@@ -50,12 +58,17 @@ pub(crate) struct CrateDetails {
     pub repository_url: Option<String>,
     pub homepage_url: Option<String>,
     keywords: Option<Value>,
+    /// keywords of the crate on crates.io
+    crate_keywords: Vec<String>,
+    /// category slugs of the crate on crates.io
+    crate_categories: Vec<String>,
     have_examples: Option<bool>, // need to check this manually
     pub target_name: Option<String>,
     releases: Vec<Release>,
     repository_metadata: Option<RepositoryMetadata>,
     pub(crate) metadata: MetaData,
     is_library: Option<bool>,
+    is_proc_macro: bool,
     pub(crate) license: Option<String>,
     pub(crate) documentation_url: Option<String>,
     pub(crate) total_items: Option<i32>,
@@ -68,6 +81,12 @@ pub(crate) struct CrateDetails {
     pub(crate) release_id: ReleaseId,
     source_size: Option<i64>,
     documentation_size: Option<i64>,
+    /// whether the latest successful build passed `--cfg docsrs` to rustdoc
+    built_with_cfg_docsrs: bool,
+    downloads: Option<i32>,
+    /// number of crates whose latest release depends on this crate,
+    /// refreshed periodically.
+    reverse_dependency_count: i32,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -99,6 +118,7 @@ pub(crate) struct Release {
     pub is_library: Option<bool>,
     pub rustdoc_status: Option<bool>,
     pub target_name: Option<String>,
+    pub downloads: Option<i32>,
 }
 
 impl CrateDetails {
@@ -145,6 +165,9 @@ impl CrateDetails {
                 releases.repository_url,
                 releases.homepage_url,
                 releases.keywords,
+                crates.keywords AS crate_keywords,
+                crates.categories AS crate_categories,
+                crates.reverse_dependency_count,
                 releases.have_examples,
                 releases.target_name,
                 repositories.host as "repo_host?",
@@ -153,17 +176,21 @@ impl CrateDetails {
                 repositories.issues as "repo_issues?",
                 repositories.name as "repo_name?",
                 releases.is_library,
+                releases.is_proc_macro,
                 releases.yanked,
                 releases.doc_targets,
                 releases.license,
                 releases.documentation_url,
                 releases.default_target,
                 releases.source_size as "source_size?",
+                releases.downloads,
                 builds.documentation_size as "documentation_size?",
+                builds.cargo_args as "cargo_args?",
                 -- we're using the rustc version here to set the correct CSS file
                 -- in the metadata.
                 -- So we're only interested in successful builds here.
                 builds.rustc_version as "rustc_version?",
+                builds.build_finished as "build_finished?",
                 doc_coverage.total_items,
                 doc_coverage.documented_items,
                 doc_coverage.total_items_needing_examples,
@@ -174,7 +201,7 @@ impl CrateDetails {
             LEFT JOIN doc_coverage ON doc_coverage.release_id = releases.id
             LEFT JOIN repositories ON releases.repository_id = repositories.id
             LEFT JOIN LATERAL (
-                 SELECT rustc_version, documentation_size, id
+                 SELECT rustc_version, documentation_size, cargo_args, build_finished, id
                  FROM builds
                  WHERE
                     builds.rid = releases.id AND
@@ -207,14 +234,18 @@ impl CrateDetails {
             description: krate.description.clone(),
             rustdoc_status: krate.rustdoc_status,
             target_name: krate.target_name.clone(),
+            doc_targets: krate.doc_targets.map(|targets| {
+                MetaData::parse_doc_targets_default_first(targets, krate.default_target.as_deref())
+            }),
             default_target: krate.default_target,
-            doc_targets: krate.doc_targets.map(MetaData::parse_doc_targets),
             yanked: krate.yanked,
             rustdoc_css_file: krate
                 .rustc_version
                 .as_deref()
                 .map(get_correct_docsrs_style_file)
                 .transpose()?,
+            build_finished: krate.build_finished,
+            rustc_version: krate.rustc_version,
         };
 
         // When documentation_url points to docs.rs itself, then we don't need to
@@ -242,6 +273,8 @@ impl CrateDetails {
             repository_url: krate.repository_url,
             homepage_url: krate.homepage_url,
             keywords: krate.keywords,
+            crate_keywords: krate.crate_keywords,
+            crate_categories: krate.crate_categories,
             have_examples: krate.have_examples,
             target_name: krate.target_name,
             releases: prefetched_releases,
@@ -249,6 +282,7 @@ impl CrateDetails {
             metadata,
             documentation_url,
             is_library: krate.is_library,
+            is_proc_macro: krate.is_proc_macro.unwrap_or(false),
             license: krate.license,
             documented_items: krate.documented_items,
             total_items: krate.total_items,
@@ -257,7 +291,13 @@ impl CrateDetails {
             crate_id: krate.crate_id,
             release_id: krate.release_id,
             documentation_size: krate.documentation_size,
+            built_with_cfg_docsrs: krate
+                .cargo_args
+                .as_deref()
+                .is_some_and(built_with_cfg_docsrs),
             source_size: krate.source_size,
+            downloads: krate.downloads,
+            reverse_dependency_count: krate.reverse_dependency_count,
         };
 
         // get owners
@@ -265,7 +305,8 @@ impl CrateDetails {
             r#"SELECT login, avatar, kind as "kind: OwnerKind"
              FROM owners
              INNER JOIN owner_rels ON owner_rels.oid = owners.id
-             WHERE cid = $1"#,
+             WHERE cid = $1
+             ORDER BY login"#,
             krate.crate_id.0,
         )
         .fetch(&mut *conn)
@@ -287,6 +328,41 @@ impl CrateDetails {
         Ok(Some(crate_details))
     }
 
+    /// Compare the documented targets of this release with the ones of the
+    /// previous release that has documentation.
+    ///
+    /// Returns the previous version and the diff, or `None` when there is no
+    /// previous release to compare with.
+    async fn fetch_target_diff(
+        &self,
+        conn: &mut sqlx::PgConnection,
+    ) -> Result<Option<(Version, TargetDiff)>> {
+        let Some(current_targets) = self.metadata.doc_targets() else {
+            return Ok(None);
+        };
+
+        let Some(previous) = self.releases.iter().find(|release| {
+            release.version < self.version && release.rustdoc_status.unwrap_or(false)
+        }) else {
+            return Ok(None);
+        };
+
+        let Some(previous_targets) = sqlx::query_scalar!(
+            "SELECT doc_targets FROM releases WHERE id = $1",
+            previous.id.0
+        )
+        .fetch_one(&mut *conn)
+        .await?
+        .map(MetaData::parse_doc_targets) else {
+            return Ok(None);
+        };
+
+        Ok(Some((
+            previous.version.clone(),
+            target_diff(&previous_targets, current_targets),
+        )))
+    }
+
     #[fn_error_context::context("fetching readme for {} {}", self.name, self.version)]
     async fn fetch_readme(&self, storage: &AsyncStorage) -> anyhow::Result<Option<String>> {
         let manifest = match storage
@@ -349,6 +425,36 @@ impl CrateDetails {
     pub fn latest_release(&self) -> Result<&Release> {
         latest_release(&self.releases).ok_or_else(|| anyhow!("crate without releases"))
     }
+
+    /// Returns the sum of the download counts of all releases of this crate.
+    pub(crate) fn total_downloads(&self) -> i64 {
+        self.releases
+            .iter()
+            .filter_map(|release| release.downloads)
+            .map(i64::from)
+            .sum()
+    }
+}
+
+/// Group releases that only differ in their build metadata, like `1.0.0+a` and `1.0.0+b`.
+///
+/// Each group starts with the first of these releases in `releases`, followed by the others.
+/// Expects `releases` to be sorted by version, which keeps these variants next to each other.
+pub(crate) fn group_build_metadata_variants(
+    releases: &[Release],
+) -> Vec<(&Release, Vec<&Release>)> {
+    let mut groups: Vec<(&Release, Vec<&Release>)> = Vec::new();
+    for release in releases {
+        match groups.last_mut() {
+            Some((first, variants))
+                if first.version.cmp_precedence(&release.version) == Ordering::Equal =>
+            {
+                variants.push(release)
+            }
+            _ => groups.push((release, Vec::new())),
+        }
+    }
+    groups
 }
 
 pub(crate) fn latest_release(releases: &[Release]) -> Option<&Release> {
@@ -378,7 +484,8 @@ pub(crate) async fn releases_for_crate(
              releases.yanked,
              releases.is_library,
              releases.rustdoc_status,
-             releases.target_name
+             releases.target_name,
+             releases.downloads
          FROM releases
          INNER JOIN release_build_status ON releases.id = release_build_status.rid
          WHERE
@@ -408,6 +515,7 @@ pub(crate) async fn releases_for_crate(
             is_library: row.is_library,
             rustdoc_status: row.rustdoc_status,
             target_name: row.target_name,
+            downloads: row.downloads,
         }))
     })
     .try_collect()
@@ -417,6 +525,54 @@ pub(crate) async fn releases_for_crate(
     Ok(releases)
 }
 
+/// Whether the recorded cargo arguments of a build passed `--cfg docsrs` to rustdoc.
+///
+/// The rustdoc flags are given to cargo as `--config build.rustdocflags=[...]`.
+fn built_with_cfg_docsrs(cargo_args: &[String]) -> bool {
+    #[derive(Deserialize)]
+    struct RustdocFlags {
+        flags: Vec<String>,
+    }
+
+    cargo_args
+        .iter()
+        .filter_map(|arg| arg.strip_prefix("build.rustdocflags="))
+        .filter_map(|flags| toml::from_str::<RustdocFlags>(&format!("flags = {flags}")).ok())
+        .any(|RustdocFlags { flags }| {
+            flags
+                .windows(2)
+                .any(|pair| pair[0] == "--cfg" && pair[1] == "docsrs")
+        })
+}
+
+/// Documented targets that were added or removed between two releases.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct TargetDiff {
+    pub(crate) added: Vec<String>,
+    pub(crate) removed: Vec<String>,
+}
+
+impl TargetDiff {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+pub(crate) fn target_diff(prev: &[String], curr: &[String]) -> TargetDiff {
+    TargetDiff {
+        added: curr
+            .iter()
+            .filter(|target| !prev.contains(target))
+            .cloned()
+            .collect(),
+        removed: prev
+            .iter()
+            .filter(|target| !curr.contains(target))
+            .cloned()
+            .collect(),
+    }
+}
+
 #[derive(Template)]
 #[template(path = "crate/details.html")]
 #[derive(Debug, Clone, PartialEq)]
@@ -424,6 +580,10 @@ struct CrateDetailsPage {
     version: Version,
     name: String,
     owners: Vec<(String, String, OwnerKind)>,
+    /// owners not shown inline, they are listed on the owners page.
+    hidden_owners: usize,
+    crate_keywords: Vec<String>,
+    crate_categories: Vec<String>,
     metadata: MetaData,
     documented_items: Option<i32>,
     total_items: Option<i32>,
@@ -439,11 +599,20 @@ struct CrateDetailsPage {
     build_status: BuildStatus,
     rustdoc_status: Option<bool>,
     is_library: Option<bool>,
+    is_proc_macro: bool,
     last_successful_build: Option<String>,
     rustdoc: Option<String>, // this is description_long in database
+    target_diff: Option<(Version, TargetDiff)>,
     csp_nonce: String,
     source_size: Option<i64>,
     documentation_size: Option<i64>,
+    built_with_cfg_docsrs: bool,
+    downloads: Option<i32>,
+    total_downloads: i64,
+    reverse_dependency_count: i32,
+    canonical_url: CanonicalUrl,
+    include_yanked: bool,
+    hidden_yanked_releases: usize,
 }
 
 impl CrateDetailsPage {
@@ -461,24 +630,38 @@ impl_axum_webpage! {
 #[derive(Deserialize, Clone, Debug)]
 pub(crate) struct CrateDetailHandlerParams {
     name: String,
-    version: Option<ReqVersion>,
+    version: Option<ParsedReqVersion>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub(crate) struct CrateDetailQueryParams {
+    /// list yanked releases in the sidebar, they are hidden by default.
+    #[serde(default)]
+    include_yanked: bool,
 }
 
-#[tracing::instrument(skip(conn, storage))]
+#[tracing::instrument(skip(conn, metrics, storage, config))]
 pub(crate) async fn crate_details_handler(
     Path(params): Path<CrateDetailHandlerParams>,
+    Query(query): Query<CrateDetailQueryParams>,
     Extension(storage): Extension<Arc<AsyncStorage>>,
+    Extension(config): Extension<Arc<Config>>,
     mut conn: DbConnection,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
 ) -> AxumResult<AxumResponse> {
-    let req_version = params.version.ok_or_else(|| {
+    let ParsedReqVersion {
+        req: req_version,
+        raw: raw_version,
+    } = params.version.ok_or_else(|| {
         AxumNope::Redirect(
             format!("/crate/{}/{}", &params.name, ReqVersion::Latest),
             CachePolicy::ForeverInCdn,
         )
     })?;
 
-    let matched_release = match_version(&mut conn, &params.name, &req_version)
-        .await?
+    let matched_release = match_version(&mut conn, &metrics, &params.name, &req_version)
+        .await
+        .map_err(|err| err.with_requested_version(&raw_version))?
         .assume_exact_name()?
         .into_canonical_req_version_or_else(|version| {
             AxumNope::Redirect(
@@ -494,10 +677,43 @@ pub(crate) async fn crate_details_handler(
         Err(e) => warn!("error fetching readme: {:?}", &e),
     }
 
+    // crates without a meaningful description get the start of their README
+    // in the page header instead.
+    let has_description = details
+        .metadata
+        .description
+        .as_deref()
+        .is_some_and(|description| description.split_whitespace().count() >= MIN_DESCRIPTION_WORDS);
+    if !has_description {
+        if let Some(excerpt) = details
+            .readme
+            .as_deref()
+            .and_then(|readme| markdown::excerpt(readme, README_EXCERPT_LEN))
+        {
+            details.metadata.description = Some(excerpt);
+        }
+    }
+
+    let target_diff = match details.fetch_target_diff(&mut conn).await {
+        Ok(diff) => diff.filter(|(_, diff)| !diff.is_empty()),
+        Err(e) => {
+            warn!("error fetching target diff: {:?}", &e);
+            None
+        }
+    };
+
+    let total_downloads = details.total_downloads();
+    let canonical_url = CanonicalUrl::from_path(
+        &config.external_base_url,
+        format!("/crate/{}/latest", details.name),
+    );
+
     let CrateDetails {
         version,
         name,
-        owners,
+        mut owners,
+        crate_keywords,
+        crate_categories,
         metadata,
         documented_items,
         total_items,
@@ -508,22 +724,43 @@ pub(crate) async fn crate_details_handler(
         repository_url,
         repository_metadata,
         dependencies,
-        releases,
+        mut releases,
         readme,
         build_status,
         rustdoc_status,
         is_library,
+        is_proc_macro,
         last_successful_build,
         rustdoc,
         source_size,
         documentation_size,
+        built_with_cfg_docsrs,
+        downloads,
+        reverse_dependency_count,
         ..
     } = details;
 
+    // crates with many owners only get the first few inline, with a link
+    // to the full list.
+    let hidden_owners = owners.len().saturating_sub(config.max_inline_owners);
+    owners.truncate(config.max_inline_owners);
+
+    // the release we're looking at is always listed, even when it's yanked.
+    let hidden_yanked_releases = if query.include_yanked {
+        0
+    } else {
+        let before = releases.len();
+        releases.retain(|release| release.yanked != Some(true) || release.version == version);
+        before - releases.len()
+    };
+
     let mut res = CrateDetailsPage {
         version,
         name,
         owners,
+        hidden_owners,
+        crate_keywords,
+        crate_categories,
         metadata,
         documented_items,
         total_items,
@@ -539,11 +776,20 @@ pub(crate) async fn crate_details_handler(
         build_status,
         rustdoc_status,
         is_library,
+        is_proc_macro,
         last_successful_build,
         rustdoc,
+        target_diff,
         csp_nonce: String::new(),
         source_size,
         documentation_size,
+        built_with_cfg_docsrs,
+        downloads,
+        total_downloads,
+        reverse_dependency_count,
+        canonical_url,
+        include_yanked: query.include_yanked,
+        hidden_yanked_releases,
     }
     .into_response();
     res.extensions_mut()
@@ -552,9 +798,134 @@ pub(crate) async fn crate_details_handler(
         } else {
             CachePolicy::ForeverInCdnAndStaleInBrowser
         });
+    // the page of a release that is still building is only transient,
+    // crawlers shouldn't index it.
+    if build_status == BuildStatus::InProgress {
+        res.headers_mut()
+            .insert("X-Robots-Tag", HeaderValue::from_static("noindex"));
+    }
     Ok(res.into_response())
 }
 
+#[derive(Template)]
+#[template(path = "crate/readme.html")]
+#[derive(Debug, Clone)]
+struct ReadmePage {
+    metadata: MetaData,
+    readme: String,
+    is_latest_url: bool,
+    canonical_url: CanonicalUrl,
+    csp_nonce: String,
+}
+
+impl ReadmePage {
+    // Used by templates.
+    pub(crate) fn use_direct_platform_links(&self) -> bool {
+        true
+    }
+}
+
+impl_axum_webpage! {
+    ReadmePage,
+    cache_policy = |page| if page.is_latest_url {
+        CachePolicy::ForeverInCdn
+    } else {
+        CachePolicy::ForeverInCdnAndStaleInBrowser
+    },
+    cpu_intensive_rendering = true,
+}
+
+#[tracing::instrument(skip(conn, metrics, storage, config))]
+pub(crate) async fn readme_handler(
+    Path((name, req_version)): Path<(String, ReqVersion)>,
+    Extension(storage): Extension<Arc<AsyncStorage>>,
+    Extension(config): Extension<Arc<Config>>,
+    mut conn: DbConnection,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
+) -> AxumResult<AxumResponse> {
+    let matched_release = match_version(&mut conn, &metrics, &name, &req_version)
+        .await?
+        .assume_exact_name()?
+        .into_canonical_req_version_or_else(|version| {
+            AxumNope::Redirect(
+                format!("/crate/{}/{}/readme", &name, version),
+                CachePolicy::ForeverInCdn,
+            )
+        })?;
+    let is_latest_url = matched_release.is_latest_url();
+
+    let details = CrateDetails::from_matched_release(&mut conn, matched_release).await?;
+
+    let readme = match details.fetch_readme(&storage).await {
+        Ok(readme) => readme.or(details.readme),
+        Err(e) => {
+            warn!("error fetching readme: {:?}", &e);
+            details.readme
+        }
+    };
+    let readme = readme.ok_or(AxumNope::ResourceNotFound)?;
+
+    Ok(ReadmePage {
+        metadata: details.metadata,
+        readme,
+        is_latest_url,
+        canonical_url: CanonicalUrl::from_path(
+            &config.external_base_url,
+            format!("/crate/{name}/latest/readme"),
+        ),
+        csp_nonce: String::new(),
+    }
+    .into_response())
+}
+
+#[derive(Template)]
+#[template(path = "crate/owners.html")]
+#[derive(Debug, Clone)]
+struct OwnersPage {
+    metadata: MetaData,
+    owners: Vec<(String, String, OwnerKind)>,
+    canonical_url: CanonicalUrl,
+    csp_nonce: String,
+}
+
+impl OwnersPage {
+    // Used by templates.
+    pub(crate) fn use_direct_platform_links(&self) -> bool {
+        true
+    }
+}
+
+impl_axum_webpage! {
+    OwnersPage,
+    cache_policy = |_| CachePolicy::ForeverInCdn,
+}
+
+/// Lists all owners of a crate, the crate page only shows the first few.
+#[tracing::instrument(skip(conn, metrics, config))]
+pub(crate) async fn owners_handler(
+    Path(name): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+    mut conn: DbConnection,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
+) -> AxumResult<AxumResponse> {
+    let matched_release = match_version(&mut conn, &metrics, &name, &ReqVersion::Latest)
+        .await?
+        .assume_exact_name()?;
+
+    let details = CrateDetails::from_matched_release(&mut conn, matched_release).await?;
+
+    Ok(OwnersPage {
+        metadata: details.metadata,
+        owners: details.owners,
+        canonical_url: CanonicalUrl::from_path(
+            &config.external_base_url,
+            format!("/crate/{name}/owners"),
+        ),
+        csp_nonce: String::new(),
+    }
+    .into_response())
+}
+
 #[derive(Template)]
 #[template(path = "rustdoc/releases.html")]
 #[derive(Debug, Clone, PartialEq)]
@@ -576,11 +947,12 @@ impl_axum_webpage! {
 pub(crate) async fn get_all_releases(
     Path(params): Path<RustdocHtmlParams>,
     mut conn: DbConnection,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
 ) -> AxumResult<AxumResponse> {
     let req_path: String = params.path.clone().unwrap_or_default();
     let req_path: Vec<&str> = req_path.split('/').collect();
 
-    let matched_release = match_version(&mut conn, &params.name, &params.version)
+    let matched_release = match_version(&mut conn, &metrics, &params.name, &params.version)
         .await?
         .into_canonical_req_version_or_else(|_| AxumNope::VersionNotFound)?;
 
@@ -665,6 +1037,84 @@ impl ShortMetadata {
     }
 }
 
+/// Operating system family of a target triple, used to group the platform dropdown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum OsGroup {
+    Linux,
+    Windows,
+    Apple,
+    Android,
+    Bsd,
+    Wasm,
+    Other,
+}
+
+impl OsGroup {
+    fn from_target(target: &str) -> Self {
+        let components: Vec<&str> = target.split('-').collect();
+        let has = |name: &str| {
+            components
+                .iter()
+                .any(|component| component.starts_with(name))
+        };
+
+        if target.starts_with("wasm") || has("wasi") || has("emscripten") {
+            Self::Wasm
+        } else if has("android") {
+            Self::Android
+        } else if has("linux") {
+            Self::Linux
+        } else if has("windows") {
+            Self::Windows
+        } else if has("apple") || has("darwin") {
+            Self::Apple
+        } else if ["freebsd", "netbsd", "openbsd", "dragonfly"]
+            .iter()
+            .any(|bsd| has(bsd))
+        {
+            Self::Bsd
+        } else {
+            Self::Other
+        }
+    }
+
+    // Used in templates.
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            Self::Linux => "Linux",
+            Self::Windows => "Windows",
+            Self::Apple => "Apple",
+            Self::Android => "Android",
+            Self::Bsd => "BSD",
+            Self::Wasm => "WebAssembly",
+            Self::Other => "Other",
+        }
+    }
+}
+
+/// Group target triples by their operating system, keeping their order within each group.
+///
+/// The groups are sorted by operating system, except for the group of the first target,
+/// which comes first. `doc_targets` start with the default target, so it stays on top.
+pub(crate) fn group_targets(targets: &[String]) -> Vec<(OsGroup, Vec<String>)> {
+    let mut groups: BTreeMap<OsGroup, Vec<String>> = BTreeMap::new();
+    for target in targets {
+        groups
+            .entry(OsGroup::from_target(target))
+            .or_default()
+            .push(target.clone());
+    }
+
+    let mut groups: Vec<_> = groups.into_iter().collect();
+    if let Some(first) = targets.first() {
+        let first_group = OsGroup::from_target(first);
+        if let Some(pos) = groups.iter().position(|(group, _)| *group == first_group) {
+            groups[..=pos].rotate_right(1);
+        }
+    }
+    groups
+}
+
 #[derive(Template)]
 #[template(path = "rustdoc/platforms.html")]
 #[derive(Debug, Clone, PartialEq)]
@@ -686,12 +1136,13 @@ impl_axum_webpage! {
 pub(crate) async fn get_all_platforms_inner(
     Path(params): Path<RustdocHtmlParams>,
     mut conn: DbConnection,
+    metrics: &InstanceMetrics,
     is_crate_root: bool,
 ) -> AxumResult<AxumResponse> {
     let req_path: String = params.path.unwrap_or_default();
     let req_path: Vec<&str> = req_path.split('/').collect();
 
-    let matched_release = match_version(&mut conn, &params.name, &params.version)
+    let matched_release = match_version(&mut conn, metrics, &params.name, &params.version)
         .await?
         .into_exactly_named_or_else(|corrected_name, req_version| {
             AxumNope::Redirect(
@@ -749,7 +1200,10 @@ pub(crate) async fn get_all_platforms_inner(
         .into_response());
     }
 
-    let doc_targets = MetaData::parse_doc_targets(krate.doc_targets.unwrap());
+    let doc_targets = MetaData::parse_doc_targets_default_first(
+        krate.doc_targets.unwrap(),
+        krate.default_target.as_deref(),
+    );
 
     // The path within this crate version's rustdoc output
     let inner;
@@ -807,16 +1261,18 @@ pub(crate) async fn get_all_platforms_inner(
 pub(crate) async fn get_all_platforms_root(
     Path(mut params): Path<RustdocHtmlParams>,
     conn: DbConnection,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
 ) -> AxumResult<AxumResponse> {
     params.path = None;
-    get_all_platforms_inner(Path(params), conn, true).await
+    get_all_platforms_inner(Path(params), conn, &metrics, true).await
 }
 
 pub(crate) async fn get_all_platforms(
     params: Path<RustdocHtmlParams>,
     conn: DbConnection,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
 ) -> AxumResult<AxumResponse> {
-    get_all_platforms_inner(params, conn, false).await
+    get_all_platforms_inner(params, conn, &metrics, false).await
 }
 
 #[cfg(test)]
@@ -826,12 +1282,17 @@ mod tests {
         async_wrapper, fake_release_that_failed_before_build, AxumResponseTestExt,
         AxumRouterTestExt, FakeBuild, TestDatabase, TestEnvironment,
     };
-    use crate::{db::update_build_status, registry_api::CrateOwner};
+    use crate::{
+        db::{update_build_status, update_reverse_dependency_counts},
+        registry_api::CrateOwner,
+        utils::Dependency,
+    };
     use anyhow::Error;
     use kuchikiki::traits::TendrilSink;
     use pretty_assertions::assert_eq;
     use reqwest::StatusCode;
     use std::collections::HashMap;
+    use test_case::test_case;
 
     async fn release_build_status(
         conn: &mut sqlx::PgConnection,
@@ -952,6 +1413,60 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_crate_details_is_proc_macro() {
+        async_wrapper(|env| async move {
+            let db = env.async_db().await;
+            let mut conn = db.async_conn().await;
+
+            env.fake_release()
+                .await
+                .name("some-macros")
+                .version("0.1.0")
+                .proc_macro(true)
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("some-lib")
+                .version("0.1.0")
+                .create()
+                .await?;
+
+            assert!(
+                crate_details(&mut conn, "some-macros", "0.1.0", None)
+                    .await
+                    .is_proc_macro
+            );
+            assert!(
+                !crate_details(&mut conn, "some-lib", "0.1.0", None)
+                    .await
+                    .is_proc_macro
+            );
+
+            let web = env.web_app().await;
+            let page = kuchikiki::parse_html().one(
+                web.get("/crate/some-macros/0.1.0")
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?,
+            );
+            assert!(page.select_first(".crate-kind-badge").is_ok());
+
+            let page = kuchikiki::parse_html().one(
+                web.get("/crate/some-lib/0.1.0")
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?,
+            );
+            assert!(page.select_first(".crate-kind-badge").is_err());
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn test_last_successful_build_when_last_releases_failed_or_yanked() {
         async_wrapper(|env| async move {
@@ -1075,6 +1590,49 @@ mod tests {
         });
     }
 
+    #[test]
+    fn build_metadata_variants_are_grouped() {
+        async_wrapper(|env| async move {
+            for version in ["0.9.0", "1.0.0+a", "1.0.0+b", "1.0.0-rc.1+a"] {
+                env.fake_release()
+                    .await
+                    .name("foo")
+                    .version(version)
+                    .create()
+                    .await?;
+            }
+
+            let page = kuchikiki::parse_html().one(
+                env.web_app()
+                    .await
+                    .get("/crate/foo/latest")
+                    .await?
+                    .text()
+                    .await?,
+            );
+            let versions = |selector: &str| -> Vec<String> {
+                page.select(selector)
+                    .expect("invalid selector")
+                    .map(|el| el.attributes.borrow().get("href").unwrap().to_owned())
+                    .collect()
+            };
+
+            assert_eq!(
+                versions(r#".sub-menu > ul > li > a[href^="/crate/foo/"]"#),
+                [
+                    "/crate/foo/1.0.0+b",
+                    "/crate/foo/1.0.0-rc.1+a",
+                    "/crate/foo/0.9.0"
+                ]
+            );
+            assert_eq!(
+                versions(".sub-menu > ul > li > ul.build-metadata-variants > li > a"),
+                ["/crate/foo/1.0.0+a"]
+            );
+            Ok(())
+        });
+    }
+
     #[test]
     fn test_releases_should_be_sorted() {
         async_wrapper(|env| async move {
@@ -1148,6 +1706,7 @@ mod tests {
                         rustdoc_status: Some(true),
                         id: details.releases[0].id,
                         target_name: Some("foo".to_owned()),
+                        downloads: Some(0),
                     },
                     Release {
                         version: semver::Version::parse("0.12.0")?,
@@ -1157,6 +1716,7 @@ mod tests {
                         rustdoc_status: Some(true),
                         id: details.releases[1].id,
                         target_name: Some("foo".to_owned()),
+                        downloads: Some(0),
                     },
                     Release {
                         version: semver::Version::parse("0.3.0")?,
@@ -1166,6 +1726,7 @@ mod tests {
                         rustdoc_status: Some(false),
                         id: details.releases[2].id,
                         target_name: Some("foo".to_owned()),
+                        downloads: Some(0),
                     },
                     Release {
                         version: semver::Version::parse("0.2.0")?,
@@ -1175,6 +1736,7 @@ mod tests {
                         rustdoc_status: Some(true),
                         id: details.releases[3].id,
                         target_name: Some("foo".to_owned()),
+                        downloads: Some(0),
                     },
                     Release {
                         version: semver::Version::parse("0.2.0-alpha")?,
@@ -1184,6 +1746,7 @@ mod tests {
                         rustdoc_status: Some(true),
                         id: details.releases[4].id,
                         target_name: Some("foo".to_owned()),
+                        downloads: Some(0),
                     },
                     Release {
                         version: semver::Version::parse("0.1.1")?,
@@ -1193,6 +1756,7 @@ mod tests {
                         rustdoc_status: Some(true),
                         id: details.releases[5].id,
                         target_name: Some("foo".to_owned()),
+                        downloads: Some(0),
                     },
                     Release {
                         version: semver::Version::parse("0.1.0")?,
@@ -1202,6 +1766,7 @@ mod tests {
                         rustdoc_status: Some(true),
                         id: details.releases[6].id,
                         target_name: Some("foo".to_owned()),
+                        downloads: Some(0),
                     },
                     Release {
                         version: semver::Version::parse("0.0.1")?,
@@ -1211,6 +1776,7 @@ mod tests {
                         rustdoc_status: Some(false),
                         id: details.releases[7].id,
                         target_name: Some("foo".to_owned()),
+                        downloads: Some(0),
                     },
                 ]
             );
@@ -1465,7 +2031,7 @@ mod tests {
                     .borrow()
                     .get("title")
                     .unwrap(),
-                "binary-0.1.0 is not a library"
+                "binary-0.1.0 only ships binaries, no library documentation"
             );
 
             Ok(())
@@ -1473,50 +2039,350 @@ mod tests {
     }
 
     #[test]
-    fn releases_dropdowns_show_in_progress() {
+    fn test_canonical_url_uses_external_base_url() {
+        async_wrapper(|env| async move {
+            env.override_config(|config| {
+                config.external_base_url = "https://mirror.example".parse().unwrap();
+            });
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.0.1")
+                .create()
+                .await?;
+            let web = env.web_app().await;
+
+            for (path, canonical) in [
+                (
+                    "/crate/foo/0.0.1",
+                    "https://mirror.example/crate/foo/latest",
+                ),
+                (
+                    "/crate/foo/0.0.1/builds",
+                    "https://mirror.example/crate/foo/latest/builds",
+                ),
+                (
+                    "/crate/foo/0.0.1/features",
+                    "https://mirror.example/crate/foo/latest/features",
+                ),
+            ] {
+                let text = web.get(path).await?.text().await?;
+                assert!(
+                    text.contains(&format!(r#"rel="canonical" href="{canonical}""#)),
+                    "{path} has no canonical link to {canonical}"
+                );
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn reverse_dependency_count_is_shown() {
         async_wrapper(|env| async move {
             env.fake_release()
                 .await
                 .name("foo")
                 .version("0.1.0")
-                .builds(vec![
-                    FakeBuild::default().build_status(BuildStatus::InProgress)
-                ])
                 .create()
                 .await?;
+            for name in ["bar", "baz", "qux"] {
+                env.fake_release()
+                    .await
+                    .name(name)
+                    .version("0.1.0")
+                    .add_dependency(Dependency::new("foo".into(), "0.1".into()))
+                    .create()
+                    .await?;
+            }
 
-            let response = env.web_app().await.get("/crate/foo/latest").await?;
+            let web = env.web_app().await;
+            let page =
+                kuchikiki::parse_html().one(web.get("/crate/foo/0.1.0").await?.text().await?);
+            // not shown before the counts were calculated
+            assert!(page.select_first("#reverse-dependencies").is_err());
 
-            let page = kuchikiki::parse_html().one(response.text().await?);
-            let link = page
-                .select_first("a.pure-menu-link[href='/crate/foo/0.1.0']")
-                .unwrap();
+            let mut conn = env.async_db().await.async_conn().await;
+            update_reverse_dependency_counts(&mut conn).await?;
 
+            let page =
+                kuchikiki::parse_html().one(web.get("/crate/foo/0.1.0").await?.text().await?);
+            let reverse_dependencies = page.select_first("#reverse-dependencies").unwrap();
             assert_eq!(
-                link.as_node()
-                    .as_element()
-                    .unwrap()
-                    .attributes
-                    .borrow()
-                    .get("title")
-                    .unwrap(),
-                "foo-0.1.0 is currently being built"
+                reverse_dependencies.text_contents().trim(),
+                "Used by 3 crates"
             );
 
+            let page =
+                kuchikiki::parse_html().one(web.get("/crate/bar/0.1.0").await?.text().await?);
+            assert!(page.select_first("#reverse-dependencies").is_err());
+
             Ok(())
         });
     }
 
     #[test]
-    fn test_updating_owners() {
+    fn download_counts_are_shown() {
         async_wrapper(|env| async move {
-            let db = env.async_db().await;
-
             env.fake_release()
                 .await
                 .name("foo")
-                .version("0.0.1")
-                .add_owner(CrateOwner {
+                .version("0.1.0")
+                .downloads(10)
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.2.0")
+                .downloads(32)
+                .create()
+                .await?;
+
+            let page = kuchikiki::parse_html().one(
+                env.web_app()
+                    .await
+                    .get("/crate/foo/0.2.0")
+                    .await?
+                    .text()
+                    .await?,
+            );
+
+            let downloads = page.select_first("#downloads").unwrap();
+            assert_eq!(downloads.text_contents().trim(), "This version: 32");
+            let total = page.select_first("#total-downloads").unwrap();
+            assert_eq!(total.text_contents().trim(), "All versions: 42");
+
+            let per_version: Vec<_> = page
+                .select("i.downloads")
+                .unwrap()
+                .map(|node| node.text_contents())
+                .collect();
+            assert_eq!(per_version, vec!["32", "10"]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn yanked_releases_are_hidden_unless_requested() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.2.0")
+                .yanked(true)
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.3.0")
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            let release_links = |page: &kuchikiki::NodeRef| -> Vec<String> {
+                page.select("a.pure-menu-link[rel=nofollow]")
+                    .unwrap()
+                    .filter_map(|node| node.attributes.borrow().get("href").map(str::to_owned))
+                    .filter(|href| href.starts_with("/crate/foo/0."))
+                    .collect()
+            };
+
+            let page =
+                kuchikiki::parse_html().one(web.get("/crate/foo/0.3.0").await?.text().await?);
+            assert_eq!(
+                release_links(&page),
+                vec![
+                    "/crate/foo/0.3.0",
+                    "/crate/foo/0.1.0",
+                    "/crate/foo/0.3.0?include_yanked=true"
+                ]
+            );
+            assert!(page.select_first("a.yanked").is_err());
+            assert_eq!(
+                page.select_first("#toggle-yanked")
+                    .unwrap()
+                    .text_contents()
+                    .trim(),
+                "Show 1 yanked release"
+            );
+
+            let page = kuchikiki::parse_html().one(
+                web.get("/crate/foo/0.3.0?include_yanked=true")
+                    .await?
+                    .text()
+                    .await?,
+            );
+            assert_eq!(
+                release_links(&page),
+                vec!["/crate/foo/0.3.0", "/crate/foo/0.2.0", "/crate/foo/0.1.0"]
+            );
+            let yanked = page.select_first("a.yanked").unwrap();
+            assert_eq!(
+                yanked.attributes.borrow().get("href"),
+                Some("/crate/foo/0.2.0")
+            );
+            assert_eq!(
+                page.select_first("#toggle-yanked")
+                    .unwrap()
+                    .text_contents()
+                    .trim(),
+                "Hide yanked releases"
+            );
+
+            // the yanked release itself keeps showing up in its own list
+            let page =
+                kuchikiki::parse_html().one(web.get("/crate/foo/0.2.0").await?.text().await?);
+            assert!(page.select_first("a.yanked").is_ok());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn skipped_build_has_distinct_warning() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .build_result_skipped()
+                .create()
+                .await?;
+
+            let page = kuchikiki::parse_html().one(
+                env.web_app()
+                    .await
+                    .get("/crate/foo/0.1.0")
+                    .await?
+                    .text()
+                    .await?,
+            );
+
+            let warning = page.select_first("#main > div.warning").unwrap();
+            let text = warning.text_contents();
+            assert!(text.contains("docs.rs did not build foo-0.1.0, the build was skipped."));
+            assert!(!text.contains("failed to build"));
+            assert!(page.select_first("a.doc-link").is_err());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn binary_only_crate_has_no_docs_link() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("binary")
+                .version("0.1.0")
+                .binary(true)
+                .create()
+                .await?;
+
+            let page = kuchikiki::parse_html().one(
+                env.web_app()
+                    .await
+                    .get("/crate/binary/0.1.0")
+                    .await?
+                    .text()
+                    .await?,
+            );
+
+            let warning = page.select_first("#main > div.warning").unwrap();
+            assert_eq!(
+                warning.text_contents().trim(),
+                "binary-0.1.0 only ships binaries, no library documentation."
+            );
+            assert!(page.select_first("a.doc-link").is_err());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn library_crate_has_docs_link() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("library")
+                .version("0.1.0")
+                .create()
+                .await?;
+
+            let page = kuchikiki::parse_html().one(
+                env.web_app()
+                    .await
+                    .get("/crate/library/0.1.0")
+                    .await?
+                    .text()
+                    .await?,
+            );
+
+            let link = page.select_first("a.doc-link").unwrap();
+            assert_eq!(
+                link.attributes.borrow().get("href").unwrap(),
+                "/library/0.1.0/library/"
+            );
+            assert!(page.select_first("#main > div.warning").is_err());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn releases_dropdowns_show_in_progress() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .builds(vec![
+                    FakeBuild::default().build_status(BuildStatus::InProgress)
+                ])
+                .create()
+                .await?;
+
+            let response = env.web_app().await.get("/crate/foo/latest").await?;
+
+            let page = kuchikiki::parse_html().one(response.text().await?);
+            let link = page
+                .select_first("a.pure-menu-link[href='/crate/foo/0.1.0']")
+                .unwrap();
+
+            assert_eq!(
+                link.as_node()
+                    .as_element()
+                    .unwrap()
+                    .attributes
+                    .borrow()
+                    .get("title")
+                    .unwrap(),
+                "foo-0.1.0 is currently being built"
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_updating_owners() {
+        async_wrapper(|env| async move {
+            let db = env.async_db().await;
+
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.0.1")
+                .add_owner(CrateOwner {
                     login: "foobar".into(),
                     avatar: "https://example.org/foobar".into(),
                     kind: OwnerKind::User,
@@ -2034,80 +2900,257 @@ mod tests {
         });
     }
 
-    // Ensure that if there are more than a given number of targets, it will not generate them in
-    // the HTML directly (they will be loaded by AJAX if the user opens the menu).
     #[test]
-    #[allow(clippy::assertions_on_constants)]
-    fn platform_menu_ajax() {
-        assert!(crate::DEFAULT_MAX_TARGETS > 2);
-
-        fn check_count(nb_targets: usize, expected: usize) {
-            async_wrapper(|env| async move {
-                let mut rel = env
-                    .fake_release()
-                    .await
-                    .name("dummy")
-                    .version("0.4.0")
-                    .rustdoc_file("dummy/index.html")
-                    .rustdoc_file("x86_64-pc-windows-msvc/dummy/index.html")
-                    .default_target("x86_64-unknown-linux-gnu");
-
-                for nb in 0..nb_targets - 1 {
-                    rel = rel.add_target(&format!("x86_64-pc-windows-msvc{nb}"));
-                }
-                rel.create().await?;
-
-                let response = env.web_app().await.get("/crate/dummy/0.4.0").await?;
-                assert!(response.status().is_success());
-
-                let nb_li = kuchikiki::parse_html()
-                    .one(response.text().await?)
-                    .select(r#"#platforms li a"#)
-                    .expect("invalid selector")
-                    .count();
-                assert_eq!(nb_li, expected);
-                Ok(())
-            });
-        }
+    fn group_targets_by_os() {
+        let targets: Vec<String> = [
+            "x86_64-unknown-linux-gnu",
+            "i686-pc-windows-msvc",
+            "aarch64-apple-darwin",
+            "aarch64-unknown-linux-musl",
+            "aarch64-linux-android",
+            "x86_64-pc-windows-gnu",
+            "aarch64-apple-ios",
+            "x86_64-unknown-freebsd",
+            "wasm32-unknown-unknown",
+            "wasm32-wasip1",
+            "thumbv7em-none-eabihf",
+        ]
+        .into_iter()
+        .map(str::to_owned)
+        .collect();
+
+        let groups = group_targets(&targets);
+        let expected: Vec<(OsGroup, Vec<String>)> = [
+            (
+                OsGroup::Linux,
+                vec!["x86_64-unknown-linux-gnu", "aarch64-unknown-linux-musl"],
+            ),
+            (
+                OsGroup::Windows,
+                vec!["i686-pc-windows-msvc", "x86_64-pc-windows-gnu"],
+            ),
+            (
+                OsGroup::Apple,
+                vec!["aarch64-apple-darwin", "aarch64-apple-ios"],
+            ),
+            (OsGroup::Android, vec!["aarch64-linux-android"]),
+            (OsGroup::Bsd, vec!["x86_64-unknown-freebsd"]),
+            (
+                OsGroup::Wasm,
+                vec!["wasm32-unknown-unknown", "wasm32-wasip1"],
+            ),
+            (OsGroup::Other, vec!["thumbv7em-none-eabihf"]),
+        ]
+        .into_iter()
+        .map(|(group, targets)| (group, targets.into_iter().map(str::to_owned).collect()))
+        .collect();
+        assert_eq!(groups, expected);
+    }
 
-        // First we check that with 2 releases, the platforms list should be in the HTML.
-        check_count(2, 2);
-        // Then we check the same thing but with number of targets equal
-        // to `DEFAULT_MAX_TARGETS`.
-        check_count(crate::DEFAULT_MAX_TARGETS, 0);
+    #[test]
+    fn group_of_default_target_comes_first() {
+        let targets: Vec<String> = [
+            "aarch64-apple-darwin",
+            "x86_64-unknown-linux-gnu",
+            "x86_64-pc-windows-msvc",
+            "x86_64-apple-darwin",
+        ]
+        .into_iter()
+        .map(str::to_owned)
+        .collect();
+
+        let groups: Vec<OsGroup> = group_targets(&targets)
+            .into_iter()
+            .map(|(group, _)| group)
+            .collect();
+        assert_eq!(groups, [OsGroup::Apple, OsGroup::Linux, OsGroup::Windows]);
     }
 
     #[test]
-    fn latest_url() {
+    fn platform_menu_starts_with_non_linux_default_target() {
         async_wrapper(|env| async move {
             env.fake_release()
                 .await
                 .name("dummy")
                 .version("0.4.0")
                 .rustdoc_file("dummy/index.html")
-                .rustdoc_file("x86_64-pc-windows-msvc/dummy/index.html")
-                .default_target("x86_64-unknown-linux-gnu")
-                .add_target("x86_64-pc-windows-msvc")
+                .default_target("x86_64-pc-windows-msvc")
+                .add_target("x86_64-unknown-linux-gnu")
+                .add_target("i686-pc-windows-msvc")
                 .create()
                 .await?;
-            let web = env.web_app().await;
 
-            let resp = web.get("/crate/dummy/latest").await?;
-            assert!(resp.status().is_success());
-            resp.assert_cache_control(CachePolicy::ForeverInCdn, &env.config());
-            let body = resp.text().await?;
-            assert!(body.contains("<a href=\"/crate/dummy/latest/features\""));
-            assert!(body.contains("<a href=\"/crate/dummy/latest/builds\""));
-            assert!(body.contains("<a href=\"/crate/dummy/latest/source/\""));
-            assert!(body.contains("<a href=\"/crate/dummy/latest\""));
-
-            web.assert_redirect("/crate/dummy/latest/", "/crate/dummy/latest")
-                .await?;
-            web.assert_redirect_cached(
-                "/crate/dummy",
-                "/crate/dummy/latest",
-                CachePolicy::ForeverInCdn,
-                &env.config(),
+            let page = kuchikiki::parse_html().one(
+                env.web_app()
+                    .await
+                    .get("/crate/dummy/0.4.0")
+                    .await?
+                    .text()
+                    .await?,
+            );
+            let items: Vec<String> = page
+                .select("#platforms li")
+                .expect("invalid selector")
+                .map(|el| el.text_contents().trim().to_owned())
+                .collect();
+            assert_eq!(
+                items,
+                [
+                    "Windows",
+                    "x86_64-pc-windows-msvc",
+                    "i686-pc-windows-msvc",
+                    "Linux",
+                    "x86_64-unknown-linux-gnu",
+                ]
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn platform_menu_is_grouped_by_os() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.4.0")
+                .rustdoc_file("dummy/index.html")
+                .default_target("x86_64-unknown-linux-gnu")
+                .add_target("x86_64-pc-windows-msvc")
+                .add_target("i686-unknown-linux-gnu")
+                .create()
+                .await?;
+
+            let page = kuchikiki::parse_html().one(
+                env.web_app()
+                    .await
+                    .get("/crate/dummy/0.4.0")
+                    .await?
+                    .text()
+                    .await?,
+            );
+            let items: Vec<String> = page
+                .select("#platforms li")
+                .expect("invalid selector")
+                .map(|el| el.text_contents().trim().to_owned())
+                .collect();
+            assert_eq!(
+                items,
+                [
+                    "Linux",
+                    "x86_64-unknown-linux-gnu",
+                    "i686-unknown-linux-gnu",
+                    "Windows",
+                    "x86_64-pc-windows-msvc",
+                ]
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn platform_menu_without_headings_for_single_os() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.4.0")
+                .rustdoc_file("dummy/index.html")
+                .default_target("x86_64-unknown-linux-gnu")
+                .add_target("i686-unknown-linux-gnu")
+                .create()
+                .await?;
+
+            let page = kuchikiki::parse_html().one(
+                env.web_app()
+                    .await
+                    .get("/crate/dummy/0.4.0")
+                    .await?
+                    .text()
+                    .await?,
+            );
+            assert!(page
+                .select_first("#platforms li.pure-menu-heading")
+                .is_err());
+            assert_eq!(page.select("#platforms li a").unwrap().count(), 2);
+            Ok(())
+        });
+    }
+
+    // Ensure that if there are more than a given number of targets, it will not generate them in
+    // the HTML directly (they will be loaded by AJAX if the user opens the menu).
+    #[test]
+    #[allow(clippy::assertions_on_constants)]
+    fn platform_menu_ajax() {
+        assert!(crate::DEFAULT_MAX_TARGETS > 2);
+
+        fn check_count(nb_targets: usize, expected: usize) {
+            async_wrapper(|env| async move {
+                let mut rel = env
+                    .fake_release()
+                    .await
+                    .name("dummy")
+                    .version("0.4.0")
+                    .rustdoc_file("dummy/index.html")
+                    .rustdoc_file("x86_64-pc-windows-msvc/dummy/index.html")
+                    .default_target("x86_64-unknown-linux-gnu");
+
+                for nb in 0..nb_targets - 1 {
+                    rel = rel.add_target(&format!("x86_64-pc-windows-msvc{nb}"));
+                }
+                rel.create().await?;
+
+                let response = env.web_app().await.get("/crate/dummy/0.4.0").await?;
+                assert!(response.status().is_success());
+
+                let nb_li = kuchikiki::parse_html()
+                    .one(response.text().await?)
+                    .select(r#"#platforms li a"#)
+                    .expect("invalid selector")
+                    .count();
+                assert_eq!(nb_li, expected);
+                Ok(())
+            });
+        }
+
+        // First we check that with 2 releases, the platforms list should be in the HTML.
+        check_count(2, 2);
+        // Then we check the same thing but with number of targets equal
+        // to `DEFAULT_MAX_TARGETS`.
+        check_count(crate::DEFAULT_MAX_TARGETS, 0);
+    }
+
+    #[test]
+    fn latest_url() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.4.0")
+                .rustdoc_file("dummy/index.html")
+                .rustdoc_file("x86_64-pc-windows-msvc/dummy/index.html")
+                .default_target("x86_64-unknown-linux-gnu")
+                .add_target("x86_64-pc-windows-msvc")
+                .create()
+                .await?;
+            let web = env.web_app().await;
+
+            let resp = web.get("/crate/dummy/latest").await?;
+            assert!(resp.status().is_success());
+            resp.assert_cache_control(CachePolicy::ForeverInCdn, &env.config());
+            let body = resp.text().await?;
+            assert!(body.contains("<a href=\"/crate/dummy/latest/features\""));
+            assert!(body.contains("<a href=\"/crate/dummy/latest/builds\""));
+            assert!(body.contains("<a href=\"/crate/dummy/latest/source/\""));
+            assert!(body.contains("<a href=\"/crate/dummy/latest\""));
+
+            web.assert_redirect("/crate/dummy/latest/", "/crate/dummy/latest")
+                .await?;
+            web.assert_redirect_cached(
+                "/crate/dummy",
+                "/crate/dummy/latest",
+                CachePolicy::ForeverInCdn,
+                &env.config(),
             )
             .await?;
 
@@ -2187,6 +3230,66 @@ mod tests {
         });
     }
 
+    #[test]
+    fn readme_page() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.1.0")
+                .readme("# Dummy\n\n## Usage\n\nsome readme content")
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            let response = web.get("/crate/dummy/0.1.0/readme").await?;
+            assert!(response.status().is_success());
+            response
+                .assert_cache_control(CachePolicy::ForeverInCdnAndStaleInBrowser, &env.config());
+
+            let page = kuchikiki::parse_html().one(response.text().await?);
+            let main = page.select_first("#main").expect("missing main section");
+            assert_eq!(
+                main.as_node().select_first("h1").unwrap().text_contents(),
+                "Dummy"
+            );
+            assert_eq!(
+                main.as_node().select_first("h2").unwrap().text_contents(),
+                "Usage"
+            );
+            assert!(main.text_contents().contains("some readme content"));
+
+            web.assert_redirect_cached(
+                "/crate/dummy/0.1/readme",
+                "/crate/dummy/0.1.0/readme",
+                CachePolicy::ForeverInCdn,
+                &env.config(),
+            )
+            .await?;
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn readme_page_without_readme() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.1.0")
+                .create()
+                .await?;
+
+            env.web_app()
+                .await
+                .assert_not_found("/crate/dummy/0.1.0/readme")
+                .await?;
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn test_crate_name_with_other_uri_chars() {
         async_wrapper(|env| async move {
@@ -2308,6 +3411,38 @@ mod tests {
         })
     }
 
+    #[test]
+    fn noindex_in_progress_release() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.1.0")
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.2.0")
+                .builds(vec![
+                    FakeBuild::default().build_status(BuildStatus::InProgress)
+                ])
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+
+            let response = web.get("/crate/dummy/0.2.0").await?;
+            assert!(response.status().is_success());
+            assert_eq!(response.headers().get("x-robots-tag").unwrap(), "noindex");
+
+            let response = web.get("/crate/dummy/0.1.0").await?;
+            assert!(response.status().is_success());
+            assert!(response.headers().get("x-robots-tag").is_none());
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_sizes_display() {
         async_wrapper(|env| async move {
@@ -2341,4 +3476,379 @@ mod tests {
             Ok(())
         });
     }
+
+    fn targets(targets: &[&str]) -> Vec<String> {
+        targets.iter().map(|&target| target.to_owned()).collect()
+    }
+
+    #[test_case(&["a", "b"], &["a", "b", "c"], &["c"], &[]; "added")]
+    #[test_case(&["a", "b", "c"], &["a", "b"], &[], &["c"]; "removed")]
+    #[test_case(&["a", "b"], &["b", "a"], &[], &[]; "unchanged")]
+    fn test_target_diff(prev: &[&str], curr: &[&str], added: &[&str], removed: &[&str]) {
+        let diff = target_diff(&targets(prev), &targets(curr));
+        assert_eq!(
+            diff,
+            TargetDiff {
+                added: targets(added),
+                removed: targets(removed),
+            }
+        );
+        assert_eq!(diff.is_empty(), added.is_empty() && removed.is_empty());
+    }
+
+    #[test]
+    fn crate_page_shows_target_diff() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .add_target("x86_64-unknown-linux-gnu")
+                .add_target("x86_64-pc-windows-msvc")
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.2.0")
+                .add_target("x86_64-unknown-linux-gnu")
+                .add_target("aarch64-apple-darwin")
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+
+            let page =
+                kuchikiki::parse_html().one(web.get("/crate/foo/0.2.0").await?.text().await?);
+            let note = page.select_first("#target-diff").unwrap().text_contents();
+            assert!(note.contains("changed since foo-0.1.0"));
+            assert!(note.contains("Added: aarch64-apple-darwin."));
+            assert!(note.contains("Removed: x86_64-pc-windows-msvc."));
+
+            // nothing to compare with for the first release
+            let page =
+                kuchikiki::parse_html().one(web.get("/crate/foo/0.1.0").await?.text().await?);
+            assert!(page.select_first("#target-diff").is_err());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn crate_page_hides_unchanged_targets() {
+        async_wrapper(|env| async move {
+            for version in ["0.1.0", "0.2.0"] {
+                env.fake_release()
+                    .await
+                    .name("foo")
+                    .version(version)
+                    .add_target("x86_64-unknown-linux-gnu")
+                    .create()
+                    .await?;
+            }
+
+            let page = kuchikiki::parse_html().one(
+                env.web_app()
+                    .await
+                    .get("/crate/foo/0.2.0")
+                    .await?
+                    .text()
+                    .await?,
+            );
+            assert!(page.select_first("#target-diff").is_err());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn crate_page_shows_keywords_and_categories() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .registry_keyword("parser")
+                .registry_category("development-tools::testing")
+                .create()
+                .await?;
+
+            let page = kuchikiki::parse_html().one(
+                env.web_app()
+                    .await
+                    .get("/crate/foo/0.1.0")
+                    .await?
+                    .text()
+                    .await?,
+            );
+
+            let keyword = page.select_first("#crate-keywords a").unwrap();
+            assert_eq!(keyword.text_contents(), "parser");
+            assert_eq!(
+                keyword.attributes.borrow().get("href").unwrap(),
+                "https://crates.io/keywords/parser"
+            );
+
+            let category = page.select_first("#crate-categories a").unwrap();
+            assert_eq!(category.text_contents(), "development-tools::testing");
+            assert_eq!(
+                category.attributes.borrow().get("href").unwrap(),
+                "https://crates.io/categories/development-tools%3A%3Atesting"
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn crate_page_hides_empty_keywords_and_categories() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .create()
+                .await?;
+
+            let page = kuchikiki::parse_html().one(
+                env.web_app()
+                    .await
+                    .get("/crate/foo/0.1.0")
+                    .await?
+                    .text()
+                    .await?,
+            );
+            assert!(page.select_first("#crate-keywords").is_err());
+            assert!(page.select_first("#crate-categories").is_err());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn crate_page_falls_back_to_readme_excerpt() {
+        async_wrapper(|env| async move {
+            let readme = "# foo\n\nFoo parses *all* the things.\n\nMore details.";
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .description("")
+                .readme(readme)
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("bar")
+                .version("0.1.0")
+                .description("A crate with a description")
+                .readme(readme)
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+
+            let page =
+                kuchikiki::parse_html().one(web.get("/crate/foo/0.1.0").await?.text().await?);
+            let description = page
+                .select_first(".description")
+                .expect("missing description");
+            assert_eq!(
+                description.text_contents().trim(),
+                "Foo parses all the things."
+            );
+
+            let page =
+                kuchikiki::parse_html().one(web.get("/crate/bar/0.1.0").await?.text().await?);
+            let description = page
+                .select_first(".description")
+                .expect("missing description");
+            assert_eq!(
+                description.text_contents().trim(),
+                "A crate with a description"
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn crate_and_doc_pages_show_build_time() {
+        async_wrapper(|env| async move {
+            let release_id = env
+                .fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .create()
+                .await?;
+
+            let build_finished: DateTime<Utc> = sqlx::query_scalar!(
+                "SELECT build_finished FROM builds WHERE rid = $1",
+                release_id.0
+            )
+            .fetch_one(&mut *env.async_db().await.async_conn().await)
+            .await?
+            .expect("finished build");
+            let expected = build_finished.format("%FT%TZ").to_string();
+            // these pages are cached in the CDN, so the date has to be absolute
+            let expected_date = build_finished.format("%F").to_string();
+
+            let web = env.web_app().await;
+            for (path, selector) in [
+                ("/crate/foo/0.1.0", "#docs-built"),
+                ("/foo/0.1.0/foo/", "#topbar-docs-built"),
+            ] {
+                let page = kuchikiki::parse_html().one(web.get(path).await?.text().await?);
+                assert_eq!(page.select(selector).unwrap().count(), 1);
+                let built = page.select_first(selector).expect("missing build time");
+                let titled = if built.attributes.borrow().contains("title") {
+                    built.clone()
+                } else {
+                    built
+                        .as_node()
+                        .select_first("[title]")
+                        .expect("missing title")
+                };
+                assert_eq!(
+                    titled.attributes.borrow().get("title"),
+                    Some(expected.as_str())
+                );
+                assert!(built
+                    .text_contents()
+                    .contains(&format!("Built on {expected_date}")));
+                assert!(built
+                    .text_contents()
+                    .contains("with rustc 2.0.0-nightly (000000000 1970-01-01)"));
+            }
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn crate_page_caps_inline_owners() {
+        async_wrapper(|env| async move {
+            let mut release = env.fake_release().await.name("foo").version("0.1.0");
+            for i in 0..25 {
+                release = release.add_owner(CrateOwner {
+                    login: format!("owner{i:02}"),
+                    avatar: format!("https://example.org/owner{i:02}"),
+                    kind: OwnerKind::User,
+                });
+            }
+            release.create().await?;
+
+            let web = env.web_app().await;
+
+            let page =
+                kuchikiki::parse_html().one(web.get("/crate/foo/0.1.0").await?.text().await?);
+            let inline: Vec<_> = page
+                .select("img.owner")
+                .unwrap()
+                .map(|img| img.attributes.borrow().get("alt").unwrap().to_owned())
+                .collect();
+            assert_eq!(
+                inline,
+                (0..20).map(|i| format!("owner{i:02}")).collect::<Vec<_>>()
+            );
+            let link = page
+                .select_first("#all-owners")
+                .expect("missing owners link");
+            assert_eq!(
+                link.attributes.borrow().get("href"),
+                Some("/crate/foo/owners")
+            );
+            assert!(link.text_contents().contains("Show all 25 owners"));
+
+            let page =
+                kuchikiki::parse_html().one(web.get("/crate/foo/owners").await?.text().await?);
+            assert_eq!(page.select("#owners img.owner").unwrap().count(), 25);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn detect_cfg_docsrs_in_cargo_args() {
+        let args = |rustdocflags: &str| {
+            vec![
+                "rustdoc".to_string(),
+                "--lib".to_string(),
+                "--config".to_string(),
+                format!("build.rustdocflags={rustdocflags}"),
+            ]
+        };
+
+        assert!(built_with_cfg_docsrs(&args(
+            r#"["--cfg", "docsrs", "--cap-lints", "warn"]"#
+        )));
+        assert!(!built_with_cfg_docsrs(&args(r#"["--cap-lints", "warn"]"#)));
+        assert!(!built_with_cfg_docsrs(&args(r#"["--cfg", "other"]"#)));
+        assert!(!built_with_cfg_docsrs(&args("not toml")));
+        assert!(!built_with_cfg_docsrs(&[]));
+    }
+
+    #[test]
+    fn crate_page_shows_cfg_docsrs() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .builds(vec![FakeBuild::default().cargo_args(
+                    docsrs_metadata::Metadata::default().cargo_args(&[], &[]),
+                )])
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("bar")
+                .version("0.1.0")
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            let page =
+                kuchikiki::parse_html().one(web.get("/crate/foo/0.1.0").await?.text().await?);
+            let note = page.select_first("#cfg-docsrs").expect("missing cfg note");
+            assert!(note.text_contents().contains("--cfg docsrs"));
+
+            let page =
+                kuchikiki::parse_html().one(web.get("/crate/bar/0.1.0").await?.text().await?);
+            assert!(page.select_first("#cfg-docsrs").is_err());
+
+            Ok(())
+        });
+    }
+
+    #[test_case("/crate/old-crate/latest")]
+    #[test_case("/crate/old-crate/1.0.0")]
+    #[test_case("/crate/old-crate/1.0.0/features")]
+    #[test_case("/crate/old-crate/latest/builds")]
+    #[test_case("/crate/old-crate/latest/source/")]
+    fn crate_pages_redirect_from_renamed_crate(path: &str) {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("new-crate")
+                .version("1.0.0")
+                .create()
+                .await?;
+            let mut conn = env.async_db().await.async_conn().await;
+            crate::db::crate_redirects::add_redirect(&mut conn, "old-crate", "new-crate").await?;
+
+            let web = env.web_app().await;
+            web.assert_redirect_cached(
+                path,
+                "/crate/new-crate/latest",
+                CachePolicy::ShortInCdnAndBrowser,
+                &env.config(),
+            )
+            .await?;
+            assert_eq!(web.get(path).await?.status(), StatusCode::MOVED_PERMANENTLY);
+
+            Ok(())
+        });
+    }
 }
@@ -0,0 +1,269 @@
+use crate::{
+    db::types::{BuildStatus, Feature as DbFeature},
+    web::{
+        cache::CachePolicy,
+        error::{AxumNope, AxumResult},
+        extractors::{DbConnection, Path},
+        match_version, MatchedRelease, MetaData, ReqVersion,
+    },
+    InstanceMetrics,
+};
+use anyhow::Result;
+use axum::{extract::Extension, response::IntoResponse, Json};
+use serde::Serialize;
+use std::{collections::BTreeSet, sync::Arc};
+
+/// Items only present in one of the two compared releases.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct SetDiff {
+    added: Vec<String>,
+    removed: Vec<String>,
+}
+
+impl SetDiff {
+    fn new(from: &BTreeSet<String>, to: &BTreeSet<String>) -> Self {
+        Self {
+            added: to.difference(from).cloned().collect(),
+            removed: from.difference(to).cloned().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Change<T> {
+    from: T,
+    to: T,
+    changed: bool,
+}
+
+impl<T: PartialEq> Change<T> {
+    fn new(from: T, to: T) -> Self {
+        let changed = from != to;
+        Self { from, to, changed }
+    }
+}
+
+/// Metadata-level differences between two releases of the same crate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct MetadataDiff {
+    name: String,
+    from_version: String,
+    to_version: String,
+    targets: SetDiff,
+    features: SetDiff,
+    build_status: Change<BuildStatus>,
+    rustc_version: Change<Option<String>>,
+}
+
+/// The parts of a release the diff is computed from.
+struct DiffSide {
+    build_status: BuildStatus,
+    metadata: MetaData,
+    features: BTreeSet<String>,
+}
+
+impl DiffSide {
+    async fn load(conn: &mut sqlx::PgConnection, release: MatchedRelease) -> Result<Self> {
+        let features = sqlx::query_scalar!(
+            r#"SELECT features as "features?: Vec<DbFeature>"
+            FROM releases
+            WHERE id = $1"#,
+            release.id().0,
+        )
+        .fetch_one(&mut *conn)
+        .await?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|feature| feature.name)
+        .collect();
+
+        let metadata = MetaData::from_crate(conn, &release.name, release.version(), None).await?;
+
+        Ok(Self {
+            build_status: release.build_status(),
+            metadata,
+            features,
+        })
+    }
+
+    fn targets(&self) -> BTreeSet<String> {
+        self.metadata
+            .doc_targets
+            .iter()
+            .flatten()
+            .cloned()
+            .collect()
+    }
+}
+
+pub(crate) async fn metadata_diff_handler(
+    Path((name, from_req_version, to_req_version)): Path<(String, ReqVersion, ReqVersion)>,
+    mut conn: DbConnection,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
+) -> AxumResult<impl IntoResponse> {
+    let from = match_version(&mut conn, &metrics, &name, &from_req_version)
+        .await?
+        .assume_exact_name()?
+        .into_canonical_req_version();
+    let to = match_version(&mut conn, &metrics, &name, &to_req_version)
+        .await?
+        .assume_exact_name()?
+        .into_canonical_req_version();
+
+    if from.req_version != from_req_version || to.req_version != to_req_version {
+        return Err(AxumNope::Redirect(
+            format!(
+                "/crate/{name}/diff/{}/{}/metadata.json",
+                from.req_version, to.req_version
+            ),
+            CachePolicy::ForeverInCdn,
+        ));
+    }
+
+    let from = DiffSide::load(&mut conn, from).await?;
+    let to = DiffSide::load(&mut conn, to).await?;
+
+    Ok((
+        Extension(CachePolicy::NoStoreMustRevalidate),
+        Json(MetadataDiff {
+            name,
+            from_version: from.metadata.version.to_string(),
+            to_version: to.metadata.version.to_string(),
+            targets: SetDiff::new(&from.targets(), &to.targets()),
+            features: SetDiff::new(&from.features, &to.features),
+            build_status: Change::new(from.build_status, to.build_status),
+            rustc_version: Change::new(from.metadata.rustc_version, to.metadata.rustc_version),
+        }),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::{async_wrapper, AxumResponseTestExt, AxumRouterTestExt, FakeBuild};
+    use reqwest::StatusCode;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    #[test]
+    fn diff_features_and_targets() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .default_target("x86_64-unknown-linux-gnu")
+                .add_target("i686-pc-windows-msvc")
+                .features(HashMap::from([
+                    ("default".into(), vec!["a".into()]),
+                    ("a".into(), vec![]),
+                    ("b".into(), vec![]),
+                ]))
+                .builds(vec![
+                    FakeBuild::default().rustc_version("rustc 1.84.0 (9fc6b4312 2025-01-07)")
+                ])
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.2.0")
+                .default_target("x86_64-unknown-linux-gnu")
+                .add_target("aarch64-apple-darwin")
+                .features(HashMap::from([
+                    ("default".into(), vec!["a".into()]),
+                    ("a".into(), vec![]),
+                    ("c".into(), vec![]),
+                ]))
+                .builds(vec![FakeBuild::default()
+                    .rustc_version("rustc 1.85.0 (4d91de4e4 2025-02-17)")
+                    .successful(false)])
+                .create()
+                .await?;
+
+            let response = env
+                .web_app()
+                .await
+                .get("/crate/foo/diff/0.1.0/0.2.0/metadata.json")
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["access-control-allow-origin"], "*");
+            let value: Value = serde_json::from_str(&response.text().await?)?;
+
+            assert_eq!(
+                value,
+                json!({
+                    "name": "foo",
+                    "from_version": "0.1.0",
+                    "to_version": "0.2.0",
+                    "targets": {
+                        "added": ["aarch64-apple-darwin"],
+                        "removed": ["i686-pc-windows-msvc"],
+                    },
+                    "features": {
+                        "added": ["c"],
+                        "removed": ["b"],
+                    },
+                    "build_status": {
+                        "from": "success",
+                        "to": "failure",
+                        "changed": true,
+                    },
+                    "rustc_version": {
+                        "from": "rustc 1.84.0 (9fc6b4312 2025-01-07)",
+                        "to": "rustc 1.85.0 (4d91de4e4 2025-02-17)",
+                        "changed": true,
+                    },
+                })
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn diff_redirects_semver_requests() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.2.0")
+                .create()
+                .await?;
+
+            env.web_app()
+                .await
+                .assert_redirect(
+                    "/crate/foo/diff/0.1/0.2/metadata.json",
+                    "/crate/foo/diff/0.1.0/0.2.0/metadata.json",
+                )
+                .await?;
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn diff_unknown_version() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .create()
+                .await?;
+
+            let response = env
+                .web_app()
+                .await
+                .get("/crate/foo/diff/0.1.0/0.3.0/metadata.json")
+                .await?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            Ok(())
+        })
+    }
+}
@@ -1,18 +1,33 @@
 use crate::{
     db::PoolError,
     storage::PathNotFoundError,
-    web::{cache::CachePolicy, encode_url_path, releases::Search},
+    web::{
+        cache::CachePolicy, encode_url_path, page::web_page::DelayedTemplateRender,
+        releases::Search,
+    },
 };
 use anyhow::anyhow;
 use axum::{
-    http::StatusCode,
+    body::Body,
+    extract::Request as AxumHttpRequest,
+    http::{
+        header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, RETRY_AFTER},
+        HeaderValue, StatusCode,
+    },
+    middleware::Next,
     response::{IntoResponse, Response as AxumResponse},
     Json,
 };
+use semver::Version;
 use std::borrow::Cow;
 use tracing::error;
+use url::form_urlencoded;
 
-use super::AxumErrorPage;
+use super::{AxumEmbeddedErrorPage, AxumErrorPage};
+
+const EMBED_HEADER: &str = "x-docsrs-embed";
+/// `Retry-After` seconds sent with `503 Service Unavailable` responses.
+const SERVICE_UNAVAILABLE_RETRY_AFTER: &str = "5";
 
 #[derive(Debug, thiserror::Error)]
 pub enum AxumNope {
@@ -26,6 +41,15 @@ pub enum AxumNope {
     OwnerNotFound,
     #[error("Requested crate does not have specified version")]
     VersionNotFound,
+    /// like `VersionNotFound`, but with existing versions of the crate
+    /// the user might have meant instead.
+    #[error("Requested crate does not have specified version")]
+    VersionNotFoundWithSuggestions {
+        name: String,
+        /// the version as it was written in the request, if the handler kept it.
+        requested: Option<String>,
+        suggestions: Vec<Version>,
+    },
     #[error("Search yielded no results")]
     NoResults,
     #[error("Unauthorized: {0}")]
@@ -34,8 +58,19 @@ pub enum AxumNope {
     InternalError(anyhow::Error),
     #[error("bad request")]
     BadRequest(anyhow::Error),
+    #[error("too many requests")]
+    TooManyRequests(anyhow::Error),
+    /// temporary overload, like no free database connection
+    #[error("service unavailable")]
+    ServiceUnavailable(anyhow::Error),
     #[error("redirect")]
     Redirect(String, CachePolicy),
+    /// like `Redirect`, but with a permanent status code.
+    #[error("permanent redirect")]
+    PermanentRedirect(String, CachePolicy),
+    /// the crate doesn't exist, but there is a redirect to a renamed crate.
+    #[error("Requested crate was renamed to {0}")]
+    CrateRenamed(String),
 }
 
 // FUTURE: Ideally, the split between the 3 kinds of responses would
@@ -44,6 +79,21 @@ pub enum AxumNope {
 // throughout instead of having the potential for a runtime error.
 
 impl AxumNope {
+    /// keep the version the user wrote in the request on a version-not-found
+    /// error, so the error page can show it instead of the parsed requirement.
+    pub(crate) fn with_requested_version(self, raw: &str) -> Self {
+        match self {
+            AxumNope::VersionNotFoundWithSuggestions {
+                name, suggestions, ..
+            } => AxumNope::VersionNotFoundWithSuggestions {
+                name,
+                requested: Some(raw.to_owned()),
+                suggestions,
+            },
+            other => other,
+        }
+    }
+
     fn into_error_info(self) -> ErrorInfo {
         match self {
             AxumNope::ResourceNotFound => {
@@ -73,7 +123,15 @@ impl AxumNope {
                 message: "no such owner".into(),
                 status: StatusCode::NOT_FOUND,
             },
-            AxumNope::VersionNotFound => {
+            AxumNope::VersionNotFoundWithSuggestions {
+                requested: Some(requested),
+                ..
+            } => ErrorInfo {
+                title: "The requested version does not exist",
+                message: format!("no version matching `{requested}` for this crate").into(),
+                status: StatusCode::NOT_FOUND,
+            },
+            AxumNope::VersionNotFound | AxumNope::VersionNotFoundWithSuggestions { .. } => {
                 // user tried to navigate to a crate with a version that does not exist
                 // TODO: Display the attempted crate and version
                 ErrorInfo {
@@ -91,6 +149,16 @@ impl AxumNope {
                 message: Cow::Owned(source.to_string()),
                 status: StatusCode::BAD_REQUEST,
             },
+            AxumNope::TooManyRequests(source) => ErrorInfo {
+                title: "Too many requests",
+                message: Cow::Owned(source.to_string()),
+                status: StatusCode::TOO_MANY_REQUESTS,
+            },
+            AxumNope::ServiceUnavailable(source) => ErrorInfo {
+                title: "Service Unavailable",
+                message: Cow::Owned(source.to_string()),
+                status: StatusCode::SERVICE_UNAVAILABLE,
+            },
             AxumNope::Unauthorized(what) => ErrorInfo {
                 title: "Unauthorized",
                 message: what.into(),
@@ -104,7 +172,9 @@ impl AxumNope {
                     status: StatusCode::INTERNAL_SERVER_ERROR,
                 }
             }
-            AxumNope::Redirect(_target, _cache_policy) => unreachable!(),
+            AxumNope::Redirect(_target, _cache_policy)
+            | AxumNope::PermanentRedirect(_target, _cache_policy)
+            | AxumNope::CrateRenamed(_target) => unreachable!(),
         }
     }
 }
@@ -125,6 +195,25 @@ fn redirect_with_policy(target: String, cache_policy: CachePolicy) -> AxumRespon
     }
 }
 
+fn permanent_redirect_with_policy(target: String, cache_policy: CachePolicy) -> AxumResponse {
+    let mut response = redirect_with_policy(target, cache_policy);
+    if response.status() == StatusCode::FOUND {
+        *response.status_mut() = StatusCode::MOVED_PERMANENTLY;
+    }
+    response
+}
+
+/// redirect to the crate page of the crate `to_name` a requested crate was renamed to.
+///
+/// Redirects can be removed again without any build that would purge the CDN,
+/// so they are only cached for a short time.
+fn renamed_crate_redirect(to_name: &str) -> AxumResponse {
+    permanent_redirect_with_policy(
+        format!("/crate/{to_name}/latest"),
+        CachePolicy::ShortInCdnAndBrowser,
+    )
+}
+
 impl IntoResponse for AxumNope {
     fn into_response(self) -> AxumResponse {
         match self {
@@ -138,24 +227,129 @@ impl IntoResponse for AxumNope {
                 .into_response()
             }
             AxumNope::Redirect(target, cache_policy) => redirect_with_policy(target, cache_policy),
+            AxumNope::PermanentRedirect(target, cache_policy) => {
+                permanent_redirect_with_policy(target, cache_policy)
+            }
+            AxumNope::CrateRenamed(to_name) => renamed_crate_redirect(&to_name),
             _ => {
+                let suggestions = match &self {
+                    AxumNope::VersionNotFoundWithSuggestions {
+                        name, suggestions, ..
+                    } => suggestions
+                        .iter()
+                        .map(|version| (version.to_string(), format!("/crate/{name}/{version}")))
+                        .collect(),
+                    _ => Vec::new(),
+                };
                 let ErrorInfo {
                     title,
                     message,
                     status,
                 } = self.into_error_info();
-                AxumErrorPage {
+                let page = AxumErrorPage {
                     title,
                     message,
                     status,
+                    suggestions,
                     csp_nonce: String::new(),
+                };
+                let mut response = page.clone().into_response();
+                if status == StatusCode::SERVICE_UNAVAILABLE {
+                    response.headers_mut().insert(
+                        RETRY_AFTER,
+                        HeaderValue::from_static(SERVICE_UNAVAILABLE_RETRY_AFTER),
+                    );
                 }
-                .into_response()
+                // keep the page data around so `embed_error_middleware` can
+                // swap the template.
+                response.extensions_mut().insert(page);
+                response
             }
         }
     }
 }
 
+fn is_embed_request(req: &AxumHttpRequest) -> bool {
+    req.headers()
+        .get(EMBED_HEADER)
+        .is_some_and(|value| value == "1")
+        || req.uri().query().is_some_and(|query| {
+            form_urlencoded::parse(query.as_bytes())
+                .any(|(key, value)| key == "embed" && value == "1")
+        })
+}
+
+/// Render error pages without the navigation when the request
+/// comes from an embedded context, signaled by `?embed=1` or the
+/// `X-Docsrs-Embed: 1` header.
+pub(crate) async fn embed_error_middleware(req: AxumHttpRequest, next: Next) -> AxumResponse {
+    let embed = is_embed_request(&req);
+
+    let mut response = next.run(req).await;
+
+    if embed {
+        if let Some(page) = response.extensions_mut().remove::<AxumErrorPage>() {
+            return AxumEmbeddedErrorPage::from(page).into_response();
+        }
+    }
+    response
+}
+
+fn accepts_json(req: &AxumHttpRequest) -> bool {
+    req.headers()
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|media_range| media_range.split(';').next())
+        .any(|media_type| media_type.trim() == mime::APPLICATION_JSON.as_ref())
+}
+
+/// Replace the HTML body of an error page with the JSON error body,
+/// keeping the status and headers.
+fn error_page_into_json(mut response: AxumResponse) -> AxumResponse {
+    let Some(page) = response.extensions_mut().remove::<AxumErrorPage>() else {
+        return response;
+    };
+
+    let (mut parts, _) = response.into_parts();
+    parts.extensions.remove::<DelayedTemplateRender>();
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(mime::APPLICATION_JSON.as_ref()),
+    );
+    let body = json_error_body(page.title, &page.message, page.status);
+    AxumResponse::from_parts(parts, Body::from(body.to_string()))
+}
+
+/// Render error pages as JSON when the client asks for it with
+/// `Accept: application/json`.
+pub(crate) async fn json_error_middleware(req: AxumHttpRequest, next: Next) -> AxumResponse {
+    let json = accepts_json(&req);
+
+    let response = next.run(req).await;
+
+    if json {
+        error_page_into_json(response)
+    } else {
+        response
+    }
+}
+
+/// Always render error pages as JSON, for the JSON API routes.
+pub(crate) async fn json_api_error_middleware(req: AxumHttpRequest, next: Next) -> AxumResponse {
+    error_page_into_json(next.run(req).await)
+}
+
+fn json_error_body(title: &str, message: &str, status: StatusCode) -> serde_json::Value {
+    serde_json::json!({
+        "title": title,
+        "message": message,
+        "status": status.as_u16(),
+    })
+}
+
 /// `AxumNope` but generating error responses in JSON (for API).
 pub(crate) struct JsonAxumNope(pub AxumNope);
 
@@ -168,20 +362,25 @@ impl IntoResponse for JsonAxumNope {
                 StatusCode::NOT_FOUND.into_response()
             }
             AxumNope::Redirect(target, cache_policy) => redirect_with_policy(target, cache_policy),
+            AxumNope::PermanentRedirect(target, cache_policy) => {
+                permanent_redirect_with_policy(target, cache_policy)
+            }
+            AxumNope::CrateRenamed(to_name) => renamed_crate_redirect(&to_name),
             _ => {
                 let ErrorInfo {
                     title,
                     message,
                     status,
                 } = self.0.into_error_info();
-                (
-                    status,
-                    Json(serde_json::json!({
-                        "title": title,
-                        "message": message,
-                    })),
-                )
-                    .into_response()
+                let mut response =
+                    (status, Json(json_error_body(title, &message, status))).into_response();
+                if status == StatusCode::SERVICE_UNAVAILABLE {
+                    response.headers_mut().insert(
+                        RETRY_AFTER,
+                        HeaderValue::from_static(SERVICE_UNAVAILABLE_RETRY_AFTER),
+                    );
+                }
+                response
             }
         }
     }
@@ -207,7 +406,10 @@ impl From<sqlx::Error> for AxumNope {
 
 impl From<PoolError> for AxumNope {
     fn from(err: PoolError) -> Self {
-        AxumNope::InternalError(anyhow!(err))
+        match err {
+            PoolError::AcquireTimeout => AxumNope::ServiceUnavailable(anyhow!(err)),
+            err => AxumNope::InternalError(anyhow!(err)),
+        }
     }
 }
 
@@ -219,7 +421,17 @@ mod tests {
     use super::{AxumNope, IntoResponse};
     use crate::test::{async_wrapper, AxumResponseTestExt, AxumRouterTestExt};
     use crate::web::cache::CachePolicy;
+    use axum::{
+        body::Body,
+        http::{
+            header::{ACCEPT, CONTENT_TYPE, RETRY_AFTER},
+            Request, StatusCode,
+        },
+    };
     use kuchikiki::traits::TendrilSink;
+    use std::time::Duration;
+    use test_case::test_case;
+    use tower::ServiceExt as _;
 
     #[test]
     fn test_redirect_error_encodes_url_path() {
@@ -231,6 +443,23 @@ mod tests {
         assert_eq!(response.headers().get("Location").unwrap(), "/something%3E");
     }
 
+    #[test]
+    fn exhausted_pool_is_service_unavailable() {
+        async_wrapper(|env| async move {
+            env.override_config(|config| {
+                config.max_pool_size = 1;
+                config.min_pool_idle = 0;
+                config.database_acquire_timeout = Duration::from_millis(100);
+            });
+            let _held = env.async_db().await.async_conn().await;
+
+            let response = env.web_app().await.get("/releases").await?;
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+            assert_eq!(response.headers()[RETRY_AFTER], "5");
+            Ok(())
+        });
+    }
+
     #[test]
     fn check_404_page_content_crate() {
         async_wrapper(|env| async move {
@@ -256,6 +485,38 @@ mod tests {
         });
     }
 
+    #[test_case("/crate-which-doesnt-exist", None, false; "default")]
+    #[test_case("/crate-which-doesnt-exist?embed=1", None, true; "query")]
+    #[test_case("/crate-which-doesnt-exist?embed=0", None, false; "query disabled")]
+    #[test_case("/crate-which-doesnt-exist", Some("1"), true; "header")]
+    fn check_404_page_embed_mode(path: &str, header: Option<&str>, embed: bool) {
+        async_wrapper(|env| async move {
+            let mut request = Request::builder().uri(path);
+            if let Some(value) = header {
+                request = request.header("X-Docsrs-Embed", value);
+            }
+            let response = env
+                .web_app()
+                .await
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            let page = kuchikiki::parse_html().one(response.text().await?);
+            assert_eq!(
+                page.select_first("#crate-title").unwrap().text_contents(),
+                "The requested crate does not exist",
+            );
+            assert_eq!(page.select(".nav-container").unwrap().count() == 0, embed);
+            assert_eq!(
+                page.select("body.embedded-error").unwrap().count() == 1,
+                embed
+            );
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn check_404_page_content_resource() {
         async_wrapper(|env| async move {
@@ -354,4 +615,155 @@ mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn check_404_page_suggests_nearest_versions() {
+        async_wrapper(|env| async move {
+            for version in ["1.4.0", "2.0.0"] {
+                env.fake_release()
+                    .await
+                    .name("dummy")
+                    .version(version)
+                    .create()
+                    .await?;
+            }
+
+            let response = env.web_app().await.get("/crate/dummy/1.5.0").await?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            let page = kuchikiki::parse_html().one(response.text().await?);
+            let links: Vec<_> = page
+                .select("#error-suggestions a")
+                .unwrap()
+                .map(|a| a.attributes.borrow().get("href").unwrap().to_owned())
+                .collect();
+            assert_eq!(links, ["/crate/dummy/1.4.0", "/crate/dummy/2.0.0"]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn check_404_page_suggests_only_existing_side() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("1.0.0")
+                .create()
+                .await?;
+
+            let response = env.web_app().await.get("/crate/dummy/0.5.0").await?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            let page = kuchikiki::parse_html().one(response.text().await?);
+            let links: Vec<_> = page
+                .select("#error-suggestions a")
+                .unwrap()
+                .map(|a| a.attributes.borrow().get("href").unwrap().to_owned())
+                .collect();
+            assert_eq!(links, ["/crate/dummy/1.0.0"]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn check_404_page_shows_requested_version_as_written() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("1.0.0")
+                .yanked(true)
+                .create()
+                .await?;
+
+            let response = env.web_app().await.get("/crate/dummy/newest").await?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            assert!(response
+                .text()
+                .await?
+                .contains("no version matching `newest` for this crate"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn json_route_errors_are_json() {
+        async_wrapper(|env| async move {
+            let response = env
+                .web_app()
+                .await
+                .get("/crate/nope/0.1.0/builds.json")
+                .await?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            assert_eq!(
+                response.headers().get(CONTENT_TYPE).unwrap(),
+                mime::APPLICATION_JSON.as_ref()
+            );
+            response.assert_cache_control(CachePolicy::NoCaching, &env.config());
+            assert_eq!(
+                response.json::<serde_json::Value>().await?,
+                serde_json::json!({
+                    "title": "The requested crate does not exist",
+                    "message": "no such crate",
+                    "status": 404,
+                })
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn html_route_errors_are_html() {
+        async_wrapper(|env| async move {
+            let response = env.web_app().await.get("/crate/nope/0.1.0").await?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            assert!(response
+                .headers()
+                .get(CONTENT_TYPE)
+                .unwrap()
+                .to_str()?
+                .starts_with("text/html"));
+
+            let page = kuchikiki::parse_html().one(response.text().await?);
+            assert_eq!(
+                page.select("#crate-title")
+                    .unwrap()
+                    .next()
+                    .unwrap()
+                    .text_contents(),
+                "The requested crate does not exist",
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn html_route_errors_are_json_when_requested() {
+        async_wrapper(|env| async move {
+            let response = env
+                .web_app()
+                .await
+                .oneshot(
+                    Request::builder()
+                        .uri("/crate/nope/0.1.0")
+                        .header(ACCEPT, "application/json; charset=utf-8, */*;q=0.8")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            let json: serde_json::Value = response.json().await?;
+            assert_eq!(json["status"], 404);
+            assert_eq!(json["message"], "no such crate");
+
+            Ok(())
+        });
+    }
 }
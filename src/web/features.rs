@@ -11,12 +11,16 @@ use crate::{
         page::templates::{RenderRegular, RenderSolid},
         MetaData, ReqVersion,
     },
+    Config, InstanceMetrics,
 };
 use anyhow::anyhow;
-use axum::response::IntoResponse;
+use axum::{response::IntoResponse, Extension};
 use rinja::Template;
 use serde_json::Value;
-use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    sync::Arc,
+};
 
 const DEFAULT_NAME: &str = "default";
 
@@ -26,6 +30,15 @@ struct Feature {
     subfeatures: BTreeMap<String, SubFeature>,
 }
 
+impl Feature {
+    /// Whether this feature turns on an optional dependency via `dep:`.
+    fn enables_optional_dependency(&self) -> bool {
+        self.subfeatures
+            .values()
+            .any(|sub| matches!(sub, SubFeature::Dependency(_)))
+    }
+}
+
 impl From<DbFeature> for Feature {
     fn from(feature: DbFeature) -> Self {
         let subfeatures = feature
@@ -77,6 +90,106 @@ impl SubFeature {
             feature: feature.into(),
         }
     }
+
+    /// The kind of activation, used as CSS class in the template.
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Feature(_) => "feature",
+            Self::Dependency(_) => "dependency",
+            Self::DependencyFeature { .. } => "dependency-feature",
+        }
+    }
+}
+
+/// The feature flags cargo was invoked with for a build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BuildFeatures {
+    /// `--all-features`
+    All,
+    /// the default features, unless disabled, plus any features passed explicitly
+    Selected {
+        default_features: bool,
+        features: Vec<String>,
+    },
+}
+
+impl BuildFeatures {
+    /// Reads the feature flags from the recorded cargo arguments of a build.
+    fn from_cargo_args(cargo_args: &[String]) -> Self {
+        let mut default_features = true;
+        let mut features = Vec::new();
+
+        let mut args = cargo_args.iter().map(String::as_str);
+        while let Some(arg) = args.next() {
+            let list = match arg {
+                // everything after `--` is passed on to rustdoc
+                "--" => break,
+                "--all-features" => return Self::All,
+                "--no-default-features" => {
+                    default_features = false;
+                    continue;
+                }
+                "--features" | "-F" => args.next(),
+                _ => arg.strip_prefix("--features="),
+            };
+            features.extend(
+                list.into_iter()
+                    .flat_map(|list| list.split([' ', ',']))
+                    .filter(|feature| !feature.is_empty())
+                    .map(String::from),
+            );
+        }
+
+        Self::Selected {
+            default_features,
+            features,
+        }
+    }
+
+    /// All features of the crate enabled by these flags, following the
+    /// activations of each enabled feature.
+    fn resolve(&self, raw_features: &[DbFeature]) -> HashSet<String> {
+        let all_features: HashMap<_, _> = raw_features
+            .iter()
+            .map(|feature| (feature.name.as_str(), &feature.subfeatures))
+            .collect();
+
+        let mut queue: VecDeque<SubFeature> = match self {
+            Self::All => {
+                return all_features.into_keys().map(String::from).collect();
+            }
+            Self::Selected {
+                default_features,
+                features,
+            } => default_features
+                .then_some(DEFAULT_NAME)
+                .into_iter()
+                .chain(features.iter().map(String::as_str))
+                .map(SubFeature::parse)
+                .collect(),
+        };
+
+        let mut active = HashSet::new();
+        while let Some(sub) = queue.pop_front() {
+            // `dep/feature` also enables the implicit feature of an optional dependency
+            let name = match sub {
+                SubFeature::Feature(name)
+                | SubFeature::DependencyFeature {
+                    dependency: name,
+                    optional: false,
+                    ..
+                } => name,
+                SubFeature::Dependency(_) | SubFeature::DependencyFeature { .. } => continue,
+            };
+            let Some(subfeatures) = all_features.get(name.as_str()) else {
+                continue;
+            };
+            if active.insert(name) {
+                queue.extend(subfeatures.iter().map(|sub| SubFeature::parse(sub)));
+            }
+        }
+        active
+    }
 }
 
 #[derive(Template)]
@@ -87,6 +200,9 @@ struct FeaturesPage {
     dependencies: HashMap<String, String>,
     sorted_features: Option<Vec<Feature>>,
     default_features: HashSet<String>,
+    /// features enabled in the build the documentation is from, in page order.
+    /// `None` when we don't know how the crate was built.
+    build_features: Option<Vec<String>>,
     canonical_url: CanonicalUrl,
     is_latest_url: bool,
     csp_nonce: String,
@@ -96,6 +212,11 @@ impl FeaturesPage {
     fn is_default_feature(&self, feature: &str) -> bool {
         self.default_features.contains(feature)
     }
+    fn is_build_feature(&self, feature: &str) -> bool {
+        self.build_features
+            .as_ref()
+            .is_some_and(|features| features.iter().any(|f| f == feature))
+    }
     fn dependency_version(&self, dependency: &str) -> &str {
         self.dependencies
             .get(dependency)
@@ -122,8 +243,10 @@ impl FeaturesPage {
 pub(crate) async fn build_features_handler(
     Path((name, req_version)): Path<(String, ReqVersion)>,
     mut conn: DbConnection,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
+    Extension(config): Extension<Arc<Config>>,
 ) -> AxumResult<impl IntoResponse> {
-    let version = match_version(&mut conn, &name, &req_version)
+    let version = match_version(&mut conn, &metrics, &name, &req_version)
         .await?
         .assume_exact_name()?
         .into_canonical_req_version_or_else(|version| {
@@ -141,7 +264,16 @@ pub(crate) async fn build_features_handler(
         r#"
         SELECT
             releases.features as "features?: Vec<DbFeature>",
-            releases.dependencies
+            releases.dependencies,
+            (
+                SELECT cargo_args
+                FROM builds
+                WHERE
+                    builds.rid = releases.id AND
+                    builds.build_status = 'success'
+                ORDER BY builds.build_finished DESC
+                LIMIT 1
+            ) as "cargo_args?"
         FROM releases
         INNER JOIN crates ON crates.id = releases.crate_id
         WHERE crates.name = $1 AND releases.version = $2"#,
@@ -153,20 +285,36 @@ pub(crate) async fn build_features_handler(
     .ok_or_else(|| anyhow!("missing release"))?;
 
     let dependencies = get_dependency_versions(row.dependencies);
-    let (sorted_features, default_features) = if let Some(raw_features) = row.features {
-        let (sorted_features, default_features) = get_sorted_features(raw_features);
-        (Some(sorted_features), default_features)
-    } else {
-        (None, Default::default())
-    };
+    let (sorted_features, default_features, build_features) =
+        if let Some(raw_features) = row.features {
+            let active_features = row.cargo_args.map(|cargo_args| {
+                BuildFeatures::from_cargo_args(&cargo_args).resolve(&raw_features)
+            });
+            let (sorted_features, default_features) = get_sorted_features(raw_features);
+            let build_features = active_features.map(|active| {
+                sorted_features
+                    .iter()
+                    .map(|feature| &feature.name)
+                    .filter(|name| active.contains(*name))
+                    .cloned()
+                    .collect()
+            });
+            (Some(sorted_features), default_features, build_features)
+        } else {
+            (None, Default::default(), None)
+        };
 
     Ok(FeaturesPage {
         metadata,
         dependencies,
         sorted_features,
         default_features,
+        build_features,
         is_latest_url: req_version.is_latest(),
-        canonical_url: CanonicalUrl::from_path(format!("/crate/{}/latest/features", &name)),
+        canonical_url: CanonicalUrl::from_path(
+            &config.external_base_url,
+            format!("/crate/{}/latest/features", &name),
+        ),
         csp_nonce: String::new(),
     }
     .into_response())
@@ -232,7 +380,8 @@ fn get_sorted_features(raw_features: Vec<DbFeature>) -> (Vec<Feature>, HashSet<S
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test::{async_wrapper, AxumResponseTestExt, AxumRouterTestExt};
+    use crate::test::{async_wrapper, AxumResponseTestExt, AxumRouterTestExt, FakeBuild};
+    use kuchikiki::traits::TendrilSink;
     use reqwest::StatusCode;
 
     #[test]
@@ -264,6 +413,98 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_subfeature_kinds() {
+        assert_eq!(SubFeature::parse("a-feature").kind(), "feature");
+        assert_eq!(SubFeature::parse("dep:serde").kind(), "dependency");
+        assert_eq!(SubFeature::parse("tokio/rt").kind(), "dependency-feature");
+        assert_eq!(SubFeature::parse("tokio?/rt").kind(), "dependency-feature");
+    }
+
+    #[test]
+    fn test_enables_optional_dependency() {
+        let feature = Feature::from(DbFeature::new(
+            "serde".into(),
+            vec!["dep:serde".into(), "std".into()],
+        ));
+        assert!(feature.enables_optional_dependency());
+
+        let feature = Feature::from(DbFeature::new("rt".into(), vec!["tokio/rt".into()]));
+        assert!(!feature.enables_optional_dependency());
+    }
+
+    #[test]
+    fn test_build_features_from_cargo_args() {
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|&s| s.into()).collect() };
+
+        assert_eq!(
+            BuildFeatures::from_cargo_args(&args(&["rustdoc", "--lib"])),
+            BuildFeatures::Selected {
+                default_features: true,
+                features: vec![],
+            }
+        );
+        assert_eq!(
+            BuildFeatures::from_cargo_args(&args(&[
+                "rustdoc",
+                "--features",
+                "a b,c",
+                "--no-default-features",
+                "-F",
+                "d",
+                "--features=e",
+            ])),
+            BuildFeatures::Selected {
+                default_features: false,
+                features: vec!["a".into(), "b".into(), "c".into(), "d".into(), "e".into()],
+            }
+        );
+        assert_eq!(
+            BuildFeatures::from_cargo_args(&args(&["rustdoc", "--all-features"])),
+            BuildFeatures::All
+        );
+        assert_eq!(
+            BuildFeatures::from_cargo_args(&args(&["rustdoc", "--", "--all-features"])),
+            BuildFeatures::Selected {
+                default_features: true,
+                features: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_resolve_build_features() {
+        let raw_features = vec![
+            DbFeature::new(DEFAULT_NAME.into(), vec!["std".into()]),
+            DbFeature::new("std".into(), vec!["alloc".into()]),
+            DbFeature::new("alloc".into(), vec![]),
+            DbFeature::new("serde".into(), vec!["dep:serde".into(), "_derive".into()]),
+            DbFeature::new("_derive".into(), vec!["serde?/derive".into()]),
+            DbFeature::new("tokio".into(), vec![]),
+            DbFeature::new("rt".into(), vec!["tokio/rt".into()]),
+        ];
+        let names =
+            |names: &[&str]| -> HashSet<String> { names.iter().map(|&s| s.into()).collect() };
+
+        let selected = |default_features, features: &[&str]| BuildFeatures::Selected {
+            default_features,
+            features: features.iter().map(|&s| s.into()).collect(),
+        };
+
+        assert_eq!(
+            selected(true, &[]).resolve(&raw_features),
+            names(&[DEFAULT_NAME, "std", "alloc"])
+        );
+        assert_eq!(
+            selected(false, &["serde", "rt", "unknown"]).resolve(&raw_features),
+            names(&["serde", "_derive", "rt", "tokio"])
+        );
+        assert_eq!(
+            BuildFeatures::All.resolve(&raw_features).len(),
+            raw_features.len()
+        );
+    }
+
     #[test]
     fn test_feature_map_filters_private() {
         let private1 = DbFeature::new("_private1".into(), vec!["feature1".into()]);
@@ -428,6 +669,142 @@ mod tests {
         });
     }
 
+    #[test]
+    fn activations_are_rendered_by_kind() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .features(HashMap::from([
+                    ("serde".into(), vec!["dep:serde".into()]),
+                    ("rt".into(), vec!["tokio/rt".into()]),
+                ]))
+                .create()
+                .await?;
+
+            let page = kuchikiki::parse_html().one(
+                env.web_app()
+                    .await
+                    .get("/crate/foo/0.1.0/features")
+                    .await?
+                    .text()
+                    .await?,
+            );
+
+            let dependency = page.select_first("li.subfeature-dependency").unwrap();
+            assert_eq!(dependency.text_contents().trim(), "dep:serde");
+            let dependency_feature = page
+                .select_first("li.subfeature-dependency-feature")
+                .unwrap();
+            assert_eq!(dependency_feature.text_contents().trim(), "tokio/rt");
+
+            // only the feature with a `dep:` activation is flagged
+            let serde = page.select_first("h3#serde").unwrap();
+            assert!(serde
+                .as_node()
+                .select_first(".enables-optional-dependency")
+                .is_ok());
+            let rt = page.select_first("h3#rt").unwrap();
+            assert!(rt
+                .as_node()
+                .select_first(".enables-optional-dependency")
+                .is_err());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn build_features_are_highlighted() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .features(HashMap::from([
+                    (DEFAULT_NAME.into(), vec!["std".into()]),
+                    ("std".into(), vec![]),
+                    ("serde".into(), vec![]),
+                    ("unstable".into(), vec![]),
+                ]))
+                .builds(vec![FakeBuild::default().cargo_args(vec![
+                    "rustdoc".into(),
+                    "--lib".into(),
+                    "--features".into(),
+                    "serde".into(),
+                ])])
+                .create()
+                .await?;
+
+            let page = kuchikiki::parse_html().one(
+                env.web_app()
+                    .await
+                    .get("/crate/foo/0.1.0/features")
+                    .await?
+                    .text()
+                    .await?,
+            );
+
+            let banner = page.select_first("#build-features").unwrap();
+            let listed: Vec<_> = banner
+                .as_node()
+                .select("code")
+                .unwrap()
+                .map(|code| code.text_contents())
+                .collect();
+            assert_eq!(listed, ["default", "std", "serde"]);
+
+            for active in ["default", "std", "serde"] {
+                let heading = page.select_first(&format!("h3#{active}")).unwrap();
+                assert_eq!(
+                    heading.attributes.borrow().get("class"),
+                    Some("feature-active")
+                );
+                assert!(heading.as_node().select_first(".enabled-in-build").is_ok());
+            }
+            let unstable = page.select_first("h3#unstable").unwrap();
+            assert_eq!(
+                unstable.attributes.borrow().get("class"),
+                Some("feature-inactive")
+            );
+            assert!(unstable
+                .as_node()
+                .select_first(".enabled-in-build")
+                .is_err());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn build_features_unknown_without_cargo_args() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .features(HashMap::from([("serde".into(), vec![])]))
+                .create()
+                .await?;
+
+            let page = kuchikiki::parse_html().one(
+                env.web_app()
+                    .await
+                    .get("/crate/foo/0.1.0/features")
+                    .await?
+                    .text()
+                    .await?,
+            );
+
+            assert!(page.select_first("#build-features").is_err());
+            let serde = page.select_first("h3#serde").unwrap();
+            assert_eq!(serde.attributes.borrow().get("class"), None);
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn crate_version_not_found() {
         async_wrapper(|env| async move {
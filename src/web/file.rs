@@ -10,11 +10,12 @@ use crate::{
 use axum::{
     extract::Extension,
     http::{
-        header::{CONTENT_TYPE, LAST_MODIFIED},
-        StatusCode,
+        header::{ACCEPT_RANGES, CONTENT_RANGE, CONTENT_TYPE, LAST_MODIFIED},
+        HeaderValue, StatusCode,
     },
     response::{IntoResponse, Response as AxumResponse},
 };
+use std::ops::Range;
 
 #[derive(Debug)]
 pub(crate) struct File(pub(crate) Blob);
@@ -34,6 +35,83 @@ impl File {
 
         Ok(File(storage.get(path, max_size).await?))
     }
+
+    /// Builds the response, honoring a single-range `Range: bytes=...` request header.
+    ///
+    /// Satisfiable ranges are answered with `206 Partial Content` and the requested slice,
+    /// unsatisfiable ones with `416 Range Not Satisfiable`. Headers we don't understand
+    /// (other units, multiple ranges, invalid syntax) are ignored and the full file is served.
+    pub(super) fn into_response_for_range(self, range: Option<&HeaderValue>) -> AxumResponse {
+        let len = self.0.content.len();
+        let Some(requested) = range
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| parse_byte_range(value, len))
+        else {
+            return self.into_response();
+        };
+
+        let Some(range) = requested else {
+            return (
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(CONTENT_RANGE, format!("bytes */{len}"))],
+                Extension(CachePolicy::NoCaching),
+            )
+                .into_response();
+        };
+
+        let content_range = format!("bytes {}-{}/{len}", range.start, range.end - 1);
+        let File(mut blob) = self;
+        blob.content.truncate(range.end);
+        blob.content.drain(..range.start);
+
+        let mut response = File(blob).into_response();
+        *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+        response.headers_mut().insert(
+            CONTENT_RANGE,
+            HeaderValue::from_str(&content_range).expect("valid header value"),
+        );
+        response
+    }
+}
+
+/// Parses a `Range` header value containing a single byte range.
+///
+/// Returns `None` when the header should be ignored, `Some(None)` when the range
+/// can't be satisfied for a body of `len` bytes, and otherwise the range to serve.
+fn parse_byte_range(header: &str, len: usize) -> Option<Option<Range<usize>>> {
+    let spec = header.trim().strip_prefix("bytes=")?.trim();
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = if start.is_empty() {
+        // suffix range, `bytes=-500` means the last 500 bytes.
+        let suffix: usize = end.parse().ok()?;
+        if suffix == 0 {
+            return Some(None);
+        }
+        len.saturating_sub(suffix)..len
+    } else {
+        let start: usize = start.parse().ok()?;
+        let end = if end.is_empty() {
+            len
+        } else {
+            let end: usize = end.parse().ok()?;
+            if end < start {
+                return None;
+            }
+            end.saturating_add(1).min(len)
+        };
+        start..end
+    };
+
+    if range.start >= len {
+        Some(None)
+    } else {
+        Some(Some(range))
+    }
 }
 
 impl IntoResponse for File {
@@ -42,6 +120,7 @@ impl IntoResponse for File {
             StatusCode::OK,
             [
                 (CONTENT_TYPE, self.0.mime.as_ref()),
+                (ACCEPT_RANGES, "bytes"),
                 (
                     LAST_MODIFIED,
                     &self.0.date_updated.format("%a, %d %b %Y %T %Z").to_string(),
@@ -57,9 +136,15 @@ impl IntoResponse for File {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test::async_wrapper;
+    use crate::test::{async_wrapper, AxumResponseTestExt, AxumRouterTestExt};
+    use axum::body::Body;
     use chrono::Utc;
-    use http::header::CACHE_CONTROL;
+    use http::{
+        header::{CACHE_CONTROL, RANGE},
+        Request,
+    };
+    use test_case::test_case;
+    use tower::ServiceExt;
 
     #[test]
     fn file_roundtrip_axum() {
@@ -152,4 +237,89 @@ mod tests {
             Ok(())
         })
     }
+
+    #[test_case("module.mjs", "text/javascript")]
+    #[test_case("module.wasm", "application/wasm")]
+    #[test_case("search.js.map", "application/json")]
+    #[test_case("font.woff2", "font/woff2")]
+    fn modern_rustdoc_assets_content_type(filename: &str, expected: &str) {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(true)
+                .rustdoc_file_with(&format!("dummy/{filename}"), b"content")
+                .create()
+                .await?;
+
+            let response = env
+                .web_app()
+                .await
+                .get(&format!("/dummy/0.1.0/dummy/{filename}"))
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), expected);
+
+            Ok(())
+        });
+    }
+
+    #[test_case("bytes=0-3", Some(Some(0..4)))]
+    #[test_case("bytes=2-", Some(Some(2..10)))]
+    #[test_case("bytes=-3", Some(Some(7..10)))]
+    #[test_case("bytes=5-100", Some(Some(5..10)))]
+    #[test_case("bytes=-100", Some(Some(0..10)))]
+    #[test_case("bytes=10-", Some(None))]
+    #[test_case("bytes=-0", Some(None))]
+    #[test_case("bytes=3-1", None)]
+    #[test_case("bytes=0-1,4-5", None)]
+    #[test_case("items=0-1", None)]
+    #[test_case("bytes=a-b", None)]
+    fn test_parse_byte_range(header: &str, expected: Option<Option<Range<usize>>>) {
+        assert_eq!(parse_byte_range(header, 10), expected);
+    }
+
+    #[test]
+    fn range_request_returns_partial_content() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(true)
+                .rustdoc_file_with("dummy/data.bin", b"0123456789")
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            let request = |range: &str| {
+                Request::builder()
+                    .uri("/dummy/0.1.0/dummy/data.bin")
+                    .header(RANGE, range)
+                    .body(Body::empty())
+                    .unwrap()
+            };
+
+            let response = web.clone().oneshot(request("bytes=2-5")).await?;
+            assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+            assert_eq!(
+                response.headers().get(CONTENT_RANGE).unwrap(),
+                "bytes 2-5/10"
+            );
+            assert_eq!(response.headers().get(ACCEPT_RANGES).unwrap(), "bytes");
+            assert_eq!(response.text().await?, "2345");
+
+            let response = web.clone().oneshot(request("bytes=0-1,4-5")).await?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.text().await?, "0123456789");
+
+            let response = web.clone().oneshot(request("bytes=20-30")).await?;
+            assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+            assert_eq!(response.headers().get(CONTENT_RANGE).unwrap(), "bytes */10");
+            response.assert_cache_control(CachePolicy::NoCaching, &env.config());
+
+            Ok(())
+        });
+    }
 }
@@ -1,31 +1,76 @@
 use super::encode_url_path;
 use anyhow::Result;
-use axum::http::uri::{PathAndQuery, Uri};
-use axum_extra::headers::{Header, HeaderName, HeaderValue};
+use axum::http::uri::PathAndQuery;
+use axum_extra::headers::{ETag, Header, HeaderName, HeaderValue};
 use serde::Serialize;
+use sha2::{Digest as _, Sha256};
 use std::fmt;
+use url::Url;
+
+/// compute a strong `ETag` from the given response body.
+///
+/// The tag is a truncated SHA-256 of the content, so it stays the same across
+/// deploys and between our servers.
+pub(crate) fn compute_etag(content: &[u8]) -> ETag {
+    let mut computer = ETagComputer::new();
+    computer.update(content);
+    computer.finalize()
+}
+
+/// incremental version of [`compute_etag`], for content we get in chunks
+/// and don't want to buffer completely.
+///
+/// Feeding the content in any chunking leads to the same `ETag` as
+/// `compute_etag` on the whole content.
+#[derive(Debug, Default)]
+pub(crate) struct ETagComputer {
+    hasher: Sha256,
+}
+
+impl ETagComputer {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn update(&mut self, chunk: &[u8]) {
+        self.hasher.update(chunk);
+    }
+
+    pub(crate) fn finalize(self) -> ETag {
+        let digest = self.hasher.finalize();
+        format!("\"{}\"", hex::encode(&digest[..8]))
+            .parse()
+            .expect("hex digest is always a valid etag")
+    }
+}
 
 /// simplified typed header for a `Link rel=canonical` header in the response.
-/// Only takes the path to be used, url-encodes it and attaches domain & schema to it.
-#[derive(Debug, Clone)]
-pub struct CanonicalUrl(PathAndQuery);
+/// Only takes the path to be used, url-encodes it and attaches the schema & host
+/// of the configured external base URL to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanonicalUrl {
+    base: Url,
+    path: PathAndQuery,
+}
 
 impl CanonicalUrl {
-    pub fn from_path<P: AsRef<str>>(path: P) -> Self {
-        Self(
-            encode_url_path(path.as_ref())
+    pub fn from_path<P: AsRef<str>>(base: &Url, path: P) -> Self {
+        Self {
+            base: base.clone(),
+            path: encode_url_path(path.as_ref())
                 .try_into()
                 .expect("invalid URI path characters even after encoding them"),
-        )
+        }
     }
 
-    fn build_full_uri(&self) -> Uri {
-        Uri::builder()
-            .scheme("https")
-            .authority("docs.rs")
-            .path_and_query(self.0.clone())
-            .build()
-            .expect("this unwrap can't fail because PathAndQuery is valid")
+    pub(crate) fn build_full_uri(&self) -> Url {
+        // `Url::join` with an absolute path would drop the path of the base URL,
+        // so we append our path to it.
+        let mut url = self.base.clone();
+        let base_path = url.path().trim_end_matches('/').to_owned();
+        url.set_path(&format!("{base_path}{}", self.path.path()));
+        url.set_query(self.path.query());
+        url
     }
 }
 
@@ -75,9 +120,55 @@ mod tests {
     use axum::http::HeaderMap;
     use axum_extra::headers::HeaderMapExt;
 
+    fn docs_rs() -> Url {
+        "https://docs.rs".parse().unwrap()
+    }
+
+    #[test]
+    fn test_compute_etag() {
+        let etag = compute_etag(b"some content");
+        assert_eq!(etag, compute_etag(b"some content"));
+        assert_ne!(etag, compute_etag(b"other content"));
+
+        let mut map = HeaderMap::new();
+        map.typed_insert(etag);
+        let value = map["etag"].to_str().unwrap();
+        assert!(value.starts_with('"') && value.ends_with('"'));
+        assert_eq!(value.len(), 18);
+    }
+
+    #[test]
+    fn test_compute_etag_is_stable() {
+        // first 8 bytes of the SHA-256 of "some content"
+        let mut map = HeaderMap::new();
+        map.typed_insert(compute_etag(b"some content"));
+        assert_eq!(map["etag"], "\"290f493c44f5d63d\"");
+    }
+
+    #[test]
+    fn test_etag_computer_chunked() {
+        let content: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+        let expected = compute_etag(&content);
+
+        for chunk_size in [1, 7, 64, 1000, content.len()] {
+            let mut computer = ETagComputer::new();
+            for chunk in content.chunks(chunk_size) {
+                computer.update(chunk);
+            }
+            assert_eq!(computer.finalize(), expected, "chunk size {chunk_size}");
+        }
+
+        // empty chunks don't change the result
+        let mut computer = ETagComputer::new();
+        computer.update(&[]);
+        computer.update(&content);
+        computer.update(&[]);
+        assert_eq!(computer.finalize(), expected);
+    }
+
     #[test]
     fn test_serialize_canonical() {
-        let url = CanonicalUrl::from_path("/some/path/");
+        let url = CanonicalUrl::from_path(&docs_rs(), "/some/path/");
 
         assert_eq!(
             serde_json::to_string(&url).unwrap(),
@@ -88,7 +179,7 @@ mod tests {
     #[test]
     fn test_encode_canonical() {
         let mut map = HeaderMap::new();
-        map.typed_insert(CanonicalUrl::from_path("/some/path/"));
+        map.typed_insert(CanonicalUrl::from_path(&docs_rs(), "/some/path/"));
         assert_eq!(
             map["link"],
             "<https://docs.rs/some/path/>; rel=\"canonical\""
@@ -98,10 +189,36 @@ mod tests {
     #[test]
     fn test_encode_canonical_with_encoding() {
         let mut map = HeaderMap::new();
-        map.typed_insert(CanonicalUrl::from_path("/some/äöü/"));
+        map.typed_insert(CanonicalUrl::from_path(&docs_rs(), "/some/äöü/"));
         assert_eq!(
             map["link"],
             "<https://docs.rs/some/%C3%A4%C3%B6%C3%BC/>; rel=\"canonical\""
         );
     }
+
+    #[test]
+    fn test_canonical_with_external_base_url() {
+        let base: Url = "https://mirror.example".parse().unwrap();
+        let mut map = HeaderMap::new();
+        map.typed_insert(CanonicalUrl::from_path(&base, "/some/path/"));
+        assert_eq!(
+            map["link"],
+            "<https://mirror.example/some/path/>; rel=\"canonical\""
+        );
+    }
+
+    #[test]
+    fn test_canonical_with_base_url_path() {
+        for base in [
+            "https://mirror.example/docs",
+            "https://mirror.example/docs/",
+        ] {
+            let base: Url = base.parse().unwrap();
+            let url = CanonicalUrl::from_path(&base, "/some/äöü/");
+            assert_eq!(
+                url.to_string(),
+                "https://mirror.example/docs/some/%C3%A4%C3%B6%C3%BC/"
+            );
+        }
+    }
 }
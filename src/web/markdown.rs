@@ -1,6 +1,7 @@
 use crate::web::highlight;
 use comrak::{
-    adapters::SyntaxHighlighterAdapter, ExtensionOptions, Options, Plugins, RenderPlugins,
+    adapters::SyntaxHighlighterAdapter, nodes::NodeValue, Arena, ExtensionOptions, Options,
+    Plugins, RenderPlugins,
 };
 use std::collections::HashMap;
 
@@ -93,14 +94,108 @@ fn render_with_highlighter(
     )
 }
 
+/// Markdown input larger than this is cut off before rendering.
+const MAX_INPUT_SIZE: usize = 512 * 1024;
+/// Rendered HTML larger than this is thrown away and rendered again from a shorter input.
+const MAX_OUTPUT_SIZE: usize = 4 * 1024 * 1024;
+
+const TRUNCATED_NOTICE: &str =
+    "<p><em>This document was truncated because it is too large to be displayed.</em></p>\n";
+
+/// Cut `text` to at most `max_len` bytes, preferably at the end of a line.
+fn truncate_input(text: &str, max_len: usize) -> &str {
+    if text.len() <= max_len {
+        return text;
+    }
+    let mut end = max_len;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let text = &text[..end];
+    match text.rfind('\n') {
+        Some(newline) if newline > 0 => &text[..=newline],
+        _ => text,
+    }
+}
+
+/// Render at most `max_input` bytes of markdown into at most `max_output` bytes of HTML.
+///
+/// When the document doesn't fit, the rendered part is followed by a notice. This renders
+/// at most twice: if the HTML is too large, the input is shortened in proportion and
+/// rendered once more, giving up when that still doesn't fit.
+fn render_with_limits(
+    text: &str,
+    max_input: usize,
+    max_output: usize,
+    highlighter: impl Fn(Option<&str>, &str) -> String + Send + Sync,
+) -> String {
+    let input = truncate_input(text, max_input);
+    let mut output = render_with_highlighter(input, &highlighter);
+
+    if output.len() > max_output {
+        let shorter_len = input.len() * max_output / output.len() / 2;
+        output = render_with_highlighter(truncate_input(input, shorter_len), &highlighter);
+        if output.len() > max_output {
+            output.clear();
+        }
+    } else if input.len() == text.len() {
+        return output;
+    }
+
+    output.push_str(TRUNCATED_NOTICE);
+    output
+}
+
 /// Wrapper around the Markdown parser and renderer to render markdown
+///
+/// Oversized documents are truncated and end with a notice instead of being rendered in full.
 pub fn render(text: &str) -> String {
-    render_with_highlighter(text, highlight::with_lang)
+    render_with_limits(text, MAX_INPUT_SIZE, MAX_OUTPUT_SIZE, highlight::with_lang)
+}
+
+/// Plain-text excerpt of the first paragraph of a markdown document that contains text.
+///
+/// Paragraphs that only contain images (like badge lines) are skipped, formatting is
+/// dropped and the excerpt is cut at `max_len` characters.
+pub(crate) fn excerpt(text: &str, max_len: usize) -> Option<String> {
+    let arena = Arena::new();
+    let root = comrak::parse_document(&arena, text, &Options::default());
+
+    root.children()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::Paragraph))
+        .find_map(|paragraph| {
+            let mut excerpt = String::new();
+            for node in paragraph.descendants() {
+                // skip image alt texts, they are not part of the prose
+                if node
+                    .ancestors()
+                    .any(|ancestor| matches!(ancestor.data.borrow().value, NodeValue::Image(_)))
+                {
+                    continue;
+                }
+                match &node.data.borrow().value {
+                    NodeValue::Text(text) => excerpt.push_str(text),
+                    NodeValue::Code(code) => excerpt.push_str(&code.literal),
+                    NodeValue::SoftBreak | NodeValue::LineBreak => excerpt.push(' '),
+                    _ => {}
+                }
+            }
+
+            let excerpt = excerpt.split_whitespace().collect::<Vec<_>>().join(" ");
+            if excerpt.is_empty() {
+                None
+            } else if excerpt.chars().count() > max_len {
+                let cut: String = excerpt.chars().take(max_len).collect();
+                Some(format!("{}…", cut.trim_end()))
+            } else {
+                Some(excerpt)
+            }
+        })
 }
 
 #[cfg(test)]
 mod test {
-    use super::render_with_highlighter;
+    use super::{excerpt, render_with_highlighter, render_with_limits, TRUNCATED_NOTICE};
     use indoc::indoc;
     use std::sync::Mutex;
 
@@ -135,4 +230,58 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn excerpt_skips_headings_and_badges() {
+        let readme = indoc! {"
+            # my-crate
+
+            [![Crates.io](https://img.shields.io/crates/v/my-crate.svg)](https://crates.io/crates/my-crate)
+
+            A *fast* library for `parsing`
+            things.
+
+            ## Usage
+        "};
+        assert_eq!(
+            excerpt(readme, 100).as_deref(),
+            Some("A fast library for parsing things.")
+        );
+        assert_eq!(excerpt(readme, 6).as_deref(), Some("A fast…"));
+        assert_eq!(excerpt("# only a heading", 100), None);
+    }
+
+    #[test]
+    fn render_within_limits_is_complete() {
+        let text = "# Title\n\nSome *text*.\n";
+        let output = render_with_limits(text, 1024, 1024, |_, code| code.to_owned());
+        assert_eq!(output, "<h1>Title</h1>\n<p>Some <em>text</em>.</p>\n");
+    }
+
+    #[test]
+    fn render_truncates_oversized_input() {
+        let text = "first paragraph\n\nsecond paragraph\n".repeat(100);
+        let output = render_with_limits(&text, 40, 1024 * 1024, |_, code| code.to_owned());
+        assert_eq!(
+            output,
+            format!("<p>first paragraph</p>\n<p>second paragraph</p>\n{TRUNCATED_NOTICE}")
+        );
+    }
+
+    #[test]
+    fn render_truncates_oversized_output() {
+        // every `*` becomes a list item, blowing up the output size
+        let text = "* a\n".repeat(1000);
+        let output = render_with_limits(&text, 1024 * 1024, 1000, |_, code| code.to_owned());
+        assert!(output.len() <= 1000 + TRUNCATED_NOTICE.len());
+        assert!(output.starts_with("<ul>\n<li>a</li>\n"));
+        assert!(output.ends_with(TRUNCATED_NOTICE));
+    }
+
+    #[test]
+    fn truncate_input_keeps_char_boundaries() {
+        assert_eq!(super::truncate_input("äöü", 3), "ä");
+        assert_eq!(super::truncate_input("ab\ncd", 4), "ab\n");
+        assert_eq!(super::truncate_input("short", 100), "short");
+    }
 }
@@ -111,8 +111,11 @@ pub(crate) async fn request_recorder(
 #[cfg(test)]
 mod tests {
     use crate::test::{async_wrapper, AxumResponseTestExt, AxumRouterTestExt};
+    use crate::web::build_metrics_axum_app;
     use crate::Context;
+    use reqwest::StatusCode;
     use std::collections::HashMap;
+    use test_case::test_case;
 
     #[test]
     fn test_response_times_count_being_collected() {
@@ -148,6 +151,11 @@ mod tests {
                 "/-/sitemap/a/sitemap.xml",
                 "/-/sitemap/{letter}/sitemap.xml",
             ),
+            ("/sitemap.xml.gz", "/sitemap.xml.gz"),
+            (
+                "/-/sitemap/a/sitemap.xml.gz",
+                "/-/sitemap/{letter}/sitemap.xml.gz",
+            ),
             ("/-/static/style.css", "static resource"),
             ("/-/static/vendored.css", "static resource"),
             ("/rustdoc/rcc/0.0.0/rcc/index.html", "rustdoc page"),
@@ -267,4 +275,29 @@ mod tests {
             Ok(())
         })
     }
+
+    #[test_case(true)]
+    #[test_case(false)]
+    fn test_metrics_scrape_endpoint(enabled: bool) {
+        async_wrapper(|env| async move {
+            env.override_config(|config| config.metrics_scrape_endpoint = enabled);
+
+            let response = build_metrics_axum_app(&*env).await?.get("/metrics").await?;
+            if enabled {
+                assert!(response.status().is_success());
+                let body = response.text().await?;
+                assert!(body.contains("docsrs_failed_db_connections"), "{}", body);
+                assert!(body.contains("queued_crates_count"), "{}", body);
+            } else {
+                assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            }
+
+            // the main web server keeps serving the `metrics` crate on that path
+            assert_ne!(
+                env.web_app().await.get("/metrics").await?.status(),
+                StatusCode::OK
+            );
+            Ok(())
+        })
+    }
 }
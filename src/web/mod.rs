@@ -3,6 +3,7 @@
 pub mod page;
 // mod tmp;
 
+use crate::db::crate_redirects;
 use crate::db::types::BuildStatus;
 use crate::db::CrateId;
 use crate::db::ReleaseId;
@@ -20,6 +21,7 @@ mod builds;
 pub(crate) mod cache;
 pub(crate) mod crate_details;
 mod csp;
+mod diff;
 pub(crate) mod error;
 mod extractors;
 mod features;
@@ -28,15 +30,17 @@ mod headers;
 mod highlight;
 mod markdown;
 pub(crate) mod metrics;
+mod permalink;
+mod rate_limit;
 mod releases;
 mod routes;
 pub(crate) mod rustdoc;
-mod sitemap;
+pub(crate) mod sitemap;
 mod source;
 mod statics;
 mod status;
 
-use crate::{impl_axum_webpage, Context};
+use crate::{impl_axum_webpage, Context, InstanceMetrics};
 use anyhow::Error;
 use axum::{
     extract::{Extension, MatchedPath, Request as AxumRequest},
@@ -50,6 +54,7 @@ use chrono::{DateTime, Utc};
 use error::AxumNope;
 use page::TemplateData;
 use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use rate_limit::{rate_limit_middleware, RateLimiter};
 use semver::{Version, VersionReq};
 use sentry::integrations::tower as sentry_tower;
 use serde_with::{DeserializeFromStr, SerializeDisplay};
@@ -91,6 +96,33 @@ impl ReqVersion {
     pub(crate) fn is_latest(&self) -> bool {
         matches!(self, ReqVersion::Latest)
     }
+
+    /// parse a version like [`FromStr`], but keep the exact input.
+    ///
+    /// Parsing normalizes some inputs, for example `newest` and the empty string both become `*`.
+    /// The raw string can be used to tell the user what they actually requested.
+    pub(crate) fn parse_with_raw(s: &str) -> Result<ParsedReqVersion, semver::Error> {
+        Ok(ParsedReqVersion {
+            req: s.parse()?,
+            raw: s.to_owned(),
+        })
+    }
+}
+
+/// a [`ReqVersion`] together with the string it was parsed from.
+///
+/// Can be used in place of [`ReqVersion`] in path parameters.
+#[derive(Debug, Clone, PartialEq, Eq, DeserializeFromStr)]
+pub(crate) struct ParsedReqVersion {
+    pub(crate) req: ReqVersion,
+    pub(crate) raw: String,
+}
+
+impl FromStr for ParsedReqVersion {
+    type Err = semver::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ReqVersion::parse_with_raw(s)
+    }
 }
 
 impl Display for ReqVersion {
@@ -230,6 +262,12 @@ impl MatchedRelease {
         self.release.rustdoc_status.unwrap_or(false)
     }
 
+    /// Whether this release has library documentation we can link to.
+    /// Binary-only crates never have any, even when their build succeeded.
+    fn has_library_docs(&self) -> bool {
+        self.release.is_library != Some(false) && self.rustdoc_status()
+    }
+
     fn target_name(&self) -> Option<&str> {
         self.release.target_name.as_deref()
     }
@@ -262,20 +300,79 @@ fn semver_match<'a, F: Fn(&Release) -> bool>(
     }
 }
 
+/// The newest release that is not yanked, not a pre-release and was built successfully.
+///
+/// Expects `releases` to be sorted newest first, like `releases_for_crate` returns them.
+/// Unlike `match_version` with `latest` this doesn't fall back to pre-releases, callers
+/// have to decide what to do when there is no stable release.
+pub(crate) fn latest_stable(releases: &[Release]) -> Option<&Release> {
+    releases.iter().find(|release| {
+        release.version.pre.is_empty()
+            && release.yanked != Some(true)
+            && release.build_status == BuildStatus::Success
+    })
+}
+
 /// Checks the database for crate releases that match the given name and version.
 ///
 /// `version` may be an exact version number or loose semver version requirement. The return value
 /// will indicate whether the given version exactly matched a version number from the database.
 ///
 /// This function will also check for crates where dashes in the name (`-`) have been replaced with
-/// underscores (`_`) and vice-versa. The return value will indicate whether the crate name has
-/// been matched exactly, or if there has been a "correction" in the name that matched instead.
-#[instrument(skip(conn))]
+/// underscores (`_`) and vice-versa, or where the casing differs from the published name.
+/// The return value will indicate whether the crate name has been matched exactly, or if
+/// there has been a "correction" in the name that matched instead.
+///
+/// Every lookup is counted in the `version_match_outcomes` metric, labelled with how it was
+/// resolved.
+#[instrument(skip(conn, metrics))]
 async fn match_version(
     conn: &mut sqlx::PgConnection,
+    metrics: &InstanceMetrics,
     name: &str,
     input_version: &ReqVersion,
 ) -> Result<MatchedRelease, AxumNope> {
+    let (result, outcome) = match_version_lookup(conn, name, input_version).await?;
+    let outcome: &'static str = outcome.into();
+    metrics
+        .version_match_outcomes
+        .with_label_values(&[outcome])
+        .inc();
+    result
+}
+
+/// How a [`match_version`] lookup was resolved, for metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+enum MatchOutcome {
+    Exact,
+    Semver,
+    Latest,
+    CorrectedName,
+    CrateRenamed,
+    CrateNotFound,
+    VersionNotFound,
+    AllYanked,
+}
+
+impl MatchOutcome {
+    fn of_match(matched: &MatchedRelease, input_version: &ReqVersion) -> Self {
+        if matched.corrected_name.is_some() {
+            return Self::CorrectedName;
+        }
+        match input_version {
+            ReqVersion::Exact(version) if *version == matched.release.version => Self::Exact,
+            ReqVersion::Latest => Self::Latest,
+            _ => Self::Semver,
+        }
+    }
+}
+
+async fn match_version_lookup(
+    conn: &mut sqlx::PgConnection,
+    name: &str,
+    input_version: &ReqVersion,
+) -> Result<(Result<MatchedRelease, AxumNope>, MatchOutcome), AxumNope> {
     let (crate_id, corrected_name) = {
         let row = sqlx::query!(
             r#"
@@ -288,8 +385,18 @@ async fn match_version(
         )
         .fetch_optional(&mut *conn)
         .await
-        .context("error fetching crate")?
-        .ok_or(AxumNope::CrateNotFound)?;
+        .context("error fetching crate")?;
+        let Some(row) = row else {
+            // crates that were superseded by a renamed crate redirect to it,
+            // on every page that looks up the crate.
+            if let Some(to_name) = crate_redirects::get_redirect(conn, name).await? {
+                return Ok((
+                    Err(AxumNope::CrateRenamed(to_name)),
+                    MatchOutcome::CrateRenamed,
+                ));
+            }
+            return Ok((Err(AxumNope::CrateNotFound), MatchOutcome::CrateNotFound));
+        };
 
         if row.name != name {
             (row.id, Some(row.name))
@@ -305,7 +412,7 @@ async fn match_version(
         .context("error fetching releases for crate")?;
 
     if releases.is_empty() {
-        return Err(AxumNope::CrateNotFound);
+        return Ok((Err(AxumNope::CrateNotFound), MatchOutcome::CrateNotFound));
     }
 
     let req_semver: VersionReq = match input_version {
@@ -314,13 +421,15 @@ async fn match_version(
                 .iter()
                 .find(|release| &release.version == parsed_req_version)
             {
-                return Ok(MatchedRelease {
+                let matched = MatchedRelease {
                     name: name.to_owned(),
                     corrected_name,
                     req_version: input_version.clone(),
                     release: release.clone(),
                     all_releases: releases,
-                });
+                };
+                let outcome = MatchOutcome::of_match(&matched, input_version);
+                return Ok((Ok(matched), outcome));
             }
 
             if let Ok(version_req) = VersionReq::parse(&parsed_req_version.to_string()) {
@@ -329,7 +438,10 @@ async fn match_version(
                 // A normal semver version ("1.2.3") is equivalent to a caret semver requirement.
                 version_req
             } else {
-                return Err(AxumNope::VersionNotFound);
+                return Ok((
+                    Err(AxumNope::VersionNotFound),
+                    MatchOutcome::VersionNotFound,
+                ));
             }
         }
         ReqVersion::Latest => VersionReq::STAR,
@@ -338,36 +450,109 @@ async fn match_version(
 
     // when matching semver requirements,
     // we generally only want to look at non-yanked releases,
-    // excluding releases which just contain in-progress builds
+    // excluding releases which just contain in-progress builds.
+    // Releases with skipped builds are matched just like failed ones,
+    // since they won't get any docs either.
     if let Some(release) = semver_match(&releases, &req_semver, |r: &Release| {
         r.build_status != BuildStatus::InProgress && (r.yanked.is_none() || r.yanked == Some(false))
     }) {
-        return Ok(MatchedRelease {
+        let matched = MatchedRelease {
             name: name.to_owned(),
             corrected_name,
             req_version: input_version.clone(),
             release: release.clone(),
             all_releases: releases,
-        });
+        };
+        let outcome = MatchOutcome::of_match(&matched, input_version);
+        return Ok((Ok(matched), outcome));
     }
 
     // when we don't find any match with "normal" releases, we also look into in-progress releases
     if let Some(release) = semver_match(&releases, &req_semver, |r: &Release| {
         r.yanked.is_none() || r.yanked == Some(false)
     }) {
-        return Ok(MatchedRelease {
+        let matched = MatchedRelease {
             name: name.to_owned(),
             corrected_name,
             req_version: input_version.clone(),
             release: release.clone(),
             all_releases: releases,
-        });
+        };
+        let outcome = MatchOutcome::of_match(&matched, input_version);
+        return Ok((Ok(matched), outcome));
     }
 
     // Since we return with a CrateNotFound earlier if the db reply is empty,
     // we know that versions were returned but none satisfied the version requirement.
-    // This can only happen when all versions are yanked.
-    Err(AxumNope::VersionNotFound)
+    // This happens for exact versions that don't exist, or when all versions are yanked.
+    let suggestions = match input_version {
+        ReqVersion::Exact(version) => nearest_versions(&releases, version),
+        _ => releases
+            .first()
+            .map(|r| r.version.clone())
+            .into_iter()
+            .collect(),
+    };
+    let outcome = if releases.iter().all(|release| release.yanked == Some(true)) {
+        MatchOutcome::AllYanked
+    } else {
+        MatchOutcome::VersionNotFound
+    };
+    Ok((
+        Err(AxumNope::VersionNotFoundWithSuggestions {
+            name: corrected_name.unwrap_or_else(|| name.to_owned()),
+            requested: None,
+            suggestions,
+        }),
+        outcome,
+    ))
+}
+
+/// the closest lower and higher non-yanked versions around a version that
+/// doesn't exist.
+///
+/// Falls back to the newest release when there are no non-yanked releases.
+/// `releases` has to be sorted, newest version first.
+fn nearest_versions(releases: &[Release], requested: &Version) -> Vec<Version> {
+    let candidates = releases
+        .iter()
+        .filter(|release| release.yanked != Some(true))
+        .map(|release| &release.version);
+
+    let lower = candidates.clone().filter(|v| *v < requested).max();
+    let higher = candidates.filter(|v| *v > requested).min();
+
+    let nearest: Vec<Version> = lower.into_iter().chain(higher).cloned().collect();
+    if nearest.is_empty() {
+        releases
+            .first()
+            .map(|r| r.version.clone())
+            .into_iter()
+            .collect()
+    } else {
+        nearest
+    }
+}
+
+/// The redirect for a rustdoc page of crate `name`, which was renamed to `to_name`.
+///
+/// `path` is the rest of the request path after the crate name and the version. When it
+/// starts with the old crate's target name, we point it to the same item in the new crate,
+/// otherwise we redirect to the new crate's root.
+fn redirect_renamed_crate(name: &str, to_name: &str, path: &[&str]) -> AxumNope {
+    let target = match path.split_first() {
+        Some((first, rest)) if *first == name.replace('-', "_") => format!(
+            "/{to_name}/latest/{}/{}",
+            to_name.replace('-', "_"),
+            rest.join("/")
+        ),
+        _ => format!("/{to_name}/latest/"),
+    };
+
+    // redirects can be removed again without any build that would purge the CDN,
+    // so we only cache them for a short time. The target is url-encoded when the
+    // redirect response is built.
+    AxumNope::PermanentRedirect(target, cache::CachePolicy::ShortInCdnAndBrowser)
 }
 
 async fn log_timeouts_to_sentry(req: AxumRequest, next: Next) -> AxumResponse {
@@ -437,7 +622,14 @@ async fn apply_middleware<C: Context>(
             .layer(option_layer(has_templates.then_some(middleware::from_fn(
                 page::web_page::render_templates_middleware,
             ))))
-            .layer(middleware::from_fn(cache::cache_middleware)),
+            .layer(middleware::from_fn(error::embed_error_middleware))
+            .layer(middleware::from_fn(error::json_error_middleware))
+            .layer(middleware::from_fn(cache::cache_middleware))
+            .layer(middleware::from_fn(cache::json_etag_middleware))
+            // inside the cache middleware, so the rate limit response gets its cache policy.
+            .layer(option_layer(RateLimiter::from_config(&config).map(
+                |limiter| middleware::from_fn_with_state(Arc::new(limiter), rate_limit_middleware),
+            ))),
     ))
 }
 
@@ -445,11 +637,20 @@ pub(crate) async fn build_axum_app<C: Context>(
     context: &C,
     template_data: Arc<TemplateData>,
 ) -> Result<AxumRouter, Error> {
-    apply_middleware(routes::build_axum_routes(), context, Some(template_data)).await
+    apply_middleware(
+        routes::build_axum_routes(&context.config()?),
+        context,
+        Some(template_data),
+    )
+    .await
 }
 
 pub(crate) async fn build_metrics_axum_app<C: Context>(context: &C) -> Result<AxumRouter, Error> {
-    apply_middleware(routes::build_metric_routes(), context, None).await
+    let mut router = routes::build_metric_routes();
+    if context.config()?.metrics_scrape_endpoint {
+        router = router.merge(routes::build_metric_scrape_routes());
+    }
+    apply_middleware(router, context, None).await
 }
 
 pub fn start_background_metrics_webserver<C: Context>(
@@ -512,7 +713,7 @@ pub fn start_web_server<C: Context>(addr: Option<SocketAddr>, context: &C) -> Re
     context.runtime()?.block_on(async {
         let app = build_axum_app(context, template_data)
             .await?
-            .into_make_service();
+            .into_make_service_with_connect_info::<SocketAddr>();
         let listener = tokio::net::TcpListener::bind(axum_addr)
             .await
             .context("error binding socket for metrics web server")?;
@@ -666,6 +867,9 @@ pub(crate) struct MetaData {
     /// CSS file to use depending on the rustdoc version used to generate this version of this
     /// crate.
     pub(crate) rustdoc_css_file: Option<String>,
+    /// When the build of the shown docs finished, and the rustc version it used.
+    pub(crate) build_finished: Option<DateTime<Utc>>,
+    pub(crate) rustc_version: Option<String>,
 }
 
 impl MetaData {
@@ -686,7 +890,8 @@ impl MetaData {
                 releases.default_target,
                 releases.doc_targets,
                 releases.yanked,
-                builds.rustc_version as "rustc_version?"
+                builds.rustc_version as "rustc_version?",
+                builds.build_finished as "build_finished?"
             FROM releases
             INNER JOIN crates ON crates.id = releases.crate_id
             LEFT JOIN LATERAL (
@@ -710,14 +915,18 @@ impl MetaData {
             description: row.description,
             target_name: row.target_name,
             rustdoc_status: row.rustdoc_status,
+            doc_targets: row.doc_targets.map(|targets| {
+                MetaData::parse_doc_targets_default_first(targets, row.default_target.as_deref())
+            }),
             default_target: row.default_target,
-            doc_targets: row.doc_targets.map(MetaData::parse_doc_targets),
             yanked: row.yanked,
             rustdoc_css_file: row
                 .rustc_version
                 .as_deref()
                 .map(get_correct_docsrs_style_file)
                 .transpose()?,
+            build_finished: row.build_finished,
+            rustc_version: row.rustc_version,
         })
     }
 
@@ -735,6 +944,32 @@ impl MetaData {
         targets
     }
 
+    /// Like [`MetaData::parse_doc_targets`], but with the default target first,
+    /// since that's the one most people are looking for.
+    fn parse_doc_targets_default_first(
+        targets: Value,
+        default_target: Option<&str>,
+    ) -> Vec<String> {
+        let mut targets = Self::parse_doc_targets(targets);
+        if let Some(pos) =
+            default_target.and_then(|default| targets.iter().position(|t| t == default))
+        {
+            targets[..=pos].rotate_right(1);
+        }
+        targets
+    }
+
+    /// The name of the documentation root of this release.
+    ///
+    /// Falls back to the crate name with dashes replaced by underscores, the same
+    /// normalization cargo applies to library targets, when no target name was recorded.
+    pub(crate) fn effective_target_name(&self) -> Cow<'_, str> {
+        match self.target_name {
+            Some(ref target_name) => Cow::Borrowed(target_name),
+            None => Cow::Owned(self.name.replace('-', "_")),
+        }
+    }
+
     fn target_name_url(&self) -> String {
         if let Some(ref target_name) = self.target_name {
             format!("{target_name}/index.html")
@@ -757,6 +992,8 @@ pub(crate) struct AxumErrorPage {
     /// The error message, displayed as a description
     pub message: Cow<'static, str>,
     pub status: StatusCode,
+    /// links to pages the user might have wanted instead, as `(label, url)`
+    pub suggestions: Vec<(String, String)>,
     pub csp_nonce: String,
 }
 
@@ -765,6 +1002,34 @@ impl_axum_webpage! {
     status = |err| err.status,
 }
 
+/// Minimal error page without the navigation, for pages embedded in other
+/// tools, for example in an iframe.
+#[derive(Template)]
+#[template(path = "error-embed.html")]
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct AxumEmbeddedErrorPage {
+    pub title: &'static str,
+    pub message: Cow<'static, str>,
+    pub status: StatusCode,
+    pub csp_nonce: String,
+}
+
+impl From<AxumErrorPage> for AxumEmbeddedErrorPage {
+    fn from(page: AxumErrorPage) -> Self {
+        Self {
+            title: page.title,
+            message: page.message,
+            status: page.status,
+            csp_nonce: page.csp_nonce,
+        }
+    }
+}
+
+impl_axum_webpage! {
+    AxumEmbeddedErrorPage,
+    status = |err| err.status,
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -791,6 +1056,7 @@ mod test {
         let mut conn = db.async_conn().await;
         let version = match_version(
             &mut conn,
+            &InstanceMetrics::new().unwrap(),
             "foo",
             &ReqVersion::from_str(v.unwrap_or_default()).unwrap(),
         )
@@ -998,6 +1264,31 @@ mod test {
         })
     }
 
+    #[test_case(true, false)]
+    #[test_case(false, true)]
+    fn matched_release_has_library_docs(binary: bool, expected: bool) {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .binary(binary)
+                .create()
+                .await?;
+
+            let mut conn = env.async_db().await.async_conn().await;
+            let matched_release = match_version(
+                &mut conn,
+                &env.instance_metrics(),
+                "foo",
+                &ReqVersion::Latest,
+            )
+            .await?;
+            assert_eq!(matched_release.has_library_docs(), expected);
+            Ok(())
+        })
+    }
+
     #[test]
     fn can_view_source() {
         async_wrapper(|env| async move {
@@ -1048,6 +1339,77 @@ mod test {
         });
     }
 
+    #[test_case("0", "0.2.1"; "major zero skips prereleases")]
+    #[test_case("1", "1.3.0"; "major")]
+    #[test_case("1.2", "1.3.0"; "partial version is a caret requirement")]
+    #[test_case("~1.2", "1.2.3"; "tilde")]
+    #[test_case("0.1", "0.1.0"; "zero minor")]
+    fn partial_versions_resolve_to_highest_match(req: &str, expected: &str) {
+        async_wrapper(|env| async move {
+            for v in [
+                "0.1.0",
+                "0.2.0",
+                "0.2.1",
+                "0.3.0-alpha.1",
+                "1.0.0",
+                "1.2.0",
+                "1.2.3",
+                "1.3.0",
+                "2.0.0-beta.1",
+            ] {
+                release(v, &env).await;
+            }
+
+            assert_eq!(
+                version(Some(req), env.async_db().await).await,
+                semver(expected)
+            );
+
+            let web = env.web_app().await;
+            web.assert_redirect(&format!("/foo/{req}/"), &format!("/foo/{expected}/foo/"))
+                .await?;
+            web.assert_redirect(
+                &format!("/crate/foo/{req}"),
+                &format!("/crate/foo/{expected}"),
+            )
+            .await?;
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn skipped_builds_are_matched_like_failed_ones() {
+        async_wrapper(|env| async move {
+            let db = env.async_db().await;
+
+            release("0.1.0", &env).await;
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.2.0")
+                .build_result_skipped()
+                .create()
+                .await?;
+
+            assert_eq!(version(None, db).await, semver("0.2.0"));
+            assert_eq!(version(Some("0.2.0"), db).await, exact("0.2.0"));
+
+            let mut conn = db.async_conn().await;
+            let matched_release = match_version(
+                &mut conn,
+                &env.instance_metrics(),
+                "foo",
+                &ReqVersion::Latest,
+            )
+            .await?;
+            assert_eq!(matched_release.build_status(), BuildStatus::Skipped);
+            assert!(!matched_release.has_library_docs());
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn platform_dropdown_not_shown_with_no_targets() {
         async_wrapper(|env| async move {
@@ -1106,6 +1468,38 @@ mod test {
         });
     }
 
+    fn fake_release_list(releases: &[(&str, BuildStatus, bool)]) -> Vec<Release> {
+        releases
+            .iter()
+            .enumerate()
+            .map(|(id, &(version, build_status, yanked))| Release {
+                id: ReleaseId(id as i32),
+                version: version.parse().unwrap(),
+                build_status,
+                yanked: Some(yanked),
+                is_library: Some(true),
+                rustdoc_status: Some(build_status == BuildStatus::Success),
+                target_name: Some("foo".into()),
+                downloads: None,
+            })
+            .collect()
+    }
+
+    #[test_case(&[("0.3.0", BuildStatus::Success, false), ("0.2.0", BuildStatus::Success, false)], Some("0.3.0"); "newest")]
+    #[test_case(&[("0.3.0-pre.1", BuildStatus::Success, false), ("0.2.0", BuildStatus::Success, false)], Some("0.2.0"); "skips prereleases")]
+    #[test_case(&[("0.3.0", BuildStatus::Success, true), ("0.2.0", BuildStatus::Success, false)], Some("0.2.0"); "skips yanked")]
+    #[test_case(&[("0.3.0", BuildStatus::Failure, false), ("0.2.0", BuildStatus::Success, false)], Some("0.2.0"); "skips failed")]
+    #[test_case(&[("0.3.0", BuildStatus::InProgress, false), ("0.2.0", BuildStatus::Success, false)], Some("0.2.0"); "skips in progress")]
+    #[test_case(&[("0.3.0-pre.2", BuildStatus::Success, false), ("0.3.0-pre.1", BuildStatus::Success, false)], None; "only prereleases")]
+    #[test_case(&[("0.3.0", BuildStatus::Success, true), ("0.2.0", BuildStatus::Success, true)], None; "all yanked")]
+    fn test_latest_stable(releases: &[(&str, BuildStatus, bool)], expected: Option<&str>) {
+        let releases = fake_release_list(releases);
+        assert_eq!(
+            latest_stable(&releases).map(|release| release.version.to_string()),
+            expected.map(str::to_owned)
+        );
+    }
+
     #[test]
     fn in_progress_releases_are_ignored_when_others_match() {
         async_wrapper(|env| async move {
@@ -1135,6 +1529,118 @@ mod test {
         })
     }
 
+    #[test]
+    fn exact_operator_matches_in_progress_release_and_canonicalizes() {
+        async_wrapper(|env| async move {
+            release("1.0.0", &env).await;
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("1.1.0")
+                .builds(vec![
+                    FakeBuild::default().build_status(BuildStatus::InProgress)
+                ])
+                .create()
+                .await?;
+
+            let db = env.async_db().await;
+            // `=` pins the version, while the caret requirement `1` skips the in-progress release
+            assert_eq!(version(Some("=1.1.0"), db).await, exact("1.1.0"));
+            assert_eq!(version(Some("1"), db).await, exact("1.0.0"));
+
+            let web = env.web_app().await;
+            web.assert_redirect("/crate/foo/=1.1.0", "/crate/foo/1.1.0")
+                .await?;
+            web.assert_redirect("/crate/foo/1", "/crate/foo/1.0.0")
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn version_match_outcomes_are_counted() {
+        async_wrapper(|env| async move {
+            release("1.0.0", &env).await;
+            env.fake_release()
+                .await
+                .name("yanked")
+                .version("1.0.0")
+                .yanked(true)
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            web.assert_success("/crate/foo/1.0.0").await?;
+            web.assert_success("/crate/foo/latest").await?;
+            web.assert_redirect_unchecked("/crate/foo/~1", "/crate/foo/1.0.0")
+                .await?;
+            // the crate page only accepts exact names
+            assert_eq!(
+                web.get("/crate/Foo/1.0.0").await?.status(),
+                StatusCode::NOT_FOUND
+            );
+            assert_eq!(
+                web.get("/crate/foo/2.0.0").await?.status(),
+                StatusCode::NOT_FOUND
+            );
+            assert_eq!(
+                web.get("/crate/nope/1.0.0").await?.status(),
+                StatusCode::NOT_FOUND
+            );
+            assert_eq!(
+                web.get("/crate/yanked/latest").await?.status(),
+                StatusCode::NOT_FOUND
+            );
+
+            let metrics = env.instance_metrics();
+            let count = |outcome: &str| {
+                metrics
+                    .version_match_outcomes
+                    .with_label_values(&[outcome])
+                    .get()
+            };
+            assert_eq!(count("exact"), 1);
+            assert_eq!(count("latest"), 1);
+            assert_eq!(count("semver"), 1);
+            assert_eq!(count("corrected_name"), 1);
+            assert_eq!(count("version_not_found"), 1);
+            assert_eq!(count("crate_not_found"), 1);
+            assert_eq!(count("all_yanked"), 1);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn compound_requirement_matches_and_canonicalizes() {
+        async_wrapper(|env| async move {
+            for v in ["0.9.0", "1.0.0", "1.4.0", "1.5.0", "2.0.0"] {
+                release(v, &env).await;
+            }
+
+            let db = env.async_db().await;
+            assert_eq!(version(Some(">1.0.0, <2.0.0"), db).await, exact("1.5.0"));
+            assert_eq!(version(Some(">=0.9.0, <1.5.0"), db).await, exact("1.4.0"));
+            assert_eq!(version(Some(">1.0.0, <1.4.0"), db).await, None);
+
+            let web = env.web_app().await;
+            web.assert_redirect("/crate/foo/%3E1.0.0,%20%3C2.0.0", "/crate/foo/1.5.0")
+                .await?;
+            web.assert_redirect("/crate/foo/%3E=0.9.0,%3C1.5.0", "/crate/foo/1.4.0")
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn exclusion_operator_is_not_supported() {
+        // the `semver` crate has no `!=` comparator, cargo doesn't support it either.
+        assert!("!=1.0.0".parse::<ReqVersion>().is_err());
+        assert!(">1.0.0, !=1.2.0".parse::<ReqVersion>().is_err());
+    }
+
     #[test]
     // https://github.com/rust-lang/docs.rs/issues/1682
     fn prereleases_are_considered_when_others_dont_match() {
@@ -1197,6 +1703,8 @@ mod test {
             ]),
             yanked: Some(false),
             rustdoc_css_file: Some("rustdoc.css".to_string()),
+            build_finished: None,
+            rustc_version: None,
         };
 
         let correct_json = json!({
@@ -1213,6 +1721,8 @@ mod test {
             ],
             "yanked": false,
             "rustdoc_css_file": "rustdoc.css",
+            "build_finished": null,
+            "rustc_version": null,
         });
 
         assert_eq!(correct_json, serde_json::to_value(&metadata).unwrap());
@@ -1232,6 +1742,8 @@ mod test {
             ],
             "yanked": false,
             "rustdoc_css_file": "rustdoc.css",
+            "build_finished": null,
+            "rustc_version": null,
         });
 
         assert_eq!(correct_json, serde_json::to_value(&metadata).unwrap());
@@ -1251,16 +1763,93 @@ mod test {
             ],
             "yanked": false,
             "rustdoc_css_file": "rustdoc.css",
+            "build_finished": null,
+            "rustc_version": null,
         });
 
         assert_eq!(correct_json, serde_json::to_value(&metadata).unwrap());
     }
 
+    fn metadata_with_target_name(name: &str, target_name: Option<&str>) -> MetaData {
+        MetaData {
+            name: name.to_string(),
+            version: "1.0.0".parse().unwrap(),
+            req_version: ReqVersion::Latest,
+            description: None,
+            target_name: target_name.map(ToOwned::to_owned),
+            rustdoc_status: Some(true),
+            default_target: None,
+            doc_targets: None,
+            yanked: Some(false),
+            rustdoc_css_file: None,
+            build_finished: None,
+            rustc_version: None,
+        }
+    }
+
+    #[test]
+    fn parse_doc_targets_puts_default_target_first() {
+        let targets = json!([
+            "x86_64-unknown-linux-gnu",
+            "aarch64-apple-darwin",
+            "x86_64-pc-windows-msvc",
+            "i686-unknown-linux-gnu",
+        ]);
+
+        assert_eq!(
+            MetaData::parse_doc_targets_default_first(
+                targets.clone(),
+                Some("x86_64-unknown-linux-gnu")
+            ),
+            vec![
+                "x86_64-unknown-linux-gnu",
+                "aarch64-apple-darwin",
+                "i686-unknown-linux-gnu",
+                "x86_64-pc-windows-msvc",
+            ]
+        );
+
+        // unknown or missing default targets keep the alphabetical order
+        for default_target in [None, Some("wasm32-unknown-unknown")] {
+            assert_eq!(
+                MetaData::parse_doc_targets_default_first(targets.clone(), default_target),
+                vec![
+                    "aarch64-apple-darwin",
+                    "i686-unknown-linux-gnu",
+                    "x86_64-pc-windows-msvc",
+                    "x86_64-unknown-linux-gnu",
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn effective_target_name_prefers_explicit_target_name() {
+        let metadata = metadata_with_target_name("foo-bar", Some("foo_lib"));
+        assert_eq!(metadata.effective_target_name(), "foo_lib");
+        assert_eq!(metadata.target_name_url(), "foo_lib/index.html");
+    }
+
+    #[test]
+    fn effective_target_name_falls_back_to_normalized_crate_name() {
+        let metadata = metadata_with_target_name("foo-bar", None);
+        assert_eq!(metadata.effective_target_name(), "foo_bar");
+        assert_eq!(metadata.target_name_url(), "");
+    }
+
     #[test]
     fn metadata_from_crate() {
         async_wrapper(|env| async move {
-            release("0.1.0", &env).await;
+            let release_id = release("0.1.0", &env).await;
             let mut conn = env.async_db().await.async_conn().await;
+            let build_finished = sqlx::query_scalar!(
+                "SELECT build_finished FROM builds WHERE rid = $1",
+                release_id.0
+            )
+            .fetch_one(&mut *conn)
+            .await?;
+            assert!(build_finished.is_some());
+
             let metadata = MetaData::from_crate(
                 &mut conn,
                 "foo",
@@ -1281,6 +1870,8 @@ mod test {
                     doc_targets: Some(vec![]),
                     yanked: Some(false),
                     rustdoc_css_file: Some("rustdoc.css".to_string()),
+                    build_finished,
+                    rustc_version: Some("rustc 2.0.0-nightly (000000000 1970-01-01)".to_string()),
                 },
             );
             Ok(())
@@ -1359,6 +1950,8 @@ mod test {
     }
 
     #[test_case("^1.2.3")]
+    #[test_case("=1.2.3")]
+    #[test_case(">1.0.0, <2.0.0")]
     #[test_case("*")]
     fn test_parse_req_version_semver(input: &str) {
         let req_version: ReqVersion = input.parse().unwrap();
@@ -1377,6 +1970,30 @@ mod test {
         assert_eq!(req_version.to_string(), "*")
     }
 
+    #[test_case("", ReqVersion::Semver(VersionReq::STAR))]
+    #[test_case("newest", ReqVersion::Semver(VersionReq::STAR))]
+    #[test_case("latest", ReqVersion::Latest)]
+    #[test_case("1.2.3", ReqVersion::Exact(Version::new(1, 2, 3)))]
+    #[test_case("~1.2", ReqVersion::Semver(VersionReq::parse("~1.2").unwrap()))]
+    fn test_parse_req_version_with_raw(input: &str, expected: ReqVersion) {
+        let parsed = ReqVersion::parse_with_raw(input).unwrap();
+        assert_eq!(parsed.req, expected);
+        assert_eq!(parsed.raw, input);
+    }
+
+    #[test]
+    fn test_parse_req_version_with_raw_keeps_distinct_input() {
+        let newest = ReqVersion::parse_with_raw("newest").unwrap();
+        let empty = ReqVersion::parse_with_raw("").unwrap();
+        assert_eq!(newest.req, empty.req);
+        assert_ne!(newest.raw, empty.raw);
+    }
+
+    #[test]
+    fn test_parse_req_version_with_raw_invalid() {
+        assert!(ReqVersion::parse_with_raw("not a version").is_err());
+    }
+
     #[test_case("/something/", "/something/")] // already valid path
     #[test_case("/something>", "/something%3E")] // something to encode
     #[test_case("/something%3E", "/something%3E")] // re-running doesn't change anything
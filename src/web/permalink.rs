@@ -0,0 +1,427 @@
+use super::{
+    axum_cached_redirect,
+    cache::CachePolicy,
+    encode_url_path,
+    error::{AxumNope, AxumResult},
+    extractors::{DbConnection, Path},
+    headers::CanonicalUrl,
+    match_version, ReqVersion,
+};
+use crate::{
+    db::{BuildId, ReleaseId},
+    AsyncStorage, Config, InstanceMetrics,
+};
+use anyhow::{anyhow, Result};
+use axum::{extract::Extension, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+const SHORT_ID_LENGTH: usize = 8;
+const BASE62: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+/// random bytes at or above this are skipped, so every character is equally likely.
+const UNBIASED_LIMIT: u8 = (256 / BASE62.len() * BASE62.len()) as u8;
+/// the longest doc path we create permalinks for.
+const MAX_PATH_LENGTH: usize = 1024;
+
+fn generate_short_id() -> String {
+    let mut id = String::with_capacity(SHORT_ID_LENGTH);
+    let mut random = [0u8; SHORT_ID_LENGTH * 2];
+    while id.len() < SHORT_ID_LENGTH {
+        getrandom::getrandom(&mut random).expect("failed to generate a permalink id");
+        let missing = SHORT_ID_LENGTH - id.len();
+        id.extend(
+            random
+                .iter()
+                .filter(|&&b| b < UNBIASED_LIMIT)
+                .map(|&b| BASE62[b as usize % BASE62.len()] as char)
+                .take(missing),
+        );
+    }
+    id
+}
+
+/// Whether the doc path exists in the release, so we only create permalinks
+/// for real pages.
+///
+/// The empty path is the crate root, which always exists.
+async fn doc_path_exists(
+    conn: &mut sqlx::PgConnection,
+    storage: &AsyncStorage,
+    name: &str,
+    version: &str,
+    release_id: ReleaseId,
+    path: &str,
+) -> Result<bool> {
+    if path.is_empty() {
+        return Ok(true);
+    }
+
+    let release = sqlx::query!(
+        r#"SELECT
+            releases.archive_storage,
+            releases.default_target,
+            (
+                SELECT id
+                FROM builds
+                WHERE
+                    builds.rid = releases.id AND
+                    builds.build_status = 'success'
+                ORDER BY build_finished DESC
+                LIMIT 1
+            ) AS "latest_build_id?: BuildId"
+         FROM releases
+         WHERE releases.id = $1"#,
+        release_id.0,
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    // the docs for the default target are stored without the target prefix
+    let path = release
+        .default_target
+        .as_deref()
+        .and_then(|target| path.strip_prefix(target)?.strip_prefix('/'))
+        .unwrap_or(path);
+    let storage_path = if path.is_empty() || path.ends_with('/') {
+        format!("{path}index.html")
+    } else {
+        path.to_owned()
+    };
+
+    storage
+        .rustdoc_file_exists(
+            name,
+            version,
+            release.latest_build_id,
+            &storage_path,
+            release.archive_storage,
+        )
+        .await
+}
+
+/// get the permalink id for a path inside a release, creating it when it
+/// doesn't exist yet.
+pub(crate) async fn get_or_create_permalink(
+    conn: &mut sqlx::PgConnection,
+    release_id: ReleaseId,
+    path: &str,
+) -> Result<String> {
+    loop {
+        if let Some(short_id) = sqlx::query_scalar!(
+            "SELECT short_id FROM permalinks WHERE release_id = $1 AND path = $2",
+            release_id.0,
+            path,
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        {
+            return Ok(short_id);
+        }
+
+        // when the insert conflicts, either the generated id is already taken,
+        // or a concurrent request created the permalink. Both are handled in
+        // the next loop iteration.
+        if let Some(short_id) = sqlx::query_scalar!(
+            "INSERT INTO permalinks (short_id, release_id, path)
+             VALUES ($1, $2, $3)
+             ON CONFLICT DO NOTHING
+             RETURNING short_id",
+            generate_short_id(),
+            release_id.0,
+            path,
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        {
+            return Ok(short_id);
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PermalinkRequest {
+    name: String,
+    version: ReqVersion,
+    #[serde(default)]
+    path: String,
+}
+
+#[derive(Debug, Serialize)]
+struct PermalinkResponse {
+    id: String,
+    url: String,
+}
+
+/// create a permalink for a specific crate release & doc path.
+///
+/// Version requirements are resolved to the matching release, so the
+/// permalink always points to the same version. The path has to exist in
+/// the release's documentation.
+pub(crate) async fn create_permalink_handler(
+    Extension(config): Extension<Arc<Config>>,
+    Extension(storage): Extension<Arc<AsyncStorage>>,
+    mut conn: DbConnection,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
+    Json(request): Json<PermalinkRequest>,
+) -> AxumResult<impl IntoResponse> {
+    if request.path.len() > MAX_PATH_LENGTH {
+        return Err(AxumNope::BadRequest(anyhow!(
+            "path is longer than {MAX_PATH_LENGTH} bytes"
+        )));
+    }
+
+    let matched_release = match_version(&mut conn, &metrics, &request.name, &request.version)
+        .await?
+        .assume_exact_name()?;
+
+    let path = request.path.trim_start_matches('/');
+    if !doc_path_exists(
+        &mut conn,
+        &storage,
+        &matched_release.name,
+        &matched_release.version().to_string(),
+        matched_release.id(),
+        path,
+    )
+    .await?
+    {
+        return Err(AxumNope::ResourceNotFound);
+    }
+
+    let id = get_or_create_permalink(&mut conn, matched_release.id(), path).await?;
+
+    let url = CanonicalUrl::from_path(&config.external_base_url, format!("/-/s/{id}"))
+        .build_full_uri()
+        .to_string();
+
+    Ok((
+        Extension(CachePolicy::NoCaching),
+        Json(PermalinkResponse { id, url }),
+    ))
+}
+
+/// redirect a permalink to the doc page it was created for.
+///
+/// The redirect is only cached for a short time in the CDN, so fixing or
+/// removing a permalink takes effect without a purge.
+pub(crate) async fn permalink_redirect_handler(
+    Path(short_id): Path<String>,
+    mut conn: DbConnection,
+) -> AxumResult<impl IntoResponse> {
+    let target = sqlx::query!(
+        "SELECT
+            crates.name,
+            releases.version,
+            permalinks.path
+         FROM permalinks
+         INNER JOIN releases ON releases.id = permalinks.release_id
+         INNER JOIN crates ON crates.id = releases.crate_id
+         WHERE permalinks.short_id = $1",
+        short_id,
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AxumNope::ResourceNotFound)?;
+
+    Ok(axum_cached_redirect(
+        encode_url_path(&format!(
+            "/{}/{}/{}",
+            target.name, target.version, target.path
+        )),
+        CachePolicy::ShortInCdnLongInBrowser,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{async_wrapper, AxumResponseTestExt, AxumRouterTestExt};
+    use axum::{body::Body, http::Request};
+    use reqwest::StatusCode;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    fn create_request(body: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/-/permalink")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_owned()))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_generate_short_id() {
+        let id = generate_short_id();
+        assert_eq!(id.len(), SHORT_ID_LENGTH);
+        assert!(id.chars().all(|c| c.is_ascii_alphanumeric()));
+    }
+
+    #[test]
+    fn test_unbiased_limit() {
+        assert_eq!(UNBIASED_LIMIT, 248);
+        assert_eq!(UNBIASED_LIMIT as usize % BASE62.len(), 0);
+    }
+
+    #[test]
+    fn create_and_resolve_permalink() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .rustdoc_file("foo/struct.Bar.html")
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.2.0")
+                .create()
+                .await?;
+            let web = env.web_app().await;
+
+            let response = web
+                .clone()
+                .oneshot(create_request(
+                    r#"{"name": "foo", "version": "0.1", "path": "/foo/struct.Bar.html"}"#,
+                ))
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+            let created: Value = response.json().await?;
+            let id = created["id"].as_str().unwrap().to_owned();
+            assert_eq!(
+                created["url"],
+                format!("{}-/s/{id}", env.config().external_base_url)
+            );
+
+            // creating it again returns the same id
+            let response = web
+                .clone()
+                .oneshot(create_request(
+                    r#"{"name": "foo", "version": "0.1.0", "path": "foo/struct.Bar.html"}"#,
+                ))
+                .await?;
+            let again: Value = response.json().await?;
+            assert_eq!(again["id"], id);
+
+            web.assert_redirect_cached_unchecked(
+                &format!("/-/s/{id}"),
+                "/foo/0.1.0/foo/struct.Bar.html",
+                CachePolicy::ShortInCdnLongInBrowser,
+                &env.config(),
+            )
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn permalink_url_keeps_base_url_path() {
+        async_wrapper(|env| async move {
+            env.override_config(|config| {
+                config.external_base_url = "https://mirror.example/docs/".parse().unwrap();
+            });
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .rustdoc_file("foo/struct.Bar.html")
+                .create()
+                .await?;
+            let web = env.web_app().await;
+
+            let response = web
+                .oneshot(create_request(
+                    r#"{"name": "foo", "version": "0.1.0", "path": "foo/struct.Bar.html"}"#,
+                ))
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+            let created: Value = response.json().await?;
+            let id = created["id"].as_str().unwrap();
+            assert_eq!(
+                created["url"],
+                format!("https://mirror.example/docs/-/s/{id}")
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn create_permalink_for_missing_path() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .rustdoc_file("foo/struct.Bar.html")
+                .create()
+                .await?;
+            let web = env.web_app().await;
+
+            let response = web
+                .clone()
+                .oneshot(create_request(
+                    r#"{"name": "foo", "version": "0.1.0", "path": "foo/struct.Missing.html"}"#,
+                ))
+                .await?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            let long_path = "a/".repeat(MAX_PATH_LENGTH);
+            let response = web
+                .clone()
+                .oneshot(create_request(&format!(
+                    r#"{{"name": "foo", "version": "0.1.0", "path": "{long_path}"}}"#
+                )))
+                .await?;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            // the crate root and paths with the default target prefix exist
+            for path in ["", "x86_64-unknown-linux-gnu/foo/struct.Bar.html"] {
+                let response = web
+                    .clone()
+                    .oneshot(create_request(&format!(
+                        r#"{{"name": "foo", "version": "0.1.0", "path": "{path}"}}"#
+                    )))
+                    .await?;
+                assert_eq!(response.status(), StatusCode::OK, "{path}");
+            }
+
+            let mut conn = env.async_db().await.async_conn().await;
+            assert_eq!(
+                sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM permalinks"#)
+                    .fetch_one(&mut *conn)
+                    .await?,
+                2
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn create_permalink_for_unknown_crate() {
+        async_wrapper(|env| async move {
+            let response = env
+                .web_app()
+                .await
+                .oneshot(create_request(r#"{"name": "foo", "version": "0.1.0"}"#))
+                .await?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn unknown_permalink_is_not_found() {
+        async_wrapper(|env| async move {
+            env.web_app()
+                .await
+                .assert_not_found("/-/s/unknown1")
+                .await?;
+            Ok(())
+        })
+    }
+}
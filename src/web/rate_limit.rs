@@ -0,0 +1,297 @@
+use crate::{config::Config, web::cache::CachePolicy};
+use axum::{
+    extract::{ConnectInfo, Extension, Request as AxumHttpRequest, State},
+    http::{header::RETRY_AFTER, HeaderName, StatusCode},
+    middleware::Next,
+    response::{IntoResponse as _, Response as AxumResponse},
+};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// paths that are never rate limited, so monitoring can always reach us.
+const EXEMPT_PATHS: &[&str] = &[
+    "/about/metrics",
+    "/about/metrics/instance",
+    "/about/metrics/service",
+    // the Prometheus scrape path on the metrics web server
+    "/metrics",
+    // health check for the storage backend
+    "/-/storage-change-detection.html",
+];
+
+/// the maximum number of clients we track. When we're at the limit,
+/// new clients aren't limited until the next sweep makes room.
+const MAX_TRACKED_CLIENTS: usize = 100_000;
+
+/// how often we drop the buckets that are full again, since they
+/// are equivalent to a new bucket.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Per-client-IP token bucket rate limiter.
+///
+/// Every client starts with `burst` tokens, each request takes one token,
+/// and tokens are refilled with `rate` tokens per second, up to `burst`.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    ip_header: Option<HeaderName>,
+    max_clients: usize,
+    buckets: Mutex<Buckets>,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    clients: HashMap<IpAddr, Bucket>,
+    last_sweep: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(rate: f64, burst: u32, ip_header: Option<HeaderName>) -> Self {
+        Self {
+            rate,
+            burst: burst.into(),
+            ip_header,
+            max_clients: MAX_TRACKED_CLIENTS,
+            buckets: Mutex::new(Buckets {
+                clients: HashMap::new(),
+                last_sweep: Instant::now(),
+            }),
+        }
+    }
+
+    /// Build the rate limiter from the config, if rate limiting is enabled.
+    pub(crate) fn from_config(config: &Config) -> Option<Self> {
+        let rate = config.rate_limit_per_second?;
+        Some(Self::new(
+            rate,
+            config.rate_limit_burst,
+            config.rate_limit_ip_header.clone(),
+        ))
+    }
+
+    /// Find the client IP for a request.
+    ///
+    /// When an IP header is configured, we use the last address in it, which is the one
+    /// added by our own proxy. Earlier addresses are client-controlled.
+    /// Otherwise we fall back to the address of the socket.
+    fn client_ip(&self, req: &AxumHttpRequest) -> Option<IpAddr> {
+        if let Some(ref header) = self.ip_header {
+            return req
+                .headers()
+                .get(header)?
+                .to_str()
+                .ok()?
+                .rsplit(',')
+                .next()?
+                .trim()
+                .parse()
+                .ok();
+        }
+
+        req.extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+    }
+
+    /// Take a token for the given client.
+    ///
+    /// Returns `Err` with the number of seconds until the next token is available
+    /// when the client exceeded its limit.
+    fn check(&self, ip: IpAddr, now: Instant) -> Result<(), u64> {
+        let mut buckets = self.buckets.lock().unwrap();
+
+        // sweeping is linear in the number of clients, so it only happens once
+        // per interval, independent of how many clients we see.
+        if now.duration_since(buckets.last_sweep) >= SWEEP_INTERVAL {
+            let (rate, burst) = (self.rate, self.burst);
+            buckets.clients.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.last_refill).as_secs_f64() * rate < burst
+            });
+            buckets.last_sweep = now;
+        }
+
+        if buckets.clients.len() >= self.max_clients && !buckets.clients.contains_key(&ip) {
+            return Ok(());
+        }
+
+        let bucket = buckets.clients.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            last_refill: now,
+        });
+
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let seconds = ((1.0 - bucket.tokens) / self.rate).ceil();
+            Err((seconds as u64).max(1))
+        }
+    }
+}
+
+pub(crate) async fn rate_limit_middleware(
+    State(limiter): State<Arc<RateLimiter>>,
+    req: AxumHttpRequest,
+    next: Next,
+) -> AxumResponse {
+    if EXEMPT_PATHS.contains(&req.uri().path()) {
+        return next.run(req).await;
+    }
+
+    if let Some(ip) = limiter.client_ip(&req) {
+        if let Err(retry_after) = limiter.check(ip, Instant::now()) {
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after.to_string())],
+                Extension(CachePolicy::NoStoreMustRevalidate),
+                "Too Many Requests",
+            )
+                .into_response();
+        }
+    }
+
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::async_wrapper;
+    use axum::{body::Body, http::header::CACHE_CONTROL};
+    use tower::ServiceExt as _;
+
+    fn request(path: &str, ip: &str) -> AxumHttpRequest {
+        AxumHttpRequest::builder()
+            .uri(path)
+            .header("X-Forwarded-For", ip)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let limiter = RateLimiter::new(1.0, 2, None);
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let start = Instant::now();
+
+        assert!(limiter.check(ip, start).is_ok());
+        assert!(limiter.check(ip, start).is_ok());
+        assert_eq!(limiter.check(ip, start), Err(1));
+
+        assert!(limiter.check(ip, start + Duration::from_secs(1)).is_ok());
+        assert!(limiter.check(ip, start + Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn full_buckets_are_swept_periodically() {
+        let mut limiter = RateLimiter::new(1.0, 1, None);
+        limiter.max_clients = 2;
+        let start = limiter.buckets.lock().unwrap().last_sweep;
+        let ip = |last: u8| IpAddr::from([10, 0, 0, last]);
+
+        assert!(limiter.check(ip(1), start).is_ok());
+        assert!(limiter.check(ip(2), start).is_ok());
+        // at the limit, new clients are let through untracked
+        assert!(limiter.check(ip(3), start).is_ok());
+        assert!(limiter.check(ip(3), start).is_ok());
+        assert_eq!(limiter.buckets.lock().unwrap().clients.len(), 2);
+        // known clients are still limited
+        assert!(limiter.check(ip(1), start).is_err());
+
+        // after the sweep interval, the refilled buckets are gone
+        let later = start + SWEEP_INTERVAL;
+        assert!(limiter.check(ip(3), later).is_ok());
+        assert_eq!(
+            limiter
+                .buckets
+                .lock()
+                .unwrap()
+                .clients
+                .keys()
+                .collect::<Vec<_>>(),
+            [&ip(3)]
+        );
+        assert!(limiter.check(ip(3), later).is_err());
+    }
+
+    #[test]
+    fn limits_per_client_ip() {
+        async_wrapper(|env| async move {
+            env.override_config(|config| {
+                config.rate_limit_per_second = Some(0.01);
+                config.rate_limit_burst = 3;
+                config.rate_limit_ip_header = Some(HeaderName::from_static("x-forwarded-for"));
+            });
+            let web = env.web_app().await;
+
+            for _ in 0..3 {
+                let response = web.clone().oneshot(request("/about", "10.0.0.1")).await?;
+                assert_eq!(response.status(), StatusCode::OK);
+            }
+
+            let response = web.clone().oneshot(request("/about", "10.0.0.1")).await?;
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(response.headers()[RETRY_AFTER], "100");
+            assert_eq!(
+                response.headers().get(CACHE_CONTROL),
+                CachePolicy::NoStoreMustRevalidate
+                    .render(&env.config())
+                    .as_ref()
+            );
+
+            // the proxy-added address is the one we limit on
+            let response = web
+                .clone()
+                .oneshot(request("/about", "10.0.0.2, 10.0.0.1"))
+                .await?;
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+            let response = web.clone().oneshot(request("/about", "10.0.0.2")).await?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn metrics_are_exempt() {
+        async_wrapper(|env| async move {
+            env.override_config(|config| {
+                config.rate_limit_per_second = Some(0.01);
+                config.rate_limit_burst = 1;
+                config.rate_limit_ip_header = Some(HeaderName::from_static("x-forwarded-for"));
+            });
+            let web = env.web_app().await;
+
+            for path in ["/about/metrics", "/-/storage-change-detection.html"] {
+                for _ in 0..3 {
+                    let response = web.clone().oneshot(request(path, "10.0.0.1")).await?;
+                    assert_eq!(response.status(), StatusCode::OK);
+                }
+            }
+
+            // other routes are still limited for the same client
+            let response = web.clone().oneshot(request("/about", "10.0.0.1")).await?;
+            assert_eq!(response.status(), StatusCode::OK);
+            let response = web.clone().oneshot(request("/about", "10.0.0.1")).await?;
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+            Ok(())
+        })
+    }
+}
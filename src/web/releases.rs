@@ -3,10 +3,11 @@
 use crate::{
     build_queue::{QueuedCrate, REBUILD_PRIORITY},
     cdn, impl_axum_webpage,
+    registry_api::OwnerKind,
     utils::report_error,
     web::{
         axum_parse_uri_with_params, axum_redirect, encode_url_path,
-        error::{AxumNope, AxumResult},
+        error::{AxumNope, AxumResult, JsonAxumNope, JsonAxumResult},
         extractors::{DbConnection, Path},
         match_version,
         page::templates::{filters, RenderRegular, RenderSolid},
@@ -18,6 +19,7 @@ use anyhow::{anyhow, Context as _, Result};
 use axum::{
     extract::{Extension, Query},
     response::{IntoResponse, Response as AxumResponse},
+    Json,
 };
 use base64::{engine::general_purpose::STANDARD as b64, Engine};
 use chrono::{DateTime, Utc};
@@ -74,7 +76,8 @@ pub(crate) async fn get_releases(
     order: Order,
     latest_only: bool,
 ) -> Result<Vec<Release>> {
-    let offset = (page - 1) * limit;
+    // huge page numbers just lead to an empty page
+    let offset = (page - 1).saturating_mul(limit);
 
     // WARNING: it is _crucial_ that this always be hard-coded and NEVER be user input
     let (ordering, filter_failed): (&'static str, _) = match order {
@@ -302,6 +305,7 @@ pub(crate) enum ReleaseType {
     RecentFailures,
     Failures,
     Search,
+    Owner,
 }
 
 impl PartialEq<&str> for ReleaseType {
@@ -323,6 +327,7 @@ impl ReleaseType {
             Self::RecentFailures => "recent_failures",
             Self::Failures => "failures",
             Self::Search => "search",
+            Self::Owner => "owner",
         }
     }
 }
@@ -348,8 +353,8 @@ pub(crate) async fn releases_handler(
             true,
         ),
 
-        ReleaseType::Search => {
-            panic!("The search page has special requirements and cannot use this handler",)
+        ReleaseType::Search | ReleaseType::Owner => {
+            panic!("The {release_type:?} page has special requirements and cannot use this handler",)
         }
     };
 
@@ -548,14 +553,14 @@ pub(crate) async fn search_handler(
         // since we never pass a version into `match_version` here, we'll never get
         // `MatchVersion::Exact`, so the distinction between `Exact` and `Semver` doesn't
         // matter
-        if let Ok(matchver) = match_version(&mut conn, krate, &ReqVersion::Latest)
+        if let Ok(matchver) = match_version(&mut conn, &metrics, krate, &ReqVersion::Latest)
             .await
             .map(|matched_release| matched_release.into_exactly_named())
         {
             params.remove("query");
             queries.extend(params);
 
-            let uri = if matchver.rustdoc_status() {
+            let uri = if matchver.has_library_docs() {
                 axum_parse_uri_with_params(
                     &format!(
                         "/{}/{}/{}/",
@@ -563,7 +568,7 @@ pub(crate) async fn search_handler(
                         matchver.version(),
                         matchver
                             .target_name()
-                            .expect("target name will exist when rustdoc_status is true"),
+                            .expect("target name will exist when we have library docs"),
                     ),
                     queries,
                 )?
@@ -578,21 +583,7 @@ pub(crate) async fn search_handler(
     }
 
     let search_result = if let Some(paginate) = params.get("paginate") {
-        let decoded = b64.decode(paginate.as_bytes()).map_err(|e| {
-            warn!("error when decoding pagination base64 string \"{paginate}\": {e:?}");
-            AxumNope::NoResults
-        })?;
-        let query_params = String::from_utf8_lossy(&decoded);
-        let query_params = query_params.strip_prefix('?').ok_or_else(|| {
-            // sometimes we see plain bytes being passed to `paginate`.
-            // In these cases we just return `NoResults` and don't call
-            // the crates.io API.
-            // The whole point of the `paginate` design is that we don't
-            // know anything about the pagination args and crates.io can
-            // change them as they wish, so we cannot do any more checks here.
-            warn!("didn't get query args in `paginate` arguments for search: \"{query_params}\"");
-            AxumNope::NoResults
-        })?;
+        let query_params = decode_paginate(paginate).ok_or(AxumNope::NoResults)?;
 
         for (k, v) in form_urlencoded::parse(query_params.as_bytes()) {
             match &*k {
@@ -602,7 +593,7 @@ pub(crate) async fn search_handler(
             }
         }
 
-        get_search_results(&mut conn, &registry, query_params).await?
+        get_search_results(&mut conn, &registry, &query_params).await?
     } else if !query.is_empty() {
         let query_params: String = form_urlencoded::Serializer::new(String::new())
             .append_pair("q", &query)
@@ -637,6 +628,280 @@ pub(crate) async fn search_handler(
     .into_response())
 }
 
+/// decode the crates.io pagination arguments we pass around base64-encoded.
+fn decode_paginate(paginate: &str) -> Option<String> {
+    let decoded = b64
+        .decode(paginate.as_bytes())
+        .map_err(|e| {
+            warn!("error when decoding pagination base64 string \"{paginate}\": {e:?}");
+        })
+        .ok()?;
+    let query_params = String::from_utf8_lossy(&decoded);
+    let Some(query_params) = query_params.strip_prefix('?') else {
+        // sometimes we see plain bytes being passed to `paginate`.
+        // In these cases we just return `NoResults` and don't call
+        // the crates.io API.
+        // The whole point of the `paginate` design is that we don't
+        // know anything about the pagination args and crates.io can
+        // change them as they wish, so we cannot do any more checks here.
+        warn!("didn't get query args in `paginate` arguments for search: \"{query_params}\"");
+        return None;
+    };
+    Some(query_params.to_owned())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SearchJsonParams {
+    #[serde(default)]
+    q: String,
+    /// opaque pagination cursor from a previous response.
+    page: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct SearchJsonCrate {
+    name: String,
+    /// latest version known to docs.rs, if any
+    version: Option<String>,
+    has_docs: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct SearchJsonResponse {
+    crates: Vec<SearchJsonCrate>,
+    next_page: Option<String>,
+    prev_page: Option<String>,
+}
+
+/// crates.io search, extended with the docs.rs documentation status for
+/// each crate.
+pub(crate) async fn search_json_handler(
+    mut conn: DbConnection,
+    Extension(registry): Extension<Arc<RegistryApi>>,
+    Query(params): Query<SearchJsonParams>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let query_params = if let Some(page) = params.page {
+        decode_paginate(&page)
+            .ok_or_else(|| JsonAxumNope(AxumNope::BadRequest(anyhow!("invalid page cursor"))))?
+    } else if !params.q.is_empty() {
+        form_urlencoded::Serializer::new(String::new())
+            .append_pair("q", &params.q)
+            .append_pair("per_page", &RELEASES_IN_RELEASES.to_string())
+            .finish()
+    } else {
+        return Err(JsonAxumNope(AxumNope::NoResults));
+    };
+
+    let search_result = get_search_results(&mut conn, &registry, &query_params)
+        .await
+        .map_err(|err| JsonAxumNope(err.into()))?;
+
+    let page_link = |params: String| format!("/-/search?page={}", b64.encode(params));
+
+    Ok((
+        Extension(CachePolicy::ShortInCdnAndBrowser),
+        Json(SearchJsonResponse {
+            crates: search_result
+                .results
+                .into_iter()
+                .map(|status| match status {
+                    ReleaseStatus::Available(release) => SearchJsonCrate {
+                        name: release.name,
+                        version: Some(release.version),
+                        has_docs: release.rustdoc_status,
+                    },
+                    ReleaseStatus::NotAvailable(name) => SearchJsonCrate {
+                        name,
+                        version: None,
+                        has_docs: false,
+                    },
+                })
+                .collect(),
+            next_page: search_result.next_page.map(page_link),
+            prev_page: search_result.prev_page.map(page_link),
+        }),
+    ))
+}
+
+/// The latest releases of all crates owned by a crates.io user or team.
+struct OwnerCrates {
+    /// `None` when we don't know this owner.
+    kind: Option<OwnerKind>,
+    releases: Vec<Release>,
+    has_next_page: bool,
+}
+
+async fn get_owner_crates(
+    conn: &mut sqlx::PgConnection,
+    login: &str,
+    page: i64,
+) -> Result<OwnerCrates> {
+    let kind = sqlx::query_scalar!(
+        r#"SELECT kind as "kind: OwnerKind" FROM owners WHERE login = $1"#,
+        login
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let mut releases: Vec<Release> = sqlx::query!(
+        r#"SELECT
+            crates.name,
+            releases.version,
+            releases.description,
+            releases.target_name,
+            releases.rustdoc_status,
+            release_build_status.last_build_time,
+            repositories.stars as "stars?"
+        FROM owners
+        INNER JOIN owner_rels ON owner_rels.oid = owners.id
+        INNER JOIN crates ON crates.id = owner_rels.cid
+        INNER JOIN releases ON releases.id = crates.latest_version_id
+        INNER JOIN release_build_status ON releases.id = release_build_status.rid
+        LEFT JOIN repositories ON releases.repository_id = repositories.id
+        WHERE owners.login = $1
+        ORDER BY crates.name
+        LIMIT $2 OFFSET $3"#,
+        login,
+        // one more than we show, to know if there is a next page
+        RELEASES_IN_RELEASES + 1,
+        (page - 1) * RELEASES_IN_RELEASES,
+    )
+    .fetch(&mut *conn)
+    .map_ok(|row| Release {
+        name: row.name,
+        version: row.version,
+        description: row.description,
+        target_name: row.target_name,
+        rustdoc_status: row.rustdoc_status.unwrap_or(false),
+        build_time: row.last_build_time,
+        stars: row.stars.unwrap_or(0),
+        has_unyanked_releases: None,
+    })
+    .try_collect()
+    .await?;
+
+    let has_next_page = releases.len() > RELEASES_IN_RELEASES as usize;
+    releases.truncate(RELEASES_IN_RELEASES as usize);
+
+    Ok(OwnerCrates {
+        kind,
+        releases,
+        has_next_page,
+    })
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct OwnerParams {
+    page: Option<i64>,
+}
+
+impl OwnerParams {
+    fn page(&self) -> Result<i64, AxumNope> {
+        match self.page {
+            Some(page) if page < 1 => Err(AxumNope::BadRequest(anyhow!("invalid page number"))),
+            // the offset of pages this far out doesn't fit into an `i64`,
+            // there can't be any crates on them.
+            Some(page) if (page - 1).checked_mul(RELEASES_IN_RELEASES).is_none() => {
+                Err(AxumNope::ResourceNotFound)
+            }
+            page => Ok(page.unwrap_or(1)),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "releases/owner.html")]
+#[derive(Debug, Clone, PartialEq)]
+struct OwnerReleases {
+    login: String,
+    description: String,
+    releases: Vec<ReleaseStatus>,
+    previous_page_link: Option<String>,
+    next_page_link: Option<String>,
+    /// This should always be `ReleaseType::Owner`
+    release_type: ReleaseType,
+    csp_nonce: String,
+}
+
+impl_axum_webpage! { OwnerReleases }
+
+/// all crates owned by a crates.io user or team.
+///
+/// An unknown login just leads to an empty list.
+pub(crate) async fn owner_crates_handler(
+    Path(login): Path<String>,
+    Query(params): Query<OwnerParams>,
+    mut conn: DbConnection,
+) -> AxumResult<impl IntoResponse> {
+    let page = params.page()?;
+    let owner = get_owner_crates(&mut conn, &login, page).await?;
+
+    let page_link = |page: i64| format!("/-/owner/{}?page={page}", encode_url_path(&login));
+
+    Ok(OwnerReleases {
+        description: match owner.kind {
+            Some(kind) => format!("Crates owned by {kind} {login}"),
+            None => format!("Crates owned by {login}"),
+        },
+        releases: owner
+            .releases
+            .into_iter()
+            .map(ReleaseStatus::Available)
+            .collect(),
+        previous_page_link: (page > 1).then(|| page_link(page - 1)),
+        next_page_link: owner.has_next_page.then(|| page_link(page + 1)),
+        release_type: ReleaseType::Owner,
+        login,
+        csp_nonce: String::new(),
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct OwnerJsonResponse {
+    login: String,
+    kind: Option<OwnerKind>,
+    crates: Vec<SearchJsonCrate>,
+    next_page: Option<String>,
+    prev_page: Option<String>,
+}
+
+pub(crate) async fn owner_crates_json_handler(
+    Path(login): Path<String>,
+    Query(params): Query<OwnerParams>,
+    mut conn: DbConnection,
+) -> JsonAxumResult<impl IntoResponse> {
+    let page = params.page().map_err(JsonAxumNope)?;
+    let owner = get_owner_crates(&mut conn, &login, page)
+        .await
+        .map_err(|err| JsonAxumNope(err.into()))?;
+
+    let page_link = |page: i64| {
+        format!(
+            "/-/owner/{}/crates.json?page={page}",
+            encode_url_path(&login)
+        )
+    };
+
+    Ok((
+        Extension(CachePolicy::ShortInCdnAndBrowser),
+        Json(OwnerJsonResponse {
+            crates: owner
+                .releases
+                .into_iter()
+                .map(|release| SearchJsonCrate {
+                    name: release.name,
+                    version: Some(release.version),
+                    has_docs: release.rustdoc_status,
+                })
+                .collect(),
+            next_page: owner.has_next_page.then(|| page_link(page + 1)),
+            prev_page: (page > 1).then(|| page_link(page - 1)),
+            kind: owner.kind,
+            login,
+        }),
+    ))
+}
+
 #[derive(Template)]
 #[template(path = "releases/activity.html")]
 #[derive(Debug, Clone, PartialEq)]
@@ -716,6 +981,8 @@ struct BuildQueuePage {
     in_progress_builds: Vec<(String, String)>,
     csp_nonce: String,
     expand_rebuild_queue: bool,
+    queue_locked: bool,
+    queue_lock_reason: Option<String>,
 }
 
 impl_axum_webpage! { BuildQueuePage }
@@ -786,6 +1053,13 @@ pub(crate) async fn build_queue_handler(
         }
     });
 
+    let queue_locked = build_queue.is_locked().await?;
+    let queue_lock_reason = if queue_locked {
+        build_queue.lock_reason().await?
+    } else {
+        None
+    };
+
     Ok(BuildQueuePage {
         description: "crate documentation scheduled to build & deploy",
         queue,
@@ -794,6 +1068,8 @@ pub(crate) async fn build_queue_handler(
         in_progress_builds,
         csp_nonce: String::new(),
         expand_rebuild_queue: params.expand.is_some(),
+        queue_locked,
+        queue_lock_reason,
     })
 }
 
@@ -1178,6 +1454,207 @@ mod tests {
         })
     }
 
+    #[test]
+    fn search_json_joins_docs_status() {
+        async_wrapper(|env| async move {
+            let mut crates_io = mockito::Server::new_async().await;
+            env.override_config(|config| {
+                config.registry_api_host = crates_io.url().parse().unwrap();
+            });
+
+            let web = env.web_app().await;
+            env.fake_release()
+                .await
+                .name("documented")
+                .version("1.0.0")
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("failed")
+                .version("0.1.0")
+                .build_result_failed()
+                .create()
+                .await?;
+
+            let _m = crates_io
+                .mock("GET", "/api/v1/crates")
+                .match_query(Matcher::AllOf(vec![
+                    Matcher::UrlEncoded("q".into(), "some query".into()),
+                    Matcher::UrlEncoded("per_page".into(), "30".into()),
+                ]))
+                .with_status(200)
+                .with_header("content-type", "application/json")
+                .with_body(
+                    json!({
+                        "crates": [
+                            { "name": "documented" },
+                            { "name": "unknown" },
+                            { "name": "failed" },
+                        ],
+                        "meta": {
+                            "next_page": "?q=some+query&page=2",
+                            "prev_page": null,
+                        }
+                    })
+                    .to_string(),
+                )
+                .create_async()
+                .await;
+
+            let response = web.assert_success("/-/search?q=some+query").await?;
+            let result: serde_json::Value = response.json().await?;
+
+            assert_eq!(
+                result["crates"],
+                json!([
+                    { "name": "documented", "version": "1.0.0", "has_docs": true },
+                    { "name": "unknown", "version": null, "has_docs": false },
+                    { "name": "failed", "version": "0.1.0", "has_docs": false },
+                ])
+            );
+            assert_eq!(
+                result["next_page"],
+                format!("/-/search?page={}", b64.encode("?q=some+query&page=2"))
+            );
+            assert!(result["prev_page"].is_null());
+
+            // the cursor is forwarded to crates.io as-is
+            let _m2 = crates_io
+                .mock("GET", "/api/v1/crates")
+                .match_query(Matcher::AllOf(vec![
+                    Matcher::UrlEncoded("q".into(), "some query".into()),
+                    Matcher::UrlEncoded("page".into(), "2".into()),
+                ]))
+                .with_status(200)
+                .with_header("content-type", "application/json")
+                .with_body(
+                    json!({
+                        "crates": [{ "name": "documented" }],
+                        "meta": { "next_page": null, "prev_page": "?q=some+query&page=1" }
+                    })
+                    .to_string(),
+                )
+                .create_async()
+                .await;
+
+            let next_page = result["next_page"].as_str().unwrap();
+            let result: serde_json::Value = web.assert_success(next_page).await?.json().await?;
+            assert_eq!(result["crates"].as_array().unwrap().len(), 1);
+            assert!(result["next_page"].is_null());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn search_json_without_query() {
+        async_wrapper(|env| async move {
+            let web = env.web_app().await;
+            assert_eq!(web.get("/-/search").await?.status(), StatusCode::NOT_FOUND);
+            assert_eq!(
+                web.get("/-/search?page=invalid").await?.status(),
+                StatusCode::BAD_REQUEST
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn owner_crates_json() {
+        async_wrapper(|env| async move {
+            let owner = CrateOwner {
+                login: "foobar".into(),
+                avatar: "https://example.org/foobar".into(),
+                kind: OwnerKind::User,
+            };
+            env.fake_release()
+                .await
+                .name("documented")
+                .version("1.0.0")
+                .add_owner(owner.clone())
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("failed")
+                .version("0.1.0")
+                .build_result_failed()
+                .add_owner(owner)
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("other")
+                .version("1.0.0")
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            let result: serde_json::Value = web
+                .assert_success("/-/owner/foobar/crates.json")
+                .await?
+                .json()
+                .await?;
+
+            assert_eq!(
+                result,
+                json!({
+                    "login": "foobar",
+                    "kind": "user",
+                    "crates": [
+                        { "name": "documented", "version": "1.0.0", "has_docs": true },
+                        { "name": "failed", "version": "0.1.0", "has_docs": false },
+                    ],
+                    "next_page": null,
+                    "prev_page": null,
+                })
+            );
+
+            let page = kuchikiki::parse_html()
+                .one(web.assert_success("/-/owner/foobar").await?.text().await?);
+            let crates: Vec<_> = page
+                .select(".release > .pure-g > .name")
+                .unwrap()
+                .map(|node| node.text_contents().trim().to_owned())
+                .collect();
+            assert_eq!(crates, ["documented-1.0.0", "failed-0.1.0"]);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn owner_crates_unknown_login() {
+        async_wrapper(|env| async move {
+            let web = env.web_app().await;
+            let result: serde_json::Value = web
+                .assert_success("/-/owner/nobody/crates.json")
+                .await?
+                .json()
+                .await?;
+
+            assert!(result["kind"].is_null());
+            assert_eq!(result["crates"], json!([]));
+
+            web.assert_success("/-/owner/nobody").await?;
+            assert_eq!(
+                web.get("/-/owner/nobody/crates.json?page=0")
+                    .await?
+                    .status(),
+                StatusCode::BAD_REQUEST
+            );
+            for path in [
+                format!("/-/owner/nobody?page={}", i64::MAX),
+                format!("/-/owner/nobody/crates.json?page={}", i64::MAX),
+            ] {
+                assert_eq!(web.get(&path).await?.status(), StatusCode::NOT_FOUND);
+            }
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn search_invalid_paginate_doesnt_request_cratesio() {
         async_wrapper(|env| async move {
@@ -1909,6 +2386,36 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_releases_queue_locked() {
+        async_wrapper(|env| async move {
+            let web = env.web_app().await;
+            let queue = env.async_build_queue().await;
+
+            let page = kuchikiki::parse_html().one(web.get("/releases/queue").await?.text().await?);
+            assert!(page.select_first("#queue-locked").is_err());
+
+            queue.lock(None).await?;
+            let page = kuchikiki::parse_html().one(web.get("/releases/queue").await?.text().await?);
+            let notice = page
+                .select_first("#queue-locked")
+                .expect("missing lock notice")
+                .text_contents();
+            assert!(notice.contains("The build queue is currently locked"));
+            assert!(!notice.contains("Reason"));
+
+            queue.lock(Some("toolchain update failed")).await?;
+            let page = kuchikiki::parse_html().one(web.get("/releases/queue").await?.text().await?);
+            let notice = page
+                .select_first("#queue-locked")
+                .expect("missing lock notice")
+                .text_contents();
+            assert!(notice.contains("Reason: toolchain update failed"));
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn test_releases_rebuild_queue_empty() {
         async_wrapper(|env| async move {
@@ -1,9 +1,11 @@
 use super::{
     cache::CachePolicy, error::AxumNope, metrics::request_recorder, statics::build_static_router,
 };
+use crate::Config;
 use axum::{
     extract::Request as AxumHttpRequest,
     handler::Handler as AxumHandler,
+    http::{HeaderValue, Method},
     middleware::{self, Next},
     response::{IntoResponse, Redirect},
     routing::{get, post, MethodRouter},
@@ -11,10 +13,18 @@ use axum::{
 };
 use axum_extra::routing::RouterExt;
 use rinja::Template;
-use std::convert::Infallible;
-use tracing::{debug, instrument};
+use std::{convert::Infallible, time::Duration};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::{debug, instrument, warn};
 
-const INTERNAL_PREFIXES: &[&str] = &["-", "about", "crate", "releases", "sitemap.xml"];
+const INTERNAL_PREFIXES: &[&str] = &[
+    "-",
+    "about",
+    "crate",
+    "releases",
+    "sitemap.xml",
+    "sitemap.xml.gz",
+];
 
 #[instrument(skip_all)]
 pub(crate) fn get_static<H, T, S>(handler: H) -> MethodRouter<S, Infallible>
@@ -102,7 +112,71 @@ pub(super) fn build_metric_routes() -> AxumRouter {
         )
 }
 
-pub(super) fn build_axum_routes() -> AxumRouter {
+/// `/metrics` is the conventional path for Prometheus scrapers.
+///
+/// Only used on the background metrics web server, on the main web server
+/// it would shadow the docs of the `metrics` crate.
+pub(super) fn build_metric_scrape_routes() -> AxumRouter {
+    AxumRouter::new().route("/metrics", get_internal(super::metrics::metrics_handler))
+}
+
+/// how long browsers may cache the result of a CORS preflight request.
+const CORS_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn cors_layer(config: &Config) -> CorsLayer {
+    let allow_origin = if config
+        .cors_allowed_origins
+        .iter()
+        .any(|origin| origin == "*")
+    {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(config.cors_allowed_origins.iter().filter_map(|origin| {
+            match HeaderValue::from_str(origin) {
+                Ok(value) => Some(value),
+                Err(err) => {
+                    warn!(origin, ?err, "ignoring invalid CORS origin");
+                    None
+                }
+            }
+        }))
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::HEAD])
+        .max_age(CORS_MAX_AGE)
+}
+
+/// read-only JSON endpoints that browser-based tooling is allowed to call
+/// from other origins.
+fn build_json_api_routes(config: &Config) -> AxumRouter {
+    AxumRouter::new()
+        .route(
+            "/crate/{name}/{version}/builds.json",
+            get_internal(super::builds::build_list_json_handler),
+        )
+        .route(
+            "/crate/{name}/{version}/status.json",
+            get_internal(super::status::status_handler),
+        )
+        .route(
+            "/crate/{name}/diff/{from_version}/{to_version}/metadata.json",
+            get_internal(super::diff::metadata_diff_handler),
+        )
+        .route(
+            "/-/search",
+            get_internal(super::releases::search_json_handler),
+        )
+        .route(
+            "/-/owner/{login}/crates.json",
+            get_internal(super::releases::owner_crates_json_handler),
+        )
+        .layer(middleware::from_fn(super::error::json_api_error_middleware))
+        .layer(cors_layer(config))
+}
+
+pub(super) fn build_axum_routes(config: &Config) -> AxumRouter {
     // hint for naming axum routes:
     // when routes overlap, the route parameters at the same position
     // have to use the same name:
@@ -144,6 +218,14 @@ pub(super) fn build_axum_routes() -> AxumRouter {
             "/-/sitemap/{letter}/sitemap.xml",
             get_internal(super::sitemap::sitemap_handler),
         )
+        .route(
+            "/sitemap.xml.gz",
+            get_internal(super::sitemap::gzipped_sitemapindex_handler),
+        )
+        .route(
+            "/-/sitemap/{letter}/sitemap.xml.gz",
+            get_internal(super::sitemap::gzipped_sitemap_handler),
+        )
         .route_with_tsr(
             "/about/builds",
             get_internal(super::sitemap::about_builds_handler),
@@ -223,18 +305,11 @@ pub(super) fn build_axum_routes() -> AxumRouter {
             "/crate/{name}/{version}/builds",
             get_internal(super::builds::build_list_handler),
         )
-        .route(
-            "/crate/{name}/{version}/builds.json",
-            get_internal(super::builds::build_list_json_handler),
-        )
+        .merge(build_json_api_routes(config))
         .route(
             "/crate/{name}/{version}/rebuild",
             post_internal(super::builds::build_trigger_rebuild_handler),
         )
-        .route(
-            "/crate/{name}/{version}/status.json",
-            get_internal(super::status::status_handler),
-        )
         .route_with_tsr(
             "/crate/{name}/{version}/builds/{id}",
             get_internal(super::build_details::build_details_handler),
@@ -243,6 +318,14 @@ pub(super) fn build_axum_routes() -> AxumRouter {
             "/crate/{name}/{version}/builds/{id}/{filename}",
             get_internal(super::build_details::build_details_handler),
         )
+        .route_with_tsr(
+            "/crate/{name}/owners",
+            get_internal(super::crate_details::owners_handler),
+        )
+        .route_with_tsr(
+            "/crate/{name}/{version}/readme",
+            get_internal(super::crate_details::readme_handler),
+        )
         .route_with_tsr(
             "/crate/{name}/{version}/features",
             get_internal(super::features::build_features_handler),
@@ -283,6 +366,18 @@ pub(super) fn build_axum_routes() -> AxumRouter {
             "/crate/{name}/{version}/menus/releases/{target}/",
             get_internal(super::crate_details::get_all_releases),
         )
+        .route(
+            "/-/owner/{login}",
+            get_internal(super::releases::owner_crates_handler),
+        )
+        .route(
+            "/-/permalink",
+            post_internal(super::permalink::create_permalink_handler),
+        )
+        .route(
+            "/-/s/{short_id}",
+            get_internal(super::permalink::permalink_redirect_handler),
+        )
         .route(
             "/-/rustdoc.static/{*path}",
             get_internal(super::rustdoc::static_asset_handler),
@@ -309,6 +404,10 @@ pub(super) fn build_axum_routes() -> AxumRouter {
             "/crate/{name}/{version}/download",
             get_internal(super::rustdoc::download_handler),
         )
+        .route(
+            "/crate/{name}/{version}/docs.tar",
+            get_internal(super::rustdoc::rustdoc_tarball_handler),
+        )
         .route(
             "/crate/{name}/{version}/target-redirect/{*path}",
             get_internal(super::rustdoc::target_redirect_handler),
@@ -317,6 +416,10 @@ pub(super) fn build_axum_routes() -> AxumRouter {
             "/{name}/badge.svg",
             get_internal(super::rustdoc::badge_handler),
         )
+        .route(
+            "/crate/{name}/versions/badge.svg",
+            get_internal(super::rustdoc::versions_badge_handler),
+        )
         .route(
             "/{name}",
             get_rustdoc(super::rustdoc::rustdoc_redirector_handler),
@@ -372,7 +475,130 @@ async fn fallback() -> impl IntoResponse {
 mod tests {
     use crate::test::{async_wrapper, AxumResponseTestExt, AxumRouterTestExt};
     use crate::web::cache::CachePolicy;
+    use axum::{
+        body::Body,
+        http::{
+            header::{
+                ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE,
+                ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+            },
+            Method, Request,
+        },
+    };
     use reqwest::StatusCode;
+    use tower::ServiceExt;
+
+    fn preflight(path: &str, origin: &str) -> Request<Body> {
+        Request::builder()
+            .method(Method::OPTIONS)
+            .uri(path)
+            .header(ORIGIN, origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn cors_preflight_on_json_endpoints() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .create()
+                .await?;
+            let web = env.web_app().await;
+
+            for path in [
+                "/crate/foo/0.1.0/status.json",
+                "/crate/foo/0.1.0/builds.json",
+            ] {
+                let response = web
+                    .clone()
+                    .oneshot(preflight(path, "https://play.example"))
+                    .await?;
+                assert!(response.status().is_success(), "{path}");
+                let headers = response.headers();
+                assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+                assert_eq!(headers[ACCESS_CONTROL_MAX_AGE], "86400");
+                assert!(headers[ACCESS_CONTROL_ALLOW_METHODS]
+                    .to_str()?
+                    .contains("GET"));
+            }
+
+            let response = web.get("/crate/foo/0.1.0/status.json").await?;
+            assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn no_cors_on_html_routes() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .create()
+                .await?;
+            let web = env.web_app().await;
+
+            let response = web
+                .clone()
+                .oneshot(preflight("/crate/foo/0.1.0", "https://play.example"))
+                .await?;
+            assert!(response
+                .headers()
+                .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+                .is_none());
+
+            let response = web.get("/crate/foo/0.1.0").await?;
+            assert!(response
+                .headers()
+                .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+                .is_none());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn cors_with_configured_origins() {
+        async_wrapper(|env| async move {
+            env.override_config(|config| {
+                config.cors_allowed_origins = vec!["https://play.example".into()];
+            });
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .create()
+                .await?;
+            let web = env.web_app().await;
+            let path = "/crate/foo/0.1.0/status.json";
+
+            let response = web
+                .clone()
+                .oneshot(preflight(path, "https://play.example"))
+                .await?;
+            assert_eq!(
+                response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN],
+                "https://play.example"
+            );
+
+            let response = web
+                .clone()
+                .oneshot(preflight(path, "https://other.example"))
+                .await?;
+            assert!(response
+                .headers()
+                .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+                .is_none());
+
+            Ok(())
+        })
+    }
 
     #[test]
     fn test_root_redirects() {
@@ -435,4 +661,34 @@ mod tests {
             Ok(())
         })
     }
+
+    #[test]
+    fn serve_rustdoc_content_rejects_unexpected_extensions() {
+        async_wrapper(|env| async move {
+            let web = env.web_app().await;
+            let storage = env.async_storage().await;
+            storage
+                .store_one("/rustdoc-static/main.css", "content".as_bytes())
+                .await?;
+            storage
+                .store_one("/rustdoc-static/evil.exe", "content".as_bytes())
+                .await?;
+            storage
+                .store_one("/rustdoc-static/no_extension", "content".as_bytes())
+                .await?;
+
+            assert!(web
+                .get("/-/rustdoc.static/main.css")
+                .await?
+                .status()
+                .is_success());
+            for path in [
+                "/-/rustdoc.static/evil.exe",
+                "/-/rustdoc.static/no_extension",
+            ] {
+                assert_eq!(web.get(path).await?.status(), StatusCode::NOT_FOUND);
+            }
+            Ok(())
+        })
+    }
 }
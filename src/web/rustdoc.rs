@@ -13,23 +13,26 @@ use crate::{
         error::{AxumNope, AxumResult},
         extractors::{DbConnection, Path},
         file::File,
-        match_version,
+        latest_stable, match_version,
         page::{
             templates::{filters, RenderRegular, RenderSolid},
             TemplateData,
         },
-        MetaData, ReqVersion,
+        redirect_renamed_crate, MetaData, ParsedReqVersion, ReqVersion,
     },
     AsyncStorage, Config, InstanceMetrics, RUSTDOC_STATIC_STORAGE_PREFIX,
 };
 use anyhow::{anyhow, Context as _};
 use axum::{
+    body::Body,
     extract::{Extension, Query},
-    http::{StatusCode, Uri},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE, RANGE},
+        HeaderMap, StatusCode, Uri,
+    },
     response::{Html, IntoResponse, Response as AxumResponse},
 };
 use lol_html::errors::RewritingError;
-use once_cell::sync::Lazy;
 use rinja::Template;
 use semver::Version;
 use serde::Deserialize;
@@ -39,23 +42,50 @@ use std::{
 };
 use tracing::{debug, error, info_span, instrument, trace, Instrument};
 
-static DOC_RUST_LANG_ORG_REDIRECTS: Lazy<HashMap<&str, &str>> = Lazy::new(|| {
-    HashMap::from([
-        ("alloc", "stable/alloc"),
-        ("core", "stable/core"),
-        ("proc_macro", "stable/proc_macro"),
-        ("proc-macro", "stable/proc_macro"),
-        ("std", "stable/std"),
-        ("test", "stable/test"),
-        ("rustc", "nightly/nightly-rustc"),
-        ("rustdoc", "nightly/nightly-rustc/rustdoc"),
-    ])
-});
+/// Toolchain crates that aren't on crates.io, and where their documentation lives instead.
+///
+/// Requests for these names are redirected instead of looking for a crate,
+/// adding another one only needs a new entry here.
+const TOOLCHAIN_DOCS_REDIRECTS: &[(&str, &str)] = &[
+    ("alloc", "https://doc.rust-lang.org/stable/alloc/"),
+    ("core", "https://doc.rust-lang.org/stable/core/"),
+    ("proc_macro", "https://doc.rust-lang.org/stable/proc_macro/"),
+    ("proc-macro", "https://doc.rust-lang.org/stable/proc_macro/"),
+    ("std", "https://doc.rust-lang.org/stable/std/"),
+    ("test", "https://doc.rust-lang.org/stable/test/"),
+    ("rustc", "https://doc.rust-lang.org/nightly/nightly-rustc/"),
+    (
+        "rustdoc",
+        "https://doc.rust-lang.org/nightly/nightly-rustc/rustdoc/",
+    ),
+    #[cfg(test)]
+    (
+        "docsrs_test_toolchain_crate",
+        "https://toolchain.example.com/docs/",
+    ),
+];
+
+/// The documentation URL for a toolchain crate from [`TOOLCHAIN_DOCS_REDIRECTS`].
+fn toolchain_docs_url(crate_name: &str) -> Option<&'static str> {
+    TOOLCHAIN_DOCS_REDIRECTS
+        .iter()
+        .find(|(name, _)| *name == crate_name)
+        .map(|(_, url)| *url)
+}
+
+/// rustdoc themes that can be selected through the `?theme=` query parameter.
+const RUSTDOC_THEMES: &[&str] = &["light", "dark", "ayu"];
+
+/// Returns the known rustdoc theme requested in the query string, if any.
+fn requested_theme(query: Option<&str>) -> Option<&'static str> {
+    let (_, theme) = url::form_urlencoded::parse(query?.as_bytes()).find(|(k, _)| k == "theme")?;
+    RUSTDOC_THEMES.iter().copied().find(|known| *known == theme)
+}
 
 #[derive(Debug, Clone, Deserialize)]
 pub(crate) struct RustdocRedirectorParams {
     name: String,
-    version: Option<ReqVersion>,
+    version: Option<ParsedReqVersion>,
     target: Option<String>,
 }
 
@@ -90,14 +120,16 @@ async fn try_serve_legacy_toolchain_asset(
 
 /// Handler called for `/:crate` and `/:crate/:version` URLs. Automatically redirects to the docs
 /// or crate details page based on whether the given crate version was successfully built.
-#[instrument(skip(storage, config, conn))]
+#[instrument(skip(storage, config, metrics, conn, headers))]
 pub(crate) async fn rustdoc_redirector_handler(
     Path(params): Path<RustdocRedirectorParams>,
     Extension(storage): Extension<Arc<AsyncStorage>>,
     Extension(config): Extension<Arc<Config>>,
     mut conn: DbConnection,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
     Query(query_pairs): Query<HashMap<String, String>>,
     uri: Uri,
+    headers: HeaderMap,
 ) -> AxumResult<impl IntoResponse> {
     #[instrument]
     fn redirect_to_doc(
@@ -149,10 +181,10 @@ pub(crate) async fn rustdoc_redirector_handler(
         None => (params.name.to_string(), None),
     };
 
-    if let Some(inner_path) = DOC_RUST_LANG_ORG_REDIRECTS.get(crate_name.as_str()) {
+    if let Some(url) = toolchain_docs_url(&crate_name) {
         return Ok(redirect_to_doc(
             &query_pairs,
-            format!("https://doc.rust-lang.org/{inner_path}/"),
+            url.to_owned(),
             CachePolicy::ForeverInCdnAndStaleInBrowser,
             path_in_crate.as_deref(),
         )?
@@ -161,12 +193,33 @@ pub(crate) async fn rustdoc_redirector_handler(
 
     // it doesn't matter if the version that was given was exact or not, since we're redirecting
     // anyway
-    let matched_release = match_version(
+    let matched_release = match match_version(
         &mut conn,
+        &metrics,
         &crate_name,
-        &params.version.clone().unwrap_or_default(),
+        &params
+            .version
+            .as_ref()
+            .map(|version| version.req.clone())
+            .unwrap_or_default(),
     )
-    .await?
+    .await
+    {
+        Ok(matched_release) => matched_release,
+        Err(AxumNope::CrateRenamed(to_name)) => {
+            return Err(redirect_renamed_crate(
+                &crate_name,
+                &to_name,
+                params.target.as_deref().as_slice(),
+            ));
+        }
+        Err(err) => {
+            return Err(match &params.version {
+                Some(version) => err.with_requested_version(&version.raw),
+                None => err,
+            })
+        }
+    }
     .into_exactly_named();
     trace!(?matched_release, "matched version");
     let crate_name = matched_release.name.clone();
@@ -188,7 +241,7 @@ pub(crate) async fn rustdoc_redirector_handler(
                     )
                     .await
                 {
-                    Ok(blob) => Ok(File(blob).into_response()),
+                    Ok(blob) => Ok(File(blob).into_response_for_range(headers.get(RANGE))),
                     Err(err) => {
                         if !matches!(err.downcast_ref(), Some(AxumNope::ResourceNotFound))
                             && !matches!(
@@ -218,10 +271,32 @@ pub(crate) async fn rustdoc_redirector_handler(
 
     let matched_release = matched_release.into_canonical_req_version();
 
-    if matched_release.rustdoc_status() {
+    // On the crate root, when the newest release has no docs (for example because its
+    // build failed), we prefer the docs of the newest stable release over the crate page.
+    if matched_release.is_latest_url()
+        && params.target.is_none()
+        && !matched_release.has_library_docs()
+    {
+        if let Some((release, target_name)) = latest_stable(&matched_release.all_releases)
+            .filter(|release| {
+                release.rustdoc_status == Some(true) && release.is_library != Some(false)
+            })
+            .and_then(|release| Some((release, release.target_name.as_deref()?)))
+        {
+            return Ok(redirect_to_doc(
+                &query_pairs,
+                encode_url_path(&format!("/{crate_name}/{}/{target_name}/", release.version)),
+                CachePolicy::ForeverInCdn,
+                path_in_crate.as_deref(),
+            )?
+            .into_response());
+        }
+    }
+
+    if matched_release.has_library_docs() {
         let target_name = matched_release
             .target_name()
-            .expect("when rustdoc_status is true, target name exists");
+            .expect("when we have library docs, target name exists");
         let mut target = params.target.as_deref();
         if target == Some("index.html") || target == Some(target_name) {
             target = None;
@@ -277,6 +352,8 @@ pub struct RustdocPage {
     pub krate: CrateDetails,
     pub metadata: MetaData,
     pub current_target: String,
+    /// theme selected via the `?theme=` query parameter, applied while rewriting the HTML
+    pub theme: Option<&'static str>,
 }
 
 impl RustdocPage {
@@ -289,6 +366,7 @@ impl RustdocPage {
         file_path: &str,
     ) -> AxumResult<AxumResponse> {
         let is_latest_url = self.is_latest_url;
+        let is_themed = self.theme.is_some();
 
         // Extract the head and body of the rustdoc file so that we can insert it into our own html
         // while logging OOM errors from html rewriting
@@ -309,7 +387,11 @@ impl RustdocPage {
         Ok((
             StatusCode::OK,
             (!is_latest_url).then_some([("X-Robots-Tag", "noindex")]),
-            Extension(if is_latest_url {
+            Extension(if is_themed {
+                // themed pages are variants of the same page which a purge after a rebuild
+                // wouldn't reach, so we don't cache them at all.
+                CachePolicy::NoCaching
+            } else if is_latest_url {
                 CachePolicy::ForeverInCdn
             } else {
                 CachePolicy::ForeverInCdnAndStaleInBrowser
@@ -350,6 +432,7 @@ pub(crate) async fn rustdoc_html_server_handler(
     Extension(config): Extension<Arc<Config>>,
     Extension(csp): Extension<Arc<Csp>>,
     uri: Uri,
+    headers: HeaderMap,
 ) -> AxumResult<AxumResponse> {
     // since we directly use the Uri-path and not the extracted params from the router,
     // we have to percent-decode the string here.
@@ -390,8 +473,14 @@ pub(crate) async fn rustdoc_html_server_handler(
     // * If both the name and the version are an exact match, return the version of the crate.
     // * If there is an exact match, but the requested crate name was corrected (dashes vs. underscores), redirect to the corrected name.
     // * If there is a semver (but not exact) match, redirect to the exact version.
-    let matched_release = match_version(&mut conn, &params.name, &params.version)
-        .await?
+    let matched_release =
+        match match_version(&mut conn, &metrics, &params.name, &params.version).await {
+            Err(AxumNope::CrateRenamed(to_name)) => {
+                return Err(redirect_renamed_crate(&params.name, &to_name, &req_path));
+            }
+            result => result?,
+        };
+    let matched_release = matched_release
         .into_exactly_named_or_else(|corrected_name, req_version| {
             AxumNope::Redirect(
                 encode_url_path(&format!(
@@ -415,7 +504,7 @@ pub(crate) async fn rustdoc_html_server_handler(
             )
         })?;
 
-    if !matched_release.rustdoc_status() {
+    if !matched_release.has_library_docs() {
         return Ok(axum_cached_redirect(
             format!("/crate/{}/{}", params.name, params.version),
             CachePolicy::ForeverInCdn,
@@ -544,7 +633,7 @@ pub(crate) async fn rustdoc_html_server_handler(
         // default asset caching behaviour is `Cache::ForeverInCdnAndBrowser`.
         // This is an edge-case when we serve invocation specific static assets under `/latest/`:
         // https://github.com/rust-lang/docs.rs/issues/1593
-        return Ok(File(blob).into_response());
+        return Ok(File(blob).into_response_for_range(headers.get(RANGE)));
     }
 
     let latest_release = krate.latest_release()?;
@@ -613,6 +702,8 @@ pub(crate) async fn rustdoc_html_server_handler(
         .recently_accessed_releases
         .record(krate.crate_id, krate.release_id, target);
 
+    let theme = requested_theme(uri.query());
+
     // Build the page of documentation,
     templates
         .render_in_threadpool({
@@ -629,6 +720,7 @@ pub(crate) async fn rustdoc_html_server_handler(
                     metadata,
                     krate,
                     current_target,
+                    theme,
                 }
                 .into_response(
                     &blob.content,
@@ -696,10 +788,7 @@ fn path_for_version(
         // else, don't try searching at all, we don't know how to find it
         last_component.strip_suffix(".rs.html")
     };
-    let target_name = &crate_details
-        .target_name
-        .as_ref()
-        .expect("this method is only used when we have docs, so this field contains data");
+    let target_name = crate_details.metadata.effective_target_name();
     let path = if platform.is_empty() {
         format!("{target_name}/")
     } else {
@@ -717,9 +806,10 @@ fn path_for_version(
 pub(crate) async fn target_redirect_handler(
     Path((name, req_version, req_path)): Path<(String, ReqVersion, String)>,
     mut conn: DbConnection,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
     Extension(storage): Extension<Arc<AsyncStorage>>,
 ) -> AxumResult<impl IntoResponse> {
-    let matched_release = match_version(&mut conn, &name, &req_version)
+    let matched_release = match_version(&mut conn, &metrics, &name, &req_version)
         .await?
         .into_canonical_req_version_or_else(|_| AxumNope::VersionNotFound)?;
 
@@ -813,18 +903,65 @@ pub(crate) async fn badge_handler(
     ))
 }
 
+/// Redirects to a static shields.io badge with the number of releases of a crate
+/// that have documentation.
+#[instrument(skip_all)]
+pub(crate) async fn versions_badge_handler(
+    Path(name): Path<String>,
+    mut conn: DbConnection,
+) -> AxumResult<impl IntoResponse> {
+    let documented_versions = sqlx::query_scalar!(
+        r#"SELECT
+            (
+                SELECT COUNT(*)
+                FROM releases
+                WHERE releases.crate_id = crates.id AND releases.rustdoc_status = true
+            ) as "count!"
+        FROM crates
+        WHERE crates.name = $1"#,
+        name,
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AxumNope::CrateNotFound)?;
+
+    let message = if documented_versions == 1 {
+        "1 version".to_owned()
+    } else {
+        format!("{documented_versions} versions")
+    };
+    let url = url::Url::parse(&format!("https://img.shields.io/badge/docs-{message}-blue"))
+        .context("could not parse URL")?;
+
+    Ok((
+        StatusCode::FOUND,
+        [(http::header::LOCATION, url.to_string())],
+        // the count changes with every new build of the crate
+        Extension(CachePolicy::ShortInCdnAndBrowser),
+    ))
+}
+
 #[instrument(skip_all)]
 pub(crate) async fn download_handler(
     Path((name, req_version)): Path<(String, ReqVersion)>,
     mut conn: DbConnection,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
     Extension(storage): Extension<Arc<AsyncStorage>>,
     Extension(config): Extension<Arc<Config>>,
 ) -> AxumResult<impl IntoResponse> {
-    let version = match_version(&mut conn, &name, &req_version)
+    let version = match_version(&mut conn, &metrics, &name, &req_version)
         .await?
         .assume_exact_name()?
         .into_version();
 
+    // the target of `latest` or semver redirects changes when a release is yanked,
+    // which doesn't trigger a CDN purge.
+    let cache_policy = if matches!(req_version, ReqVersion::Exact(_)) {
+        CachePolicy::ForeverInCdn
+    } else {
+        CachePolicy::ShortInCdnLongInBrowser
+    };
+
     let archive_path = rustdoc_archive_path(&name, &version.to_string());
 
     // not all archives are set for public access yet, so we check if
@@ -850,10 +987,110 @@ pub(crate) async fn download_handler(
 
     Ok(super::axum_cached_redirect(
         format!("{}/{}", config.s3_static_root_path, archive_path),
-        CachePolicy::ForeverInCdn,
+        cache_policy,
     )?)
 }
 
+#[derive(Deserialize, Debug)]
+pub(crate) struct TarballParams {
+    target: Option<String>,
+}
+
+/// Streams all rendered documentation files of a release as an uncompressed tar.
+///
+/// With `?target=`, only the docs for that target are included.
+#[instrument(skip_all)]
+pub(crate) async fn rustdoc_tarball_handler(
+    Path((name, req_version)): Path<(String, ReqVersion)>,
+    Query(params): Query<TarballParams>,
+    mut conn: DbConnection,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
+    Extension(storage): Extension<Arc<AsyncStorage>>,
+) -> AxumResult<impl IntoResponse> {
+    let matched_release = match_version(&mut conn, &metrics, &name, &req_version)
+        .await?
+        .assume_exact_name()?;
+
+    let cache_policy = if matches!(req_version, ReqVersion::Exact(_)) {
+        CachePolicy::ForeverInCdn
+    } else {
+        CachePolicy::ShortInCdnLongInBrowser
+    };
+
+    let krate = CrateDetails::from_matched_release(&mut conn, matched_release).await?;
+
+    // releases from before archive storage don't have an archive index we
+    // could list the files from.
+    if !krate.rustdoc_status.unwrap_or(false) || !krate.archive_storage {
+        return Err(AxumNope::ResourceNotFound);
+    }
+
+    let include: Box<dyn Fn(&str) -> bool + Send> = match params.target {
+        None => Box::new(|_| true),
+        Some(target) => {
+            let doc_targets = krate.metadata.doc_targets().unwrap_or_default();
+            if !doc_targets.contains(&target) {
+                return Err(AxumNope::ResourceNotFound);
+            }
+
+            if krate.metadata.default_target.as_deref() == Some(&target) {
+                // the docs for the default target are stored in the root,
+                // next to the directories of the other targets.
+                let other_targets: Vec<String> = doc_targets
+                    .iter()
+                    .filter(|other| **other != target)
+                    .map(|other| format!("{other}/"))
+                    .collect();
+                Box::new(move |path| !other_targets.iter().any(|dir| path.starts_with(dir)))
+            } else {
+                let dir = format!("{target}/");
+                Box::new(move |path| path.starts_with(&dir))
+            }
+        }
+    };
+
+    let stream = match storage
+        .stream_rustdoc_tarball(
+            &name,
+            &krate.version.to_string(),
+            krate.latest_build_id,
+            include,
+        )
+        .await
+    {
+        Ok(stream) => stream,
+        Err(err) if err.is::<crate::storage::PathNotFoundError>() => {
+            return Err(AxumNope::ResourceNotFound)
+        }
+        Err(err) if err.is::<crate::error::SizeLimitReached>() => {
+            return Err(AxumNope::BadRequest(anyhow!(
+                "the documentation is too large to download as a tar"
+            )))
+        }
+        Err(err) => return Err(AxumNope::InternalError(err)),
+    };
+
+    Ok((
+        [
+            (CONTENT_TYPE, "application/x-tar".to_owned()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{name}-{}-docs.tar\"", krate.version),
+            ),
+        ],
+        Extension(cache_policy),
+        Body::from_stream(stream),
+    ))
+}
+
+/// File extensions of rustdoc's shared static files we are willing to serve.
+///
+/// Besides stylesheets, scripts, fonts & icons, rustdoc also ships
+/// `favicon-32x32.png` and the font license files as `.txt`.
+const RUSTDOC_STATIC_EXTENSIONS: &[&str] = &[
+    "css", "js", "mjs", "woff2", "svg", "ico", "wasm", "png", "txt",
+];
+
 /// Serves shared resources used by rustdoc-generated documentation.
 ///
 /// This serves files from S3, and is pointed to by the `--static-root-path` flag to rustdoc.
@@ -862,10 +1099,21 @@ pub(crate) async fn static_asset_handler(
     Path(path): Path<String>,
     Extension(storage): Extension<Arc<AsyncStorage>>,
     Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
 ) -> AxumResult<impl IntoResponse> {
+    let extension_allowed = std::path::Path::new(&path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| RUSTDOC_STATIC_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+    if !extension_allowed {
+        return Err(AxumNope::ResourceNotFound);
+    }
+
     let storage_path = format!("{RUSTDOC_STATIC_STORAGE_PREFIX}{path}");
 
-    Ok(File::from_path(&storage, &storage_path, &config).await?)
+    Ok(File::from_path(&storage, &storage_path, &config)
+        .await?
+        .into_response_for_range(headers.get(RANGE)))
 }
 
 #[cfg(test)]
@@ -880,7 +1128,7 @@ mod test {
     use anyhow::Context;
     use kuchikiki::traits::TendrilSink;
     use reqwest::StatusCode;
-    use std::collections::BTreeMap;
+    use std::{collections::BTreeMap, io::Read as _};
     use test_case::test_case;
     use tracing::info;
 
@@ -1318,6 +1566,72 @@ mod test {
         })
     }
 
+    #[test]
+    fn crate_root_redirects_to_latest_stable_docs_when_newest_build_failed() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.1.0")
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.2.0-pre.1")
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.2.0")
+                .build_result_failed()
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            web.assert_redirect_cached(
+                "/dummy",
+                "/dummy/0.1.0/dummy/",
+                CachePolicy::ForeverInCdn,
+                &env.config(),
+            )
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn crate_root_redirects_to_crate_page_without_stable_docs() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.1.0-pre.1")
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.1.0")
+                .build_result_failed()
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            web.assert_redirect_cached(
+                "/dummy",
+                "/crate/dummy/latest",
+                CachePolicy::ForeverInCdn,
+                &env.config(),
+            )
+            .await?;
+
+            Ok(())
+        })
+    }
+
     #[test_case(true)]
     #[test_case(false)]
     fn redirect_latest_does_not_go_to_yanked_versions(archive_storage: bool) {
@@ -1436,6 +1750,88 @@ mod test {
         })
     }
 
+    #[test]
+    fn toolchain_crates_redirect_to_their_docs() {
+        async_wrapper(|env| async move {
+            let web = env.web_app().await;
+            for (name, target) in super::TOOLCHAIN_DOCS_REDIRECTS {
+                // with or without slash
+                web.assert_redirect_cached_unchecked(
+                    &format!("/{name}"),
+                    target,
+                    CachePolicy::ForeverInCdnAndStaleInBrowser,
+                    &env.config(),
+                )
+                .await?;
+                web.assert_redirect_unchecked(&format!("/{name}/"), target)
+                    .await?;
+            }
+
+            web.assert_redirect_unchecked(
+                "/docsrs_test_toolchain_crate::some::Item",
+                "https://toolchain.example.com/docs/?search=some%3A%3AItem",
+            )
+            .await?;
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn versions_badge_counts_documented_releases() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.1.0")
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.2.0")
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("0.3.0")
+                .build_result_failed()
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("bar")
+                .version("1.0.0")
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            let response = web
+                .assert_redirect_cached_unchecked(
+                    "/crate/foo/versions/badge.svg",
+                    "https://img.shields.io/badge/docs-2%20versions-blue",
+                    CachePolicy::ShortInCdnAndBrowser,
+                    &env.config(),
+                )
+                .await?;
+            assert_eq!(response.status(), StatusCode::FOUND);
+
+            web.assert_redirect_cached_unchecked(
+                "/crate/bar/versions/badge.svg",
+                "https://img.shields.io/badge/docs-1%20version-blue",
+                CachePolicy::ShortInCdnAndBrowser,
+                &env.config(),
+            )
+            .await?;
+
+            assert_eq!(
+                web.get("/crate/missing/versions/badge.svg").await?.status(),
+                StatusCode::NOT_FOUND
+            );
+            Ok(())
+        })
+    }
+
     #[test_case(true)]
     #[test_case(false)]
     fn crate_name_percent_decoded_redirect(archive_storage: bool) {
@@ -1524,6 +1920,48 @@ mod test {
         })
     }
 
+    #[test_case(true)]
+    #[test_case(false)]
+    fn redirects_to_canonically_cased_name(archive_storage: bool) {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("foo")
+                .version("1.0.0")
+                .archive_storage(archive_storage)
+                .rustdoc_file("foo/index.html")
+                .create()
+                .await?;
+            env.fake_release()
+                .await
+                .name("Mixed-Case")
+                .version("0.1.0")
+                .archive_storage(archive_storage)
+                .rustdoc_file("mixed_case/index.html")
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+
+            web.assert_redirect("/Foo", "/foo/latest/foo/").await?;
+            web.assert_redirect("/Foo/1.0/", "/foo/1.0.0/foo/").await?;
+            web.assert_redirect("/FOO/1.0.0/foo/index.html", "/foo/1.0.0/foo/index.html")
+                .await?;
+
+            // requests already using the published casing are served without a redirect
+            web.assert_success("/foo/1.0.0/foo/index.html").await?;
+            web.assert_success("/Mixed-Case/0.1.0/mixed_case/index.html")
+                .await?;
+            web.assert_redirect(
+                "/mixed-case/0.1.0/mixed_case/index.html",
+                "/Mixed-Case/0.1.0/mixed_case/index.html",
+            )
+            .await?;
+
+            Ok(())
+        })
+    }
+
     #[test_case(true)]
     #[test_case(false)]
     fn specific_pages_do_not_handle_mismatched_separators(archive_storage: bool) {
@@ -2755,7 +3193,7 @@ mod test {
             web.assert_redirect_cached_unchecked(
                 "/crate/dummy/0.1/download",
                 "https://static.docs.rs/rustdoc/dummy/0.1.0.zip",
-                CachePolicy::ForeverInCdn,
+                CachePolicy::ShortInCdnLongInBrowser,
                 &env.config(),
             )
             .await?;
@@ -2827,13 +3265,18 @@ mod test {
 
             let web = env.web_app().await;
 
-            web.assert_redirect_cached_unchecked(
-                "/crate/dummy/latest/download",
-                "https://static.docs.rs/rustdoc/dummy/0.2.0.zip",
-                CachePolicy::ForeverInCdn,
-                &env.config(),
-            )
-            .await?;
+            let response = web
+                .assert_redirect_cached_unchecked(
+                    "/crate/dummy/latest/download",
+                    "https://static.docs.rs/rustdoc/dummy/0.2.0.zip",
+                    CachePolicy::ShortInCdnLongInBrowser,
+                    &env.config(),
+                )
+                .await?;
+            assert_eq!(
+                response.headers()["cache-control"],
+                "max-age=3600, s-maxage=60"
+            );
             assert!(
                 env.async_storage()
                     .await
@@ -2844,6 +3287,158 @@ mod test {
         });
     }
 
+    #[test]
+    fn rustdoc_tarball_contains_all_files() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(true)
+                .rustdoc_file_with("dummy/index.html", b"index content")
+                .rustdoc_file_with("dummy/module/index.html", b"module content")
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            let response = web.get("/crate/dummy/0.1.0/docs.tar").await?;
+            assert!(response.status().is_success());
+            assert_eq!(response.headers()["content-type"], "application/x-tar");
+            let files = read_tar(&response.bytes().await?)?;
+
+            assert_eq!(files["dummy/index.html"], b"index content");
+            assert_eq!(files["dummy/module/index.html"], b"module content");
+            Ok(())
+        });
+    }
+
+    /// collect the paths & contents of all entries in the tar
+    fn read_tar(tar: &[u8]) -> Result<BTreeMap<String, Vec<u8>>, anyhow::Error> {
+        let mut files = BTreeMap::new();
+        for entry in tar::Archive::new(tar).entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.to_string_lossy().into_owned();
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            files.insert(path, content);
+        }
+        Ok(files)
+    }
+
+    #[test]
+    fn rustdoc_tarball_for_target() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(true)
+                .default_target("x86_64-unknown-linux-gnu")
+                .add_platform("x86_64-pc-windows-msvc")
+                .rustdoc_file_with("dummy/index.html", b"index content")
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+
+            let response = web
+                .get("/crate/dummy/0.1.0/docs.tar?target=x86_64-pc-windows-msvc")
+                .await?;
+            assert!(response.status().is_success());
+            let files = read_tar(&response.bytes().await?)?;
+            assert!(!files.is_empty());
+            assert!(files
+                .keys()
+                .all(|path| path.starts_with("x86_64-pc-windows-msvc/")));
+            assert_eq!(
+                files["x86_64-pc-windows-msvc/dummy/index.html"],
+                b"index content"
+            );
+
+            let response = web
+                .get("/crate/dummy/0.1.0/docs.tar?target=x86_64-unknown-linux-gnu")
+                .await?;
+            assert!(response.status().is_success());
+            let files = read_tar(&response.bytes().await?)?;
+            assert_eq!(files["dummy/index.html"], b"index content");
+            assert!(!files
+                .keys()
+                .any(|path| path.starts_with("x86_64-pc-windows-msvc/")));
+
+            assert_eq!(
+                web.get("/crate/dummy/0.1.0/docs.tar?target=i686-unknown-linux-gnu")
+                    .await?
+                    .status(),
+                StatusCode::NOT_FOUND
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn rustdoc_tarball_not_found_without_archive() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(false)
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            assert_eq!(
+                web.get("/crate/dummy/0.1.0/docs.tar").await?.status(),
+                StatusCode::NOT_FOUND
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn rustdoc_tarball_too_large() {
+        async_wrapper(|env| async move {
+            env.override_config(|config| config.max_rustdoc_tarball_size = 4);
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(true)
+                .rustdoc_file_with("dummy/index.html", b"index content")
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            assert_eq!(
+                web.get("/crate/dummy/0.1.0/docs.tar").await?.status(),
+                StatusCode::BAD_REQUEST
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn rustdoc_tarball_too_large_uncompressed() {
+        async_wrapper(|env| async move {
+            env.override_config(|config| config.max_rustdoc_tarball_size = 10_000);
+            env.fake_release()
+                .await
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(true)
+                .rustdoc_file_with("dummy/big.html", &[b'a'; 100_000])
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            assert_eq!(
+                web.get("/crate/dummy/0.1.0/docs.tar").await?.status(),
+                StatusCode::BAD_REQUEST
+            );
+            Ok(())
+        });
+    }
+
     #[test_case("something.js")]
     #[test_case("something.css")]
     fn serve_release_specific_static_assets(name: &str) {
@@ -2946,6 +3541,58 @@ mod test {
         })
     }
 
+    #[test_case("/old-crate", "/new-crate/latest/")]
+    #[test_case("/old-crate/1.0.0", "/new-crate/latest/")]
+    #[test_case("/old-crate/1.0.0/old_crate/", "/new-crate/latest/new_crate/")]
+    #[test_case("/old-crate/1.0.0/old_crate/foo/", "/new-crate/latest/new_crate/foo/")]
+    #[test_case(
+        "/old-crate/latest/old_crate/struct.Foo.html",
+        "/new-crate/latest/new_crate/struct.Foo.html"
+    )]
+    #[test_case("/old-crate/1.0.0/settings.html", "/new-crate/latest/")]
+    #[test_case(
+        "/old-crate/latest/old_crate/%C3%A4.html",
+        "/new-crate/latest/new_crate/%C3%A4.html"
+    )]
+    fn redirect_from_renamed_crate(path: &str, expected: &str) {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("new-crate")
+                .version("1.0.0")
+                .create()
+                .await?;
+            let mut conn = env.async_db().await.async_conn().await;
+            crate::db::crate_redirects::add_redirect(&mut conn, "old-crate", "new-crate").await?;
+
+            let web = env.web_app().await;
+            web.assert_redirect_cached_unchecked(
+                path,
+                expected,
+                CachePolicy::ShortInCdnAndBrowser,
+                &env.config(),
+            )
+            .await?;
+            assert_eq!(web.get(path).await?.status(), StatusCode::MOVED_PERMANENTLY);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn missing_crate_without_redirect_is_not_found() {
+        async_wrapper(|env| async move {
+            let mut conn = env.async_db().await.async_conn().await;
+            crate::db::crate_redirects::add_redirect(&mut conn, "old-crate", "new-crate").await?;
+
+            let web = env.web_app().await;
+            web.assert_not_found("/other-crate/1.0.0/other_crate/")
+                .await?;
+
+            Ok(())
+        })
+    }
+
     #[test_case("/something/1.2.3/some_path/", "/crate/something/1.2.3")]
     #[test_case("/something/latest/some_path/", "/crate/something/latest")]
     fn rustdoc_page_from_failed_build_redirects_to_crate(path: &str, expected: &str) {
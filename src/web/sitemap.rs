@@ -1,20 +1,33 @@
 use crate::{
+    db::toolchain_history::{toolchain_history, ToolchainChange},
     docbuilder::Limits,
     impl_axum_webpage,
     utils::{get_config, ConfigName},
     web::{
         error::{AxumNope, AxumResult},
         extractors::{DbConnection, Path},
-        page::templates::{filters, RenderBrands, RenderSolid},
+        page::{
+            templates::{filters, RenderBrands, RenderSolid},
+            TemplateData,
+        },
         AxumErrorPage,
     },
     Config,
 };
-use axum::{extract::Extension, http::StatusCode, response::IntoResponse};
+use anyhow::{bail, ensure, Context as _};
+use axum::{
+    extract::Extension,
+    http::{
+        header::{CONTENT_ENCODING, CONTENT_TYPE},
+        StatusCode,
+    },
+    response::{IntoResponse, Response as AxumResponse},
+};
 use chrono::{TimeZone, Utc};
+use flate2::{write::GzEncoder, Compression};
 use futures_util::stream::TryStreamExt;
 use rinja::Template;
-use std::sync::Arc;
+use std::{io::Write as _, str::FromStr, sync::Arc};
 
 /// sitemap index
 #[derive(Template)]
@@ -22,6 +35,10 @@ use std::sync::Arc;
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct SitemapIndexXml {
     sitemaps: Vec<char>,
+    /// external base URL without trailing slash
+    base_url: String,
+    /// link to the gzipped child sitemaps
+    gzipped: bool,
     csp_nonce: String,
 }
 
@@ -30,15 +47,68 @@ impl_axum_webpage! {
     content_type = "application/xml",
 }
 
-pub(crate) async fn sitemapindex_handler() -> impl IntoResponse {
-    let sitemaps: Vec<char> = ('a'..='z').collect();
-
-    SitemapIndexXml {
-        sitemaps,
-        csp_nonce: String::new(),
+impl SitemapIndexXml {
+    fn new(config: &Config, gzipped: bool) -> Self {
+        Self {
+            sitemaps: ('a'..='z').collect(),
+            base_url: base_url(config),
+            gzipped,
+            csp_nonce: String::new(),
+        }
     }
 }
 
+pub(crate) async fn sitemapindex_handler(
+    Extension(config): Extension<Arc<Config>>,
+) -> impl IntoResponse {
+    SitemapIndexXml::new(&config, false)
+}
+
+pub(crate) async fn gzipped_sitemapindex_handler(
+    Extension(config): Extension<Arc<Config>>,
+    Extension(templates): Extension<Arc<TemplateData>>,
+) -> AxumResult<AxumResponse> {
+    gzipped_xml(&templates, SitemapIndexXml::new(&config, true)).await
+}
+
+/// Renders an XML template and serves it gzip-compressed.
+///
+/// Both rendering and compressing are CPU intensive, so they run in the
+/// rendering threadpool instead of blocking the async runtime.
+async fn gzipped_xml(
+    templates: &Arc<TemplateData>,
+    page: impl Template + Send + 'static,
+) -> AxumResult<AxumResponse> {
+    let compressed = templates
+        .render_in_threadpool(move || {
+            let xml = page.render().context("error rendering sitemap")?;
+
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(xml.as_bytes())
+                .context("error compressing sitemap")?;
+            encoder.finish().context("error compressing sitemap")
+        })
+        .await?;
+
+    Ok((
+        [
+            (CONTENT_TYPE, "application/xml"),
+            (CONTENT_ENCODING, "gzip"),
+        ],
+        compressed,
+    )
+        .into_response())
+}
+
+fn base_url(config: &Config) -> String {
+    config
+        .external_base_url
+        .as_str()
+        .trim_end_matches('/')
+        .to_owned()
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct SitemapRow {
     crate_name: String,
@@ -52,6 +122,8 @@ struct SitemapRow {
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct SitemapXml {
     releases: Vec<SitemapRow>,
+    /// external base URL without trailing slash
+    base_url: String,
     csp_nonce: String,
 }
 
@@ -62,8 +134,26 @@ impl_axum_webpage! {
 
 pub(crate) async fn sitemap_handler(
     Path(letter): Path<String>,
-    mut conn: DbConnection,
+    conn: DbConnection,
+    Extension(config): Extension<Arc<Config>>,
 ) -> AxumResult<impl IntoResponse> {
+    load_sitemap(&letter, conn, &config).await
+}
+
+pub(crate) async fn gzipped_sitemap_handler(
+    Path(letter): Path<String>,
+    conn: DbConnection,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(templates): Extension<Arc<TemplateData>>,
+) -> AxumResult<AxumResponse> {
+    gzipped_xml(&templates, load_sitemap(&letter, conn, &config).await?).await
+}
+
+async fn load_sitemap(
+    letter: &str,
+    mut conn: DbConnection,
+    config: &Config,
+) -> AxumResult<SitemapXml> {
     if letter.len() != 1 {
         return Err(AxumNope::ResourceNotFound);
     } else if let Some(ch) = letter.chars().next() {
@@ -104,16 +194,90 @@ pub(crate) async fn sitemap_handler(
 
     Ok(SitemapXml {
         releases,
+        base_url: base_url(config),
         csp_nonce: String::new(),
     })
 }
 
+/// A single `Allow` or `Disallow` line in `robots.txt`.
+///
+/// Parsed from the same `Directive: path` form it is rendered in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum RobotsRule {
+    Allow(String),
+    Disallow(String),
+}
+
+impl RobotsRule {
+    fn directive(&self) -> &'static str {
+        match self {
+            Self::Allow(_) => "Allow",
+            Self::Disallow(_) => "Disallow",
+        }
+    }
+
+    fn path(&self) -> &str {
+        match self {
+            Self::Allow(path) | Self::Disallow(path) => path,
+        }
+    }
+}
+
+impl FromStr for RobotsRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (directive, path) = s.split_once(':').with_context(|| {
+            format!("invalid robots.txt rule {s:?}, expected `Directive: path`")
+        })?;
+        let path = path.trim().to_owned();
+        ensure!(
+            path.starts_with('/') || path.starts_with('*'),
+            "invalid path {path:?} in robots.txt rule"
+        );
+
+        match directive.trim().to_ascii_lowercase().as_str() {
+            "allow" => Ok(Self::Allow(path)),
+            "disallow" => Ok(Self::Disallow(path)),
+            other => bail!("unknown robots.txt directive {other:?}"),
+        }
+    }
+}
+
+/// robots.txt
+#[derive(Template)]
+#[template(path = "core/robots.txt")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RobotsTxt {
+    rules: Vec<RobotsRule>,
+    /// external base URL without trailing slash
+    base_url: String,
+    csp_nonce: String,
+}
+
+impl_axum_webpage! {
+    RobotsTxt,
+    content_type = "text/plain; charset=utf-8",
+}
+
+pub(crate) async fn robots_txt_handler(
+    Extension(config): Extension<Arc<Config>>,
+) -> impl IntoResponse {
+    RobotsTxt {
+        rules: config.robots_txt_rules.clone(),
+        base_url: base_url(&config),
+        csp_nonce: String::new(),
+    }
+}
+
 #[derive(Template)]
 #[template(path = "core/about/builds.html")]
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct AboutBuilds {
     /// The current version of rustc that docs.rs is using to build crates
     rustc_version: Option<String>,
+    /// The most recent toolchain changes, newest first
+    toolchain_history: Vec<ToolchainChange>,
     /// The default crate build limits
     limits: Limits,
     /// Just for the template, since this isn't shared with AboutPage
@@ -123,12 +287,16 @@ struct AboutBuilds {
 
 impl_axum_webpage!(AboutBuilds);
 
+/// how many toolchain changes are listed on the builds page
+const TOOLCHAIN_HISTORY_LEN: i64 = 10;
+
 pub(crate) async fn about_builds_handler(
     mut conn: DbConnection,
     Extension(config): Extension<Arc<Config>>,
 ) -> AxumResult<impl IntoResponse> {
     Ok(AboutBuilds {
         rustc_version: get_config::<String>(&mut conn, ConfigName::RustcVersion).await?,
+        toolchain_history: toolchain_history(&mut conn, TOOLCHAIN_HISTORY_LEN).await?,
         limits: Limits::new(&config),
         active_tab: "builds",
         csp_nonce: String::new(),
@@ -193,6 +361,7 @@ pub(crate) async fn about_handler(subpage: Option<Path<String>>) -> AxumResult<i
                 title: "The requested page does not exist",
                 message: msg.into(),
                 status: StatusCode::NOT_FOUND,
+                suggestions: Vec::new(),
                 csp_nonce: String::new(),
             };
             page.into_response()
@@ -203,8 +372,17 @@ pub(crate) async fn about_handler(subpage: Option<Path<String>>) -> AxumResult<i
 
 #[cfg(test)]
 mod tests {
+    use super::RobotsRule;
+    use crate::db::set_toolchain;
     use crate::test::{async_wrapper, AxumResponseTestExt, AxumRouterTestExt};
-    use axum::http::StatusCode;
+    use axum::http::{
+        header::{CONTENT_ENCODING, CONTENT_TYPE},
+        StatusCode,
+    };
+    use flate2::read::GzDecoder;
+    use kuchikiki::traits::TendrilSink;
+    use std::io::Read as _;
+    use test_case::test_case;
 
     #[test]
     fn sitemap_index() {
@@ -215,6 +393,33 @@ mod tests {
         })
     }
 
+    #[test]
+    fn sitemap_uses_external_base_url() {
+        async_wrapper(|env| async move {
+            env.override_config(|config| {
+                config.external_base_url = "https://mirror.example".parse().unwrap();
+            });
+            env.fake_release()
+                .await
+                .name("some_random_crate")
+                .create()
+                .await?;
+            let web = env.web_app().await;
+
+            let index = web.get("/sitemap.xml").await?.text().await?;
+            assert!(index.contains("<loc>https://mirror.example/-/sitemap/s/sitemap.xml</loc>"));
+            assert!(!index.contains("https://docs.rs"));
+
+            let sitemap = web.get("/-/sitemap/s/sitemap.xml").await?.text().await?;
+            assert!(sitemap.contains(
+                "<loc>https://mirror.example/some_random_crate/latest/some_random_crate/</loc>"
+            ));
+            assert!(!sitemap.contains("https://docs.rs"));
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn sitemap_invalid_letters() {
         async_wrapper(|env| async move {
@@ -299,6 +504,56 @@ mod tests {
         })
     }
 
+    #[test]
+    fn gzipped_sitemaps() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("some_random_crate")
+                .create()
+                .await?;
+            let web = env.web_app().await;
+
+            let decompress = |response: axum::response::Response| async move {
+                assert!(response.status().is_success());
+                assert_eq!(response.headers()[CONTENT_TYPE], "application/xml");
+                assert_eq!(response.headers()[CONTENT_ENCODING], "gzip");
+
+                let mut xml = String::new();
+                GzDecoder::new(&response.bytes().await?[..]).read_to_string(&mut xml)?;
+                anyhow::Ok(xml)
+            };
+
+            let index = decompress(web.get("/sitemap.xml.gz").await?).await?;
+            assert!(index.starts_with(r#"<?xml version="1.0" encoding="UTF-8"?>"#));
+            assert_eq!(
+                index,
+                web.get("/sitemap.xml")
+                    .await?
+                    .text()
+                    .await?
+                    .replace("/sitemap.xml</loc>", "/sitemap.xml.gz</loc>")
+            );
+            assert!(index.contains("/-/sitemap/s/sitemap.xml.gz</loc>"));
+
+            let sitemap = decompress(web.get("/-/sitemap/s/sitemap.xml.gz").await?).await?;
+            assert!(sitemap.starts_with(r#"<?xml version="1.0" encoding="UTF-8"?>"#));
+            assert!(sitemap.trim_end().ends_with("</urlset>"));
+            assert_eq!(
+                sitemap,
+                web.get("/-/sitemap/s/sitemap.xml").await?.text().await?
+            );
+            assert!(sitemap.contains("/some_random_crate/latest/some_random_crate/</loc>"));
+
+            assert_eq!(
+                web.get("/-/sitemap/1/sitemap.xml.gz").await?.status(),
+                StatusCode::NOT_FOUND
+            );
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn about_page() {
         async_wrapper(|env| async move {
@@ -321,13 +576,102 @@ mod tests {
         })
     }
 
+    #[test]
+    fn about_builds_shows_toolchain_history() {
+        async_wrapper(|env| async move {
+            let mut conn = env.async_db().await.async_conn().await;
+            set_toolchain(&mut conn, "nightly-2025-01-01").await?;
+            set_toolchain(&mut conn, "nightly-2025-02-01").await?;
+
+            let page = kuchikiki::parse_html().one(
+                env.web_app()
+                    .await
+                    .get("/about/builds")
+                    .await?
+                    .text()
+                    .await?,
+            );
+            let current = page.select_first("#current-toolchain").unwrap();
+            assert!(current.text_contents().contains("nightly-2025-02-01"));
+
+            let history: Vec<_> = page
+                .select("#toolchain-history code")
+                .unwrap()
+                .map(|node| node.text_contents())
+                .collect();
+            assert_eq!(history, ["nightly-2025-02-01", "nightly-2025-01-01"]);
+            Ok(())
+        })
+    }
+
     #[test]
     fn robots_txt() {
         async_wrapper(|env| async move {
             let web = env.web_app().await;
             web.assert_redirect("/robots.txt", "/-/static/robots.txt")
                 .await?;
+
+            let response = web.get("/-/static/robots.txt").await?;
+            assert!(response.status().is_success());
+            assert_eq!(
+                response.headers().get(CONTENT_TYPE).unwrap(),
+                "text/plain; charset=utf-8"
+            );
+            // without configured rules, this is the robots.txt we always had.
+            assert_eq!(
+                response.text().await?.trim_end(),
+                "Sitemap: https://docs.rs/sitemap.xml\n\
+                 # Semver-based URL are always redirects, and sometimes\n\
+                 # confuse Google's duplicate detection, so we block crawling them.\n\
+                 # https://docs.rs/about/redirections\n\
+                 User-Agent: *\n\
+                 Disallow: */^\n\
+                 # %5E is '^', URL-encoded. Based on the Search Console, Google\n\
+                 # may be encoding '^' before checking against robots.txt.\n\
+                 Disallow: */%5E\n\
+                 Disallow: */~"
+            );
             Ok(())
         })
     }
+
+    #[test]
+    fn robots_txt_with_configured_rules() {
+        async_wrapper(|env| async move {
+            env.override_config(|config| {
+                config.robots_txt_rules = vec![
+                    "Allow: /*/latest/".parse().unwrap(),
+                    "Disallow: /*/*.*.*/".parse().unwrap(),
+                ]
+            });
+            let web = env.web_app().await;
+
+            let body = web.get("/-/static/robots.txt").await?.text().await?;
+            let lines: Vec<_> = body.lines().collect();
+            for expected in [
+                "User-Agent: *",
+                "Disallow: */^",
+                "Disallow: */%5E",
+                "Disallow: */~",
+                "Allow: /*/latest/",
+                "Disallow: /*/*.*.*/",
+            ] {
+                assert!(lines.contains(&expected), "missing {expected:?} in {body}");
+            }
+            Ok(())
+        })
+    }
+
+    #[test_case("Allow: /*/latest/", RobotsRule::Allow("/*/latest/".into()))]
+    #[test_case("disallow:/crate/*/*.*", RobotsRule::Disallow("/crate/*/*.*".into()))]
+    fn parse_robots_rule(input: &str, expected: RobotsRule) {
+        assert_eq!(input.parse::<RobotsRule>().unwrap(), expected);
+    }
+
+    #[test_case("/*/latest/")]
+    #[test_case("Crawl-delay: 10")]
+    #[test_case("Disallow: latest")]
+    fn parse_invalid_robots_rule(input: &str) {
+        assert!(input.parse::<RobotsRule>().is_err());
+    }
 }
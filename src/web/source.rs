@@ -12,10 +12,17 @@ use crate::{
         page::templates::{filters, RenderBrands, RenderRegular, RenderSolid},
         MetaData, ReqVersion,
     },
-    AsyncStorage,
+    AsyncStorage, Config, InstanceMetrics,
 };
 use anyhow::{Context as _, Result};
-use axum::{response::IntoResponse, Extension};
+use axum::{
+    http::{
+        header::{CONTENT_DISPOSITION, RANGE},
+        HeaderMap, HeaderValue,
+    },
+    response::IntoResponse,
+    Extension,
+};
 use axum_extra::headers::HeaderMapExt;
 use mime::Mime;
 use rinja::Template;
@@ -24,15 +31,17 @@ use serde::Deserialize;
 use std::{cmp::Ordering, sync::Arc};
 use tracing::instrument;
 
-/// A source file's name and mime type
+/// A source file's name, mime type and size
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
 struct File {
     name: String,
     mime: String,
+    /// size in bytes, for directories the total size of all files they contain
+    size: Option<u64>,
 }
 
 impl File {
-    fn from_path_and_mime(path: &str, mime: &Mime) -> File {
+    fn from_path_and_mime(path: &str, mime: &Mime, size: Option<u64>) -> File {
         let (name, mime) = if let Some((dir, _)) = path.split_once('/') {
             (dir, "dir")
         } else {
@@ -42,8 +51,13 @@ impl File {
         Self {
             name: name.to_owned(),
             mime: mime.to_owned(),
+            size,
         }
     }
+
+    fn is_dir(&self) -> bool {
+        self.mime == "dir"
+    }
 }
 
 /// A list of source files
@@ -59,14 +73,16 @@ impl FileList {
     ///
     /// ```text
     /// [
-    ///   ["text/plain", ".gitignore"],
-    ///   ["text/x-c", "src/reseeding.rs"],
-    ///   ["text/x-c", "src/lib.rs"],
-    ///   ["text/x-c", "README.md"],
+    ///   ["text/plain", ".gitignore", 19],
+    ///   ["text/x-c", "src/reseeding.rs", 2831],
+    ///   ["text/x-c", "src/lib.rs", 10284],
+    ///   ["text/x-c", "README.md", 1290],
     ///   ...
     /// ]
     /// ```
     ///
+    /// The size is missing for releases added before we started recording it.
+    ///
     /// This function is only returning FileList for requested directory. If is empty,
     /// it will return list of files (and dirs) for root directory. req_path must be a
     /// directory or empty for root directory.
@@ -111,6 +127,7 @@ impl FileList {
                         .parse()
                         .unwrap_or(mime::APPLICATION_OCTET_STREAM);
                     let path = file[1].as_str().unwrap();
+                    let size = file.get(2).and_then(|size| size.as_u64());
 
                     // skip .cargo-ok generated by cargo
                     if path == ".cargo-ok" {
@@ -119,10 +136,16 @@ impl FileList {
 
                     // look only files for req_path
                     if let Some(path) = path.strip_prefix(folder) {
-                        let file = File::from_path_and_mime(path, &mime);
-
-                        // avoid adding duplicates, a directory may occur more than once
-                        if !file_list.contains(&file) {
+                        let file = File::from_path_and_mime(path, &mime, size);
+
+                        // avoid adding duplicates, a directory may occur more than once.
+                        // Its size is the sum of everything inside it.
+                        if let Some(existing) = file_list
+                            .iter_mut()
+                            .find(|f| f.name == file.name && f.mime == file.mime)
+                        {
+                            existing.size = existing.size.zip(file.size).map(|(a, b)| a + b);
+                        } else {
                             file_list.push(file);
                         }
                     }
@@ -135,9 +158,9 @@ impl FileList {
 
             file_list.sort_by(|a, b| {
                 // directories must be listed first
-                if a.mime == "dir" && b.mime != "dir" {
+                if a.is_dir() && !b.is_dir() {
                     Ordering::Less
-                } else if a.mime != "dir" && b.mime == "dir" {
+                } else if !a.is_dir() && b.is_dir() {
                     Ordering::Greater
                 } else {
                     a.name.to_lowercase().cmp(&b.name.to_lowercase())
@@ -192,15 +215,18 @@ pub(crate) struct SourceBrowserHandlerParams {
     path: String,
 }
 
-#[instrument(skip(pool, storage))]
+#[instrument(skip(pool, metrics, storage, config, headers))]
 pub(crate) async fn source_browser_handler(
     Path(params): Path<SourceBrowserHandlerParams>,
     Extension(storage): Extension<Arc<AsyncStorage>>,
     Extension(pool): Extension<Pool>,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
 ) -> AxumResult<impl IntoResponse> {
     let mut conn = pool.get_async().await?;
 
-    let version = match_version(&mut conn, &params.name, &params.version)
+    let version = match_version(&mut conn, &metrics, &params.name, &params.version)
         .await?
         .into_exactly_named_or_else(|corrected_name, req_version| {
             AxumNope::Redirect(
@@ -275,16 +301,27 @@ pub(crate) async fn source_browser_handler(
         (None, false)
     };
 
-    let canonical_url = CanonicalUrl::from_path(format!(
-        "/crate/{}/latest/source/{}",
-        params.name, params.path
-    ));
-
-    let (file, file_content) = if let Some(blob) = blob {
-        let is_text = blob.mime.type_() == mime::TEXT || blob.mime == mime::APPLICATION_JSON;
+    let canonical_url = CanonicalUrl::from_path(
+        &config.external_base_url,
+        format!("/crate/{}/latest/source/{}", params.name, params.path),
+    );
+
+    let (file, file_content) = if let Some(mut blob) = blob {
+        let mut is_text = blob.mime.type_() == mime::TEXT || blob.mime == mime::APPLICATION_JSON;
+        // the mime type is guessed from the file name, don't render binary content as text
+        if is_text && looks_binary(&blob.content) {
+            is_text = false;
+            blob.mime = mime::APPLICATION_OCTET_STREAM;
+        }
         // serve the file with DatabaseFileHandler if file isn't text and not empty
         if !is_text && !blob.is_empty() {
-            let mut response = DbFile(blob).into_response();
+            let is_octet_stream = blob.mime == mime::APPLICATION_OCTET_STREAM;
+            let mut response = DbFile(blob).into_response_for_range(headers.get(RANGE));
+            if is_octet_stream {
+                response
+                    .headers_mut()
+                    .insert(CONTENT_DISPOSITION, HeaderValue::from_static("attachment"));
+            }
             response.headers_mut().typed_insert(canonical_url);
             response
                 .extensions_mut()
@@ -297,7 +334,11 @@ pub(crate) async fn source_browser_handler(
                 .map(|(_, path)| path)
                 .unwrap_or(&blob.path);
             (
-                Some(File::from_path_and_mime(path, &blob.mime)),
+                Some(File::from_path_and_mime(
+                    path,
+                    &blob.mime,
+                    Some(blob.content.len() as u64),
+                )),
                 String::from_utf8(blob.content).ok(),
             )
         } else {
@@ -343,6 +384,12 @@ pub(crate) async fn source_browser_handler(
     .into_response())
 }
 
+/// Whether file content can't be shown as text, because it isn't UTF-8
+/// or contains NUL bytes.
+fn looks_binary(content: &[u8]) -> bool {
+    content.contains(&0) || std::str::from_utf8(content).is_err()
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
@@ -465,6 +512,37 @@ mod tests {
         });
     }
 
+    #[test_case("some_file.rs", b"fn main() {}", "text/html"; "utf8 rust")]
+    #[test_case("some_file.rs", b"fn main() {\xff\xfe}", "application/octet-stream"; "invalid utf8 rust")]
+    #[test_case("blob.txt", b"text\0with nul", "application/octet-stream"; "nul bytes in text")]
+    #[test_case("blob.bin", b"\x00\x01\x02", "application/octet-stream"; "unknown binary")]
+    fn binary_content_is_downloaded(path: &str, content: &'static [u8], content_type: &str) {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("fake")
+                .version("0.1.0")
+                .source_file(path, content)
+                .create()
+                .await?;
+            let web = env.web_app().await;
+            let response = web.get(&format!("/crate/fake/0.1.0/source/{path}")).await?;
+            assert!(response.status().is_success());
+            assert!(response.headers()["content-type"]
+                .to_str()?
+                .starts_with(content_type));
+
+            if content_type == "application/octet-stream" {
+                assert_eq!(response.headers()["content-disposition"], "attachment");
+                assert_eq!(response.bytes().await?.as_ref(), content);
+            } else {
+                assert!(response.headers().get("content-disposition").is_none());
+                assert!(response.text().await?.starts_with("<!DOCTYPE html>"));
+            }
+            Ok(())
+        });
+    }
+
     #[test_case(true)]
     #[test_case(false)]
     fn cargo_ok_not_skipped(archive_storage: bool) {
@@ -793,6 +871,50 @@ mod tests {
         });
     }
 
+    #[test]
+    fn file_list_contains_sizes_and_types() {
+        async_wrapper(|env| async move {
+            env.fake_release()
+                .await
+                .name("fake")
+                .version("0.1.0")
+                .source_file("folder/a.rs", &[b'a'; 1000])
+                .source_file("folder/nested/b.rs", &[b'b'; 1000])
+                .source_file("folder/c.rs", &[b'c'; 2000])
+                .create()
+                .await?;
+
+            let web = env.web_app().await;
+            let response = web.get("/crate/fake/0.1.0/source/folder/").await?;
+            assert!(response.status().is_success());
+
+            let dom = kuchikiki::parse_html().one(response.text().await?);
+            let entries: Vec<_> = dom
+                .select(".package-menu > ul > li > a")
+                .expect("invalid selector")
+                .filter_map(|el| {
+                    let href = el.attributes.borrow().get("href")?.to_string();
+                    let size = el
+                        .as_node()
+                        .select_first(".file-size")
+                        .ok()?
+                        .text_contents();
+                    Some((href, size))
+                })
+                .collect();
+
+            assert_eq!(
+                entries,
+                vec![
+                    ("./nested/".to_string(), "1.00 kB".to_string()),
+                    ("./a.rs".to_string(), "1.00 kB".to_string()),
+                    ("./c.rs".to_string(), "2.00 kB".to_string()),
+                ]
+            );
+            Ok(())
+        });
+    }
+
     #[test]
     fn large_file_test() {
         async_wrapper(|env| async move {
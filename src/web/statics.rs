@@ -67,6 +67,11 @@ pub(crate) fn build_static_router() -> AxumRouter {
             "/rustdoc-2021-12-05.css",
             get_static(|| async { build_static_css_response(RUSTDOC_2021_12_05_CSS) }),
         )
+        // generated from the config, `/robots.txt` redirects here.
+        .route(
+            "/robots.txt",
+            get_static(super::sitemap::robots_txt_handler),
+        )
         .fallback_service(
             get_service(ServeDir::new("static").fallback(ServeDir::new("vendor")))
                 .layer(middleware::from_fn(set_needed_static_headers))
@@ -1,24 +1,27 @@
 use super::{cache::CachePolicy, error::AxumNope};
-use crate::web::{
-    error::AxumResult,
-    extractors::{DbConnection, Path},
-    match_version, ReqVersion,
-};
-use axum::{
-    extract::Extension, http::header::ACCESS_CONTROL_ALLOW_ORIGIN, response::IntoResponse, Json,
+use crate::{
+    web::{
+        error::AxumResult,
+        extractors::{DbConnection, Path},
+        match_version, ReqVersion,
+    },
+    InstanceMetrics,
 };
+use axum::{extract::Extension, response::IntoResponse, Json};
+use std::sync::Arc;
 
 pub(crate) async fn status_handler(
     Path((name, req_version)): Path<(String, ReqVersion)>,
     mut conn: DbConnection,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
 ) -> impl IntoResponse {
     (
         Extension(CachePolicy::NoStoreMustRevalidate),
-        [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
-        // We use an async block to emulate a try block so that we can apply the above CORS header
-        // and cache policy to both successful and failed responses
+        // We use an async block to emulate a try block so that we can apply the above
+        // cache policy to both successful and failed responses.
+        // CORS headers are added by the layer on the JSON API routes.
         async move {
-            let matched_release = match_version(&mut conn, &name, &req_version)
+            let matched_release = match_version(&mut conn, &metrics, &name, &req_version)
                 .await?
                 .assume_exact_name()?;
 
@@ -0,0 +1,4 @@
+// takes longer than the timeout override used in the builder tests.
+fn main() {
+    std::thread::sleep(std::time::Duration::from_secs(120));
+}
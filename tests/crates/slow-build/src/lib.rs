@@ -0,0 +1 @@
+//! A crate whose build script never finishes in time.